@@ -0,0 +1,21 @@
+use tauri::AppHandle;
+use tauri_plugin_autostart::ManagerExt;
+
+/// Register or unregister cldbar as a login item so the OS state matches
+/// `enabled`. Does nothing when it already matches.
+pub fn apply(app: &AppHandle, enabled: bool) -> Result<(), String> {
+    let autolaunch = app.autolaunch();
+    let current = autolaunch
+        .is_enabled()
+        .map_err(|e| format!("Failed to read launch on startup state: {}", e))?;
+    if current == enabled {
+        return Ok(());
+    }
+
+    let result = if enabled {
+        autolaunch.enable()
+    } else {
+        autolaunch.disable()
+    };
+    result.map_err(|e| format!("Failed to update launch on startup: {}", e))
+}
@@ -0,0 +1,397 @@
+use crate::commands::{self, AppState};
+use crate::currency;
+use crate::exclusions;
+use crate::history;
+use crate::profile::{self, AppSettings, Profile};
+use crate::providers::{Provider, UsageStats};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_notification::NotificationExt;
+
+/// Event emitted to every window with the combined stats of all enabled
+/// profiles after each pass of the loop.
+const STATS_UPDATED_EVENT: &str = "stats-updated";
+
+/// How often the refresh interval setting is re-read while sleeping.
+const MIN_INTERVAL_MS: u64 = 1000;
+
+/// Lower bound on the time between background passes. Each pass re-reads
+/// every profile's usage (a full session-file scan for Claude while its
+/// stats cache is stale), so it runs less often than the windows poll.
+const MIN_BACKGROUND_INTERVAL_MS: u64 = 15_000;
+
+/// How often rate-limit snapshots are recorded into the history.
+const RATE_LIMIT_POLL_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// How often each profile's daily usage is written to the history database.
+const USAGE_HISTORY_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Days of daily usage re-written on each history snapshot.
+const USAGE_HISTORY_DAYS: u32 = 7;
+
+/// How often the display-currency rate is refreshed when `fx_rate_url` is set.
+const FX_RATE_REFRESH_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Spawn the background refresh loop.
+/// Wakes every `refresh_interval_ms`, checks per-profile alerts, updates
+/// the tray icon's cost summary and emits `stats-updated`.
+pub fn spawn(app: AppHandle) {
+    thread::spawn(move || {
+        // profile id -> date (YYYY-MM-DD) the budget notification was last sent
+        let mut budget_notified: HashMap<String, String> = HashMap::new();
+        // profile ids whose usage is above the token alert threshold and were notified
+        let mut token_alerted: HashSet<String> = HashSet::new();
+        let mut last_rate_limit_poll: Option<Instant> = None;
+        let mut last_fx_refresh: Option<Instant> = None;
+        let mut last_history_snapshot: Option<Instant> = None;
+
+        loop {
+            let snapshots = snapshot_profiles(&app);
+            check_budgets(&app, &snapshots, &mut budget_notified);
+            check_token_alerts(&app, &snapshots, &mut token_alerted);
+            publish_combined_stats(&app, &snapshots);
+
+            if last_rate_limit_poll.is_none_or(|t| t.elapsed() >= RATE_LIMIT_POLL_INTERVAL) {
+                record_rate_limits(&app);
+                last_rate_limit_poll = Some(Instant::now());
+            }
+
+            if last_history_snapshot.is_none_or(|t| t.elapsed() >= USAGE_HISTORY_INTERVAL) {
+                record_usage_history(&app);
+                last_history_snapshot = Some(Instant::now());
+            }
+
+            if last_fx_refresh.is_none_or(|t| t.elapsed() >= FX_RATE_REFRESH_INTERVAL) {
+                refresh_fx_rate(&app);
+                last_fx_refresh = Some(Instant::now());
+            }
+
+            sleep_for_refresh_interval(&app);
+        }
+    });
+}
+
+/// Sleep until `refresh_interval_ms` (at least `MIN_BACKGROUND_INTERVAL_MS`)
+/// has passed, re-reading it every `MIN_INTERVAL_MS` so a shorter interval
+/// set meanwhile applies right away.
+fn sleep_for_refresh_interval(app: &AppHandle) {
+    let started = Instant::now();
+    loop {
+        let interval = app
+            .state::<AppState>()
+            .config
+            .lock()
+            .map(|c| c.settings.refresh_interval_ms)
+            .unwrap_or(5000);
+        let interval = Duration::from_millis(interval.max(MIN_BACKGROUND_INTERVAL_MS));
+        let elapsed = started.elapsed();
+        if elapsed >= interval {
+            return;
+        }
+        thread::sleep((interval - elapsed).min(Duration::from_millis(MIN_INTERVAL_MS)));
+    }
+}
+
+/// One enabled profile's usage, read once per pass of the loop and shared
+/// by the budget, alert and tray checks.
+struct ProfileSnapshot {
+    id: String,
+    /// Provider display name, for notification titles.
+    name: String,
+    /// Usage stats with the app settings applied, as the windows show them.
+    stats: UsageStats,
+    /// Input and output tokens used today; only read for profiles with a
+    /// daily budget.
+    used_today: Option<u64>,
+    /// Today's estimated spend in USD; see `commands::today_cost_usd`.
+    today_cost_usd: f64,
+}
+
+/// Read every enabled profile's usage, one thread per provider like
+/// `commands::collect_usage_stats`. Providers whose stats fail are left out.
+/// The config and provider locks are released before any provider is read.
+fn snapshot_profiles(app: &AppHandle) -> Vec<ProfileSnapshot> {
+    let state = app.state::<AppState>();
+
+    let (settings, profiles) = match state.config.lock() {
+        Ok(config) => {
+            let profiles: Vec<Profile> = config.profiles.iter().filter(|p| p.enabled).cloned().collect();
+            (config.settings.clone(), profiles)
+        }
+        Err(_) => return Vec::new(),
+    };
+    let targets: Vec<(Profile, Arc<dyn Provider>)> = match state.providers.lock() {
+        Ok(providers) => profiles
+            .into_iter()
+            .filter_map(|p| providers.get(&p.id).cloned().map(|provider| (p, provider)))
+            .collect(),
+        Err(_) => return Vec::new(),
+    };
+    let settings = &settings;
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = targets
+            .iter()
+            .map(|(profile, provider)| {
+                let provider = provider.as_ref();
+                scope.spawn(move || {
+                    let mut stats = match provider.get_usage_stats() {
+                        Ok(s) => s,
+                        Err(e) => {
+                            tracing::warn!("Skipping stats for {}: {}", profile.id, e);
+                            return None;
+                        }
+                    };
+                    let excluded = exclusions::excluded_project_sessions(provider, settings);
+                    commands::apply_stats_settings(&mut stats, &excluded, settings);
+
+                    let used_today = profile
+                        .daily_token_budget
+                        .and_then(|_| commands::compute_budget_status(provider, None).ok())
+                        .map(|s| s.used_today);
+                    let today_cost_usd = commands::today_cost_usd(provider, settings).unwrap_or_else(|e| {
+                        tracing::warn!("Skipping today's cost for {}: {}", profile.id, e);
+                        0.0
+                    });
+
+                    Some(ProfileSnapshot {
+                        id: profile.id.clone(),
+                        name: provider.name().to_string(),
+                        stats,
+                        used_today,
+                        today_cost_usd,
+                    })
+                })
+            })
+            .collect();
+        handles.into_iter().filter_map(|h| h.join().ok().flatten()).collect()
+    })
+}
+
+/// Push the combined stats of all enabled profiles to the windows and the
+/// tray icon.
+fn publish_combined_stats(app: &AppHandle, snapshots: &[ProfileSnapshot]) {
+    let settings = match app.state::<AppState>().config.lock() {
+        Ok(config) => config.settings.clone(),
+        Err(_) => return,
+    };
+
+    let all: Vec<UsageStats> = snapshots.iter().map(|s| s.stats.clone()).collect();
+    let stats = commands::combine_usage_stats(&all, &settings);
+
+    let today_usd: f64 = snapshots.iter().map(|s| s.today_cost_usd).sum();
+    update_tray_summary(app, today_usd, &settings);
+    if let Err(e) = app.emit(STATS_UPDATED_EVENT, &stats) {
+        tracing::warn!("Failed to emit {}: {}", STATS_UPDATED_EVENT, e);
+    }
+}
+
+/// Show today's combined cost in the tray tooltip, and next to the icon on
+/// platforms with tray titles (macOS).
+fn update_tray_summary(app: &AppHandle, today_usd: f64, settings: &AppSettings) {
+    let tray = match app.tray_by_id("main") {
+        Some(t) => t,
+        None => return,
+    };
+
+    let cost = currency::format_amount(currency::to_display(today_usd, settings), &settings.display_currency);
+    let _ = tray.set_tooltip(Some(format!("{} today", cost)));
+    let _ = tray.set_title(Some(cost));
+}
+
+/// Notify once per day for each profile whose daily token budget is exceeded.
+fn check_budgets(app: &AppHandle, snapshots: &[ProfileSnapshot], notified: &mut HashMap<String, String>) {
+    let budgets: HashMap<String, u64> = match app.state::<AppState>().config.lock() {
+        Ok(config) if config.settings.notifications_enabled => config
+            .profiles
+            .iter()
+            .filter(|p| p.enabled)
+            .filter_map(|p| p.daily_token_budget.map(|b| (p.id.clone(), b)))
+            .collect(),
+        _ => return,
+    };
+
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+
+    for snapshot in snapshots {
+        let (Some(&budget), Some(used_today)) = (budgets.get(&snapshot.id), snapshot.used_today) else {
+            continue;
+        };
+
+        let status = commands::BudgetStatus::new(used_today, Some(budget));
+        if !status.exceeded {
+            notified.remove(&snapshot.id);
+            continue;
+        }
+
+        if notified.get(&snapshot.id) == Some(&today) {
+            continue;
+        }
+
+        let over_pct = (status.used_today as f64 / budget.max(1) as f64 - 1.0) * 100.0;
+        let _ = app
+            .notification()
+            .builder()
+            .title(format!("{}: daily token budget exceeded", snapshot.name))
+            .body(format!(
+                "{} tokens used today, {:.0}% over your cap of {}.",
+                status.used_today, over_pct, budget
+            ))
+            .show();
+
+        notified.insert(snapshot.id.clone(), today.clone());
+    }
+}
+
+/// Notify when a profile's total tokens cross `token_alert_threshold`.
+/// Each profile is notified once until its usage drops back below the
+/// threshold (e.g. when the provider's reporting window rolls over).
+fn check_token_alerts(app: &AppHandle, snapshots: &[ProfileSnapshot], alerted: &mut HashSet<String>) {
+    let threshold = match app.state::<AppState>().config.lock() {
+        Ok(config) if config.settings.notifications_enabled => config.settings.token_alert_threshold,
+        _ => return,
+    };
+
+    // A zero threshold turns the alert off
+    if threshold == 0 {
+        return;
+    }
+
+    alerted.retain(|id| snapshots.iter().any(|s| &s.id == id));
+
+    for snapshot in snapshots {
+        let total = snapshot.stats.total_input_tokens + snapshot.stats.total_output_tokens;
+        if total < threshold {
+            alerted.remove(&snapshot.id);
+            continue;
+        }
+
+        if !alerted.insert(snapshot.id.clone()) {
+            continue;
+        }
+
+        let _ = app
+            .notification()
+            .builder()
+            .title(format!("{}: token alert", snapshot.name))
+            .body(format!(
+                "{} tokens used, over your alert threshold of {}.",
+                total, threshold
+            ))
+            .show();
+    }
+}
+
+/// Write recent daily usage of every enabled profile into the history database.
+/// Providers without a daily breakdown contribute their current totals as
+/// today's row.
+fn record_usage_history(app: &AppHandle) {
+    let state = app.state::<AppState>();
+
+    let ids: Vec<String> = match state.config.lock() {
+        Ok(config) => config
+            .profiles
+            .iter()
+            .filter(|p| p.enabled)
+            .map(|p| p.id.clone())
+            .collect(),
+        Err(_) => return,
+    };
+
+    let targets: Vec<(String, Arc<dyn Provider>)> = match state.providers.lock() {
+        Ok(providers) => ids
+            .into_iter()
+            .filter_map(|id| providers.get(&id).cloned().map(|provider| (id, provider)))
+            .collect(),
+        Err(_) => return,
+    };
+
+    for (id, provider) in targets {
+        // An empty week from a provider with daily data just means no usage
+        let result = if provider.has_daily_breakdown() {
+            provider.get_daily_usage(USAGE_HISTORY_DAYS)
+        } else {
+            provider.get_usage_stats().map(|stats| vec![history::today_from_stats(&stats)])
+        };
+        let days = match result {
+            Ok(days) if !days.is_empty() => days,
+            _ => continue,
+        };
+
+        if let Err(e) = history::record(&id, &days) {
+            tracing::warn!("Failed to record usage history for {}: {}", id, e);
+        }
+    }
+}
+
+/// Snapshot the rate-limit status of every enabled profile into the history.
+fn record_rate_limits(app: &AppHandle) {
+    let state = app.state::<AppState>();
+
+    let (profiles, settings) = match state.config.lock() {
+        Ok(config) => (
+            config
+                .profiles
+                .iter()
+                .filter(|p| p.enabled)
+                .cloned()
+                .collect::<Vec<_>>(),
+            config.settings.clone(),
+        ),
+        Err(_) => return,
+    };
+
+    // Fetch before locking the history so slow requests don't block readers
+    let snapshots: Vec<_> = profiles
+        .iter()
+        .map(|p| (p.id.clone(), commands::fetch_rate_limit_status(&state, p, &settings)))
+        .filter(|(_, status)| status.available)
+        .collect();
+
+    if snapshots.is_empty() {
+        return;
+    }
+
+    let mut history = match state.rate_limit_history.lock() {
+        Ok(h) => h,
+        Err(_) => return,
+    };
+    for (id, status) in snapshots {
+        history.record(&id, status);
+    }
+    if let Err(e) = history.save() {
+        tracing::warn!("{}", e);
+    }
+}
+
+/// Update the display-currency rate from `fx_rate_url`, if configured.
+fn refresh_fx_rate(app: &AppHandle) {
+    let state = app.state::<AppState>();
+
+    let (url, currency) = match state.config.lock() {
+        Ok(config) => match config.settings.fx_rate_url.clone() {
+            Some(url) => (url, config.settings.display_currency.clone()),
+            None => return,
+        },
+        Err(_) => return,
+    };
+
+    let rate = match currency::fetch_fx_rate(&url, &currency) {
+        Ok(r) => r,
+        Err(e) => {
+            tracing::warn!("{}", e);
+            return;
+        }
+    };
+
+    if let Ok(mut config) = state.config.lock() {
+        // Skip if the user changed currency while the request was in flight
+        if config.settings.display_currency == currency {
+            config.settings.fx_rate_usd_to_display = rate;
+            let _ = profile::save_config(&config);
+        }
+    };
+}
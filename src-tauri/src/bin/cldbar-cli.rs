@@ -0,0 +1,6 @@
+// Console binary for scripts and cron: prints usage and exits without
+// starting the tray app. See `cldbar-cli --help`.
+
+fn main() {
+    std::process::exit(cldbar_lib::run_cli())
+}
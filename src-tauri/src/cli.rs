@@ -0,0 +1,189 @@
+use crate::commands::{apply_stats_settings, build_provider, filtered_daily_usage};
+use crate::currency;
+use crate::exclusions;
+use crate::logs;
+use crate::profile::{self, AppSettings, Profile};
+use crate::providers::{DailyUsage, UsageStats};
+use serde::Serialize;
+
+const USAGE: &str = "Usage: cldbar-cli [--json] [--profile <id>] [--days <n>]
+
+Prints usage for every enabled profile, or only the given one.
+
+  --profile <id>  Only this profile (may be disabled)
+  --days <n>      Per-day usage for the last <n> days instead of totals
+  --json          Print JSON instead of a table";
+
+struct Options {
+    json: bool,
+    profile: Option<String>,
+    days: Option<u32>,
+}
+
+/// What gets printed for one profile: totals, or daily usage with `--days`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProfileReport {
+    profile_id: String,
+    profile_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats: Option<UsageStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    daily: Option<Vec<DailyUsage>>,
+}
+
+/// Run the headless CLI with `args` (without the program name), printing to
+/// stdout, and return the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    if args.iter().any(|a| a == "--help" || a == "-h") {
+        println!("{}", USAGE);
+        return 0;
+    }
+    let options = match parse_args(args) {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+
+    // Warnings go to the log file, keeping stdout clean for scripts
+    logs::init();
+
+    match report(&options) {
+        Ok(output) => {
+            println!("{}", output);
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        json: false,
+        profile: None,
+        days: None,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => options.json = true,
+            // Accepted for symmetry with --json; totals are the default
+            "--stats" => {}
+            "--profile" => {
+                let id = args.next().ok_or("--profile needs a profile id")?;
+                options.profile = Some(id.clone());
+            }
+            "--days" => {
+                let value = args.next().ok_or("--days needs a number of days")?;
+                let days = value
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|d| *d > 0)
+                    .ok_or_else(|| format!("Invalid --days value: {}", value))?;
+                options.days = Some(days);
+            }
+            other => return Err(format!("Unknown argument: {}", other)),
+        }
+    }
+
+    Ok(options)
+}
+
+/// Build the output for the selected profiles. With every profile selected,
+/// one that fails to load is skipped with a note on stderr.
+fn report(options: &Options) -> Result<String, String> {
+    let config = profile::load_config()?;
+
+    let profiles: Vec<&Profile> = match options.profile {
+        Some(ref id) => vec![config
+            .profiles
+            .iter()
+            .find(|p| &p.id == id)
+            .ok_or_else(|| format!("Profile not found: {}", id))?],
+        None => config.profiles.iter().filter(|p| p.enabled).collect(),
+    };
+
+    let mut reports = Vec::new();
+    for profile in profiles {
+        match profile_report(profile, &config.settings, options.days) {
+            Ok(r) => reports.push(r),
+            Err(e) if options.profile.is_none() => eprintln!("Skipping {}: {}", profile.id, e),
+            Err(e) => return Err(e),
+        }
+    }
+
+    if options.json {
+        serde_json::to_string_pretty(&reports).map_err(|e| format!("Failed to serialize usage: {}", e))
+    } else if options.days.is_some() {
+        Ok(daily_table(&reports))
+    } else {
+        Ok(totals_table(&reports, &config.settings))
+    }
+}
+
+fn profile_report(profile: &Profile, settings: &AppSettings, days: Option<u32>) -> Result<ProfileReport, String> {
+    let provider = build_provider(profile, settings)?;
+
+    let mut report = ProfileReport {
+        profile_id: profile.id.clone(),
+        profile_name: profile.name.clone(),
+        stats: None,
+        daily: None,
+    };
+
+    match days {
+        Some(days) => {
+            report.daily = Some(filtered_daily_usage(provider.as_ref(), days, settings)?);
+        }
+        None => {
+            let mut stats = provider.get_usage_stats()?;
+            let excluded = exclusions::excluded_project_sessions(provider.as_ref(), settings);
+            apply_stats_settings(&mut stats, &excluded, settings);
+            report.stats = Some(stats);
+        }
+    }
+
+    Ok(report)
+}
+
+fn totals_table(reports: &[ProfileReport], settings: &AppSettings) -> String {
+    let mut out = format!(
+        "{:<24} {:>14} {:>14} {:>14} {:>10} {:>12}",
+        "PROFILE", "INPUT", "OUTPUT", "CACHE READ", "MESSAGES", "COST"
+    );
+    for r in reports {
+        let Some(ref s) = r.stats else { continue };
+        out.push_str(&format!(
+            "\n{:<24} {:>14} {:>14} {:>14} {:>10} {:>12}",
+            r.profile_name,
+            s.total_input_tokens,
+            s.total_output_tokens,
+            s.total_cache_read_tokens,
+            s.total_messages,
+            currency::format_amount(s.display_cost, &settings.display_currency),
+        ));
+    }
+    out
+}
+
+fn daily_table(reports: &[ProfileReport]) -> String {
+    let mut out = format!(
+        "{:<24} {:<10} {:>14} {:>14} {:>10} {:>8}",
+        "PROFILE", "DATE", "INPUT", "OUTPUT", "MESSAGES", "SESSIONS"
+    );
+    for r in reports {
+        for d in r.daily.iter().flatten() {
+            out.push_str(&format!(
+                "\n{:<24} {:<10} {:>14} {:>14} {:>10} {:>8}",
+                r.profile_name, d.date, d.input_tokens, d.output_tokens, d.messages, d.sessions,
+            ));
+        }
+    }
+    out
+}
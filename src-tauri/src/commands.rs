@@ -1,18 +1,42 @@
+use crate::autostart;
+use crate::currency;
+use crate::exclusions;
+use crate::history;
+use crate::logs::{LogBuffer, LogEntry};
+use crate::export;
+use crate::pricing::{self, PricingTable};
 use crate::profile::{self, AppConfig, AppSettings, Profile};
 use crate::providers::claude::ClaudeProvider;
 use crate::providers::claude_api::ClaudeApiProvider;
 use crate::providers::gemini::GeminiProvider;
+use crate::providers::mock::MockProvider;
+use crate::providers::openai::OpenAiProvider;
 use crate::providers::zai::ZaiProvider;
 use crate::providers::zai_api::ZaiApiProvider;
-use crate::providers::{DailyUsage, Provider, RateLimitStatus, Session, UsageStats};
+use crate::providers::{
+    self, BillingMode, DailyModelUsage, DailyUsage, Diagnostics, ModelUsage, ProjectUsage, Provider,
+    ProviderError, RateLimitStatus, Session, UsageStats, WorkspaceUsage,
+};
+use crate::rate_history::RateLimitHistory;
+use crate::secrets;
+use crate::shortcut;
+use chrono::{Datelike, NaiveDate};
 use serde::Serialize;
 use std::collections::HashMap;
-use std::sync::Mutex;
-use tauri::State;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, State};
 
 pub struct AppState {
     pub config: Mutex<AppConfig>,
-    pub providers: Mutex<HashMap<String, Box<dyn Provider>>>,
+    /// Shared handles, so slow provider calls can run after the lock is released.
+    pub providers: Mutex<HashMap<String, Arc<dyn Provider>>>,
+    pub rate_limit_history: Mutex<RateLimitHistory>,
+    /// Why a configured profile has no provider (disabled or failed to construct),
+    /// so commands can report the cause instead of "Profile not found".
+    pub provider_errors: Mutex<HashMap<String, String>>,
+    pub logs: LogBuffer,
 }
 
 /// DTO that excludes the API key from frontend exposure.
@@ -26,6 +50,9 @@ pub struct ProfileInfo {
     pub enabled: bool,
     pub source_type: String,
     pub has_api_key: bool,
+    pub daily_token_budget: Option<u64>,
+    pub projects_subdir: String,
+    pub config_dirs: Vec<String>,
 }
 
 impl From<&Profile> for ProfileInfo {
@@ -37,11 +64,186 @@ impl From<&Profile> for ProfileInfo {
             config_dir: p.config_dir.clone(),
             enabled: p.enabled,
             source_type: p.source_type.clone(),
-            has_api_key: p.api_key.is_some(),
+            has_api_key: p.api_key.is_some() || p.api_key_in_keychain,
+            daily_token_budget: p.daily_token_budget,
+            projects_subdir: p.projects_subdir.clone(),
+            config_dirs: p.config_dirs.clone(),
         }
     }
 }
 
+/// Today's token usage measured against a profile's daily budget.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetStatus {
+    pub used_today: u64,
+    pub budget: Option<u64>,
+    pub remaining: Option<u64>,
+    pub exceeded: bool,
+}
+
+impl BudgetStatus {
+    pub fn new(used_today: u64, budget: Option<u64>) -> Self {
+        Self {
+            used_today,
+            budget,
+            remaining: budget.map(|b| b.saturating_sub(used_today)),
+            exceeded: budget.map(|b| used_today > b).unwrap_or(false),
+        }
+    }
+}
+
+/// Usage aggregated over one week or calendar month.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageRollup {
+    /// "2025-W07" for weeks (ISO), "2025-02" for months.
+    pub period_label: String,
+    /// First day of the period (YYYY-MM-DD).
+    pub period_start: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_write_tokens: u64,
+    /// Each day priced per model where the provider splits usage by model,
+    /// otherwise at the profile's average cost per token.
+    pub cost_usd: f64,
+    pub sessions: u32,
+    pub messages: u32,
+}
+
+/// Spend so far this calendar month and a linear projection to its end.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CostForecast {
+    pub month_to_date: f64,
+    pub projected_total: f64,
+    pub daily_average: f64,
+}
+
+/// Compute today's budget status from the provider's daily usage.
+/// A `None` budget is never exceeded.
+pub fn compute_budget_status(
+    provider: &dyn Provider,
+    budget: Option<u64>,
+) -> Result<BudgetStatus, ProviderError> {
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let used_today: u64 = provider
+        .get_daily_usage(1)?
+        .iter()
+        .filter(|d| d.date == today)
+        .map(|d| d.input_tokens + d.output_tokens)
+        .sum();
+
+    Ok(BudgetStatus::new(used_today, budget))
+}
+
+/// Construct the provider for a profile.
+pub fn build_provider(profile: &Profile, settings: &AppSettings) -> Result<Box<dyn Provider>, String> {
+    let active_threshold = Duration::from_secs(settings.active_threshold_secs);
+    let provider: Box<dyn Provider> = match (profile.provider_type.as_str(), profile.source_type.as_str()) {
+        ("claude", "api") => {
+            let key = profile.api_key.as_ref()
+                .ok_or_else(|| "API key is required for API source type".to_string())?;
+            Box::new(ClaudeApiProvider::new(
+                key.clone(),
+                settings.proxy_url.as_deref(),
+                Duration::from_secs(settings.api_cache_ttl_secs),
+            )?)
+        }
+        ("claude", _) => Box::new(ClaudeProvider::new(
+            profile.expanded_config_dir(),
+            profile.expanded_extra_dirs(),
+            profile.projects_subdir.clone(),
+            active_threshold,
+        )),
+        ("gemini", _) => Box::new(GeminiProvider::new(profile.expanded_config_dir(), active_threshold)),
+        ("openai", _) => Box::new(OpenAiProvider::new(profile.expanded_config_dir(), active_threshold)),
+        ("zai", "api") => {
+            let key = profile.api_key.as_ref()
+                .ok_or_else(|| "API key is required for z.ai API source type".to_string())?;
+            Box::new(ZaiApiProvider::new(key.clone(), settings.proxy_url.as_deref())?)
+        }
+        ("zai", _) => Box::new(ZaiProvider::new(profile.expanded_config_dir(), active_threshold)),
+        ("demo", _) => Box::new(MockProvider::new(profile.expanded_config_dir())),
+        (other, _) => return Err(format!("Unknown provider type: {}", other)),
+    };
+    Ok(provider)
+}
+
+/// Build providers for every enabled profile, plus the reason each profile
+/// without a provider has none.
+pub fn build_providers(
+    config: &AppConfig,
+) -> (HashMap<String, Arc<dyn Provider>>, HashMap<String, String>) {
+    let mut providers: HashMap<String, Arc<dyn Provider>> = HashMap::new();
+    let mut errors: HashMap<String, String> = HashMap::new();
+    for p in &config.profiles {
+        if !p.enabled {
+            errors.insert(p.id.clone(), format!("Profile {} is disabled", p.id));
+            continue;
+        }
+        match build_provider(p, &config.settings) {
+            Ok(provider) => {
+                providers.insert(p.id.clone(), Arc::from(provider));
+            }
+            Err(e) => {
+                tracing::warn!("Provider for {} failed to initialize: {}", p.id, e);
+                errors.insert(p.id.clone(), format!("Provider for {} failed to initialize: {}", p.id, e));
+            }
+        }
+    }
+    (providers, errors)
+}
+
+/// Error for a profile id with no registered provider: the recorded cause
+/// if the profile is configured, otherwise "Profile not found".
+fn missing_provider(state: &AppState, profile_id: &str) -> ProviderError {
+    ProviderError::NotConfigured(
+        state
+            .provider_errors
+            .lock()
+            .ok()
+            .and_then(|errors| errors.get(profile_id).cloned())
+            .unwrap_or_else(|| format!("Profile not found: {}", profile_id)),
+    )
+}
+
+/// Whether the profile reads local files from its `config_dir`.
+/// API and demo profiles don't need one.
+fn uses_config_dir(profile: &Profile) -> bool {
+    profile.source_type != "api" && profile.provider_type != "demo"
+}
+
+/// The first of a profile's config directories, main one first, that
+/// doesn't exist.
+fn missing_config_dir(profile: &Profile) -> Option<PathBuf> {
+    std::iter::once(profile.expanded_config_dir())
+        .chain(profile.expanded_extra_dirs())
+        .find(|dir| !dir.exists())
+}
+
+/// Snapshot of the current settings.
+fn current_settings(state: &AppState) -> Result<AppSettings, String> {
+    Ok(state
+        .config
+        .lock()
+        .map_err(|e| format!("Failed to lock config: {}", e))?
+        .settings
+        .clone())
+}
+
+/// Stamp sessions with the profile they were read from and drop excluded ones.
+fn tag_sessions(sessions: Vec<Session>, profile_id: &str, settings: &AppSettings) -> Vec<Session> {
+    exclusions::filter_sessions(sessions, settings)
+        .into_iter()
+        .map(|mut s| {
+            s.profile_id = profile_id.to_string();
+            s
+        })
+        .collect()
+}
+
 #[tauri::command]
 pub fn get_profiles(state: State<AppState>) -> Result<Vec<ProfileInfo>, String> {
     let config = state
@@ -52,71 +254,943 @@ pub fn get_profiles(state: State<AppState>) -> Result<Vec<ProfileInfo>, String>
 }
 
 #[tauri::command]
-pub fn add_profile(state: State<AppState>, profile: Profile) -> Result<(), String> {
-    let mut config = state
-        .config
+pub fn add_profile(state: State<AppState>, mut profile: Profile, force: Option<bool>) -> Result<(), String> {
+    let mut config = state
+        .config
+        .lock()
+        .map_err(|e| format!("Failed to lock config: {}", e))?;
+
+    if config.profiles.iter().any(|p| p.id == profile.id) {
+        return Err(format!("A profile with id '{}' already exists", profile.id));
+    }
+
+    // Two account profiles reading the same directory would double-count usage.
+    if uses_config_dir(&profile) && !force.unwrap_or(false) {
+        let dir = profile.expanded_config_dir();
+        if let Some(existing) = config.profiles.iter().find(|p| {
+            uses_config_dir(p)
+                && p.provider_type == profile.provider_type
+                && p.expanded_config_dir() == dir
+        }) {
+            return Err(format!(
+                "Profile '{}' already uses {} for {}; pass force to add it anyway",
+                existing.name, profile.config_dir, profile.provider_type
+            ));
+        }
+    }
+
+    // Validate config directories for account-type profiles
+    if uses_config_dir(&profile) {
+        if let Some(dir) = missing_config_dir(&profile) {
+            return Err(format!("Config directory does not exist: {}", dir.display()));
+        }
+    }
+
+    // Create and register the provider
+    let provider = build_provider(&profile, &config.settings)?;
+
+    let mut providers = state
+        .providers
+        .lock()
+        .map_err(|e| format!("Failed to lock providers: {}", e))?;
+
+    providers.insert(profile.id.clone(), Arc::from(provider));
+    if let Ok(mut errors) = state.provider_errors.lock() {
+        errors.remove(&profile.id);
+    }
+    profile.api_key_in_keychain = false;
+    config.profiles.push(profile);
+    secrets::secure_api_keys(&mut config);
+    profile::save_config(&config)?;
+
+    Ok(())
+}
+
+/// Replace an existing profile in place, keeping its id and position.
+/// A missing API key keeps the stored one, since the frontend never sees it.
+#[tauri::command]
+pub fn update_profile(state: State<AppState>, mut profile: Profile) -> Result<(), String> {
+    let mut config = state
+        .config
+        .lock()
+        .map_err(|e| format!("Failed to lock config: {}", e))?;
+
+    let index = config
+        .profiles
+        .iter()
+        .position(|p| p.id == profile.id)
+        .ok_or_else(|| format!("Profile not found: {}", profile.id))?;
+
+    if profile.api_key.is_none() {
+        profile.api_key = config.profiles[index].api_key.clone();
+        profile.api_key_in_keychain = config.profiles[index].api_key_in_keychain;
+    } else {
+        // A new key; secure_api_keys below overwrites the keychain entry
+        profile.api_key_in_keychain = false;
+    }
+
+    // Validate config directories for account-type profiles
+    if uses_config_dir(&profile) {
+        if let Some(dir) = missing_config_dir(&profile) {
+            return Err(format!("Config directory does not exist: {}", dir.display()));
+        }
+    }
+
+    sync_provider(&state, &profile, &config.settings)?;
+
+    config.profiles[index] = profile;
+    secrets::secure_api_keys(&mut config);
+    profile::save_config(&config)?;
+
+    Ok(())
+}
+
+/// Enable or disable a profile without a restart, building its provider
+/// when enabled and dropping it when disabled.
+#[tauri::command]
+pub fn set_profile_enabled(state: State<AppState>, id: String, enabled: bool) -> Result<(), String> {
+    let mut config = state
+        .config
+        .lock()
+        .map_err(|e| format!("Failed to lock config: {}", e))?;
+
+    let index = config
+        .profiles
+        .iter()
+        .position(|p| p.id == id)
+        .ok_or_else(|| format!("Profile not found: {}", id))?;
+
+    let mut profile = config.profiles[index].clone();
+    profile.enabled = enabled;
+    sync_provider(&state, &profile, &config.settings)?;
+
+    config.profiles[index] = profile;
+    profile::save_config(&config)?;
+
+    Ok(())
+}
+
+/// Register a fresh provider for an enabled profile, or remove the provider
+/// of a disabled one and record why it's missing.
+fn sync_provider(state: &AppState, profile: &Profile, settings: &AppSettings) -> Result<(), String> {
+    let mut providers = state
+        .providers
+        .lock()
+        .map_err(|e| format!("Failed to lock providers: {}", e))?;
+
+    if profile.enabled {
+        let provider = build_provider(profile, settings)?;
+        providers.insert(profile.id.clone(), Arc::from(provider));
+        if let Ok(mut errors) = state.provider_errors.lock() {
+            errors.remove(&profile.id);
+        }
+    } else {
+        providers.remove(&profile.id);
+        if let Ok(mut errors) = state.provider_errors.lock() {
+            errors.insert(profile.id.clone(), format!("Profile {} is disabled", profile.id));
+        }
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn remove_profile(state: State<AppState>, id: String) -> Result<(), String> {
+    let mut config = state
+        .config
+        .lock()
+        .map_err(|e| format!("Failed to lock config: {}", e))?;
+
+    let mut providers = state
+        .providers
+        .lock()
+        .map_err(|e| format!("Failed to lock providers: {}", e))?;
+
+    if config.profiles.iter().any(|p| p.id == id && p.api_key_in_keychain) {
+        if let Err(e) = secrets::delete_api_key(&id) {
+            tracing::warn!("{}", e);
+        }
+    }
+    config.profiles.retain(|p| p.id != id);
+    providers.remove(&id);
+    if let Ok(mut errors) = state.provider_errors.lock() {
+        errors.remove(&id);
+    }
+    profile::save_config(&config)?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn reorder_profiles(state: State<AppState>, ordered_ids: Vec<String>) -> Result<(), String> {
+    let mut config = state
+        .config
+        .lock()
+        .map_err(|e| format!("Failed to lock config: {}", e))?;
+
+    // Refuse orderings that don't cover exactly the existing profiles,
+    // so a stale frontend list can't silently drop or duplicate entries.
+    let existing: std::collections::HashSet<&str> =
+        config.profiles.iter().map(|p| p.id.as_str()).collect();
+    let requested: std::collections::HashSet<&str> =
+        ordered_ids.iter().map(|id| id.as_str()).collect();
+    if requested.len() != ordered_ids.len() || requested != existing {
+        return Err("Profile order does not match the existing profiles".to_string());
+    }
+
+    config.profiles.sort_by_key(|p| {
+        ordered_ids
+            .iter()
+            .position(|id| *id == p.id)
+            .unwrap_or(usize::MAX)
+    });
+    profile::save_config(&config)?;
+
+    Ok(())
+}
+
+/// Outcome of `import_config`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSummary {
+    pub added: u32,
+    pub skipped: u32,
+    /// Why each skipped profile was left out.
+    pub skip_reasons: Vec<String>,
+}
+
+/// Write the current config to `path` as a backup. With `redact_keys`, API
+/// keys are left out and have to be entered again after importing.
+#[tauri::command]
+pub fn export_config(state: State<AppState>, path: String, redact_keys: bool) -> Result<(), String> {
+    let mut backup = state
+        .config
+        .lock()
+        .map_err(|e| format!("Failed to lock config: {}", e))?
+        .clone();
+
+    // Keys travel in the file (or not at all); the keychain is per machine
+    for profile in &mut backup.profiles {
+        profile.api_key_in_keychain = false;
+        if redact_keys {
+            profile.api_key = None;
+        }
+    }
+
+    if let Some(parent) = std::path::Path::new(&path).parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+
+    let data = serde_json::to_string_pretty(&backup)
+        .map_err(|e| format!("Failed to serialize config: {}", e))?;
+    std::fs::write(&path, data).map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+/// Load the profiles from a config backup at `path`. With `merge`, they are
+/// appended and ids that already exist are skipped; otherwise they replace
+/// the current profiles. Settings are left as they are. Profiles that
+/// wouldn't load (unknown provider, missing directory or API key) are skipped.
+#[tauri::command]
+pub fn import_config(state: State<AppState>, path: String, merge: bool) -> Result<ImportSummary, String> {
+    let data = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let backup: AppConfig =
+        serde_json::from_str(&data).map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+
+    let mut config = state
+        .config
+        .lock()
+        .map_err(|e| format!("Failed to lock config: {}", e))?;
+
+    let mut profiles = if merge { config.profiles.clone() } else { Vec::new() };
+    let mut summary = ImportSummary {
+        added: 0,
+        skipped: 0,
+        skip_reasons: Vec::new(),
+    };
+
+    for mut profile in backup.profiles {
+        // A copy of config.json whose keys are in this machine's keychain
+        if profile.api_key_in_keychain && profile.api_key.is_none() {
+            profile.api_key = secrets::load_api_key(&profile.id).unwrap_or_else(|e| {
+                tracing::warn!("{}", e);
+                None
+            });
+        }
+        profile.api_key_in_keychain = false;
+
+        let problem = if profiles.iter().any(|p| p.id == profile.id) {
+            Some(format!("A profile with id '{}' already exists", profile.id))
+        } else if let Some(dir) = missing_config_dir(&profile).filter(|_| uses_config_dir(&profile)) {
+            Some(format!("Config directory does not exist: {}", dir.display()))
+        } else {
+            build_provider(&profile, &config.settings).err()
+        };
+
+        match problem {
+            Some(reason) => {
+                summary.skipped += 1;
+                summary.skip_reasons.push(format!("{}: {}", profile.name, reason));
+            }
+            None => {
+                summary.added += 1;
+                profiles.push(profile);
+            }
+        }
+    }
+
+    // Don't wipe the current profiles for a backup that yields none
+    if !merge && summary.added == 0 {
+        return Err(format!("No profiles in {} could be imported", path));
+    }
+
+    // Replaced profiles' keys would otherwise linger in the keychain
+    if !merge {
+        for old in config.profiles.iter().filter(|p| p.api_key_in_keychain) {
+            if let Err(e) = secrets::delete_api_key(&old.id) {
+                tracing::warn!("{}", e);
+            }
+        }
+    }
+
+    config.profiles = profiles;
+    secrets::secure_api_keys(&mut config);
+    profile::save_config(&config)?;
+
+    let (providers, errors) = build_providers(&config);
+    *state
+        .providers
+        .lock()
+        .map_err(|e| format!("Failed to lock providers: {}", e))? = providers;
+    if let Ok(mut provider_errors) = state.provider_errors.lock() {
+        *provider_errors = errors;
+    }
+
+    Ok(summary)
+}
+
+#[tauri::command]
+pub fn get_usage_stats(state: State<AppState>, profile_id: String) -> Result<UsageStats, ProviderError> {
+    let settings = current_settings(&state)?;
+
+    let providers = state
+        .providers
+        .lock()
+        .map_err(|e| format!("Failed to lock providers: {}", e))?;
+
+    let provider = providers
+        .get(&profile_id)
+        .ok_or_else(|| missing_provider(&state, &profile_id))?;
+
+    let mut stats = provider.get_usage_stats()?;
+    let excluded = exclusions::excluded_project_sessions(provider.as_ref(), &settings);
+    apply_stats_settings(&mut stats, &excluded, &settings);
+    Ok(stats)
+}
+
+/// Usage stats between two RFC 3339 timestamps. Providers that can't query
+/// an arbitrary window return their usual stats.
+#[tauri::command]
+pub fn get_usage_stats_range(
+    state: State<AppState>,
+    profile_id: String,
+    start: String,
+    end: String,
+) -> Result<UsageStats, ProviderError> {
+    let parse = |s: &str| {
+        chrono::DateTime::parse_from_rfc3339(s)
+            .map_err(|e| ProviderError::Other(format!("Invalid date '{}': {}", s, e)))
+    };
+    if parse(&start)? > parse(&end)? {
+        return Err(ProviderError::Other(format!("Start {} is after end {}", start, end)));
+    }
+
+    let settings = current_settings(&state)?;
+
+    let providers = state
+        .providers
+        .lock()
+        .map_err(|e| format!("Failed to lock providers: {}", e))?;
+
+    let provider = providers
+        .get(&profile_id)
+        .ok_or_else(|| missing_provider(&state, &profile_id))?;
+
+    let mut stats = provider.get_usage_stats_range(&start, &end)?;
+    // Only sessions last active within the window are in the ranged stats
+    let (from, to) = (parse(&start)?, parse(&end)?);
+    let excluded: Vec<Session> = exclusions::excluded_project_sessions(provider.as_ref(), &settings)
+        .into_iter()
+        .filter(|s| providers::parse_last_active(&s.last_active).is_some_and(|t| t >= from && t <= to))
+        .collect();
+    apply_stats_settings(&mut stats, &excluded, &settings);
+    Ok(stats)
+}
+
+/// Per-model usage sorted descending by `sort_by`: "cost" or "tokens"
+/// (input, output and cache combined). Ties are ordered by model name.
+#[tauri::command]
+pub fn get_model_breakdown(
+    state: State<AppState>,
+    profile_id: String,
+    sort_by: String,
+) -> Result<Vec<ModelUsage>, ProviderError> {
+    let tokens = |m: &ModelUsage| m.input_tokens + m.output_tokens + m.cache_read_tokens + m.cache_write_tokens;
+    let by_cost = match sort_by.as_str() {
+        "cost" => true,
+        "tokens" => false,
+        _ => return Err(ProviderError::Other(format!("Unknown sort key '{}'", sort_by))),
+    };
+
+    let stats = get_usage_stats(state, profile_id)?;
+    let mut models: Vec<ModelUsage> = stats.model_breakdown.into_values().collect();
+    models.sort_by(|a, b| {
+        let order = if by_cost {
+            b.cost_usd.total_cmp(&a.cost_usd)
+        } else {
+            tokens(b).cmp(&tokens(a))
+        };
+        order.then_with(|| a.model.cmp(&b.model))
+    });
+    Ok(models)
+}
+
+/// Drop the provider's cached data and fetch usage stats again.
+#[tauri::command]
+pub fn refresh_provider(state: State<AppState>, profile_id: String) -> Result<UsageStats, ProviderError> {
+    let settings = current_settings(&state)?;
+
+    let providers = state
+        .providers
+        .lock()
+        .map_err(|e| format!("Failed to lock providers: {}", e))?;
+
+    let provider = providers
+        .get(&profile_id)
+        .ok_or_else(|| missing_provider(&state, &profile_id))?;
+
+    provider.clear_cache();
+    let mut stats = provider.get_usage_stats()?;
+    let excluded = exclusions::excluded_project_sessions(provider.as_ref(), &settings);
+    apply_stats_settings(&mut stats, &excluded, &settings);
+    Ok(stats)
+}
+
+/// Exclusions, cache folding and display currency, in that order.
+/// `excluded` are the sessions of excluded projects the stats cover; see
+/// [`exclusions::excluded_project_sessions`].
+pub(crate) fn apply_stats_settings(stats: &mut UsageStats, excluded: &[Session], settings: &AppSettings) {
+    exclusions::filter_stats(stats, excluded, settings);
+    if settings.cache_counts_as_input {
+        stats.fold_cache_into_input();
+    }
+    currency::apply_display_currency(stats, settings);
+}
+
+/// The provider's daily usage for `days`, with exclusions and cache folding
+/// applied like [`apply_stats_settings`] does for totals.
+pub(crate) fn filtered_daily_usage(
+    provider: &dyn Provider,
+    days: u32,
+    settings: &AppSettings,
+) -> Result<Vec<DailyUsage>, ProviderError> {
+    let mut daily = provider.get_daily_usage(days)?;
+    // Per-model daily usage is only needed to take excluded models off
+    let daily_models = if settings.excluded_models.is_empty() {
+        Vec::new()
+    } else {
+        provider.get_daily_model_usage(days)?
+    };
+    let excluded = exclusions::excluded_project_sessions(provider, settings);
+    exclusions::filter_daily(&mut daily, &daily_models, &excluded, settings);
+    if settings.cache_counts_as_input {
+        daily.iter_mut().for_each(DailyUsage::fold_cache_into_input);
+    }
+    Ok(daily)
+}
+
+/// Everything the tray shows for one profile, fetched in a single call.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Dashboard {
+    pub stats: UsageStats,
+    pub active: Vec<Session>,
+    pub daily: Vec<DailyUsage>,
+    pub history: Vec<Session>,
+}
+
+/// Stats, active sessions, daily usage and session history for a profile,
+/// locking the providers map once instead of once per command.
+#[tauri::command]
+pub fn get_dashboard(
+    state: State<AppState>,
+    profile_id: String,
+    days: u32,
+    history_limit: u32,
+) -> Result<Dashboard, ProviderError> {
+    let settings = current_settings(&state)?;
+
+    let providers = state
+        .providers
+        .lock()
+        .map_err(|e| format!("Failed to lock providers: {}", e))?;
+
+    let provider = providers
+        .get(&profile_id)
+        .ok_or_else(|| missing_provider(&state, &profile_id))?;
+
+    let mut stats = provider.get_usage_stats()?;
+    let excluded = exclusions::excluded_project_sessions(provider.as_ref(), &settings);
+    apply_stats_settings(&mut stats, &excluded, &settings);
+
+    Ok(Dashboard {
+        stats,
+        active: tag_sessions(provider.get_active_sessions()?, &profile_id, &settings),
+        daily: filtered_daily_usage(provider.as_ref(), days, &settings)?,
+        history: tag_sessions(provider.get_session_history(history_limit)?, &profile_id, &settings),
+    })
+}
+
+#[tauri::command]
+pub fn get_active_sessions(
+    state: State<AppState>,
+    profile_id: String,
+) -> Result<Vec<Session>, ProviderError> {
+    let settings = current_settings(&state)?;
+
+    let providers = state
+        .providers
+        .lock()
+        .map_err(|e| format!("Failed to lock providers: {}", e))?;
+
+    let provider = providers
+        .get(&profile_id)
+        .ok_or_else(|| missing_provider(&state, &profile_id))?;
+
+    Ok(tag_sessions(provider.get_active_sessions()?, &profile_id, &settings))
+}
+
+#[tauri::command]
+pub fn get_all_active_sessions(state: State<AppState>) -> Result<Vec<Session>, String> {
+    let config = state
+        .config
+        .lock()
+        .map_err(|e| format!("Failed to lock config: {}", e))?;
+
+    let providers = state
+        .providers
+        .lock()
+        .map_err(|e| format!("Failed to lock providers: {}", e))?;
+
+    let mut sessions = Vec::new();
+
+    for profile in config.profiles.iter().filter(|p| p.enabled) {
+        if let Some(provider) = providers.get(&profile.id) {
+            // Providers that fail contribute nothing
+            match provider.get_active_sessions() {
+                Ok(active) => sessions.extend(tag_sessions(active, &profile.id, &config.settings)),
+                Err(e) => tracing::warn!("Skipping active sessions for {}: {}", profile.id, e),
+            }
+        }
+    }
+
+    // Providers format last_active differently, so compare parsed times;
+    // unparseable ones sort last
+    sessions.sort_by_key(|s| std::cmp::Reverse(providers::parse_last_active(&s.last_active)));
+    Ok(sessions)
+}
+
+/// Active sessions across all enabled profiles, for the tray badge.
+/// Cheaper than `get_all_active_sessions`, but can't apply project
+/// exclusions since sessions aren't parsed.
+#[tauri::command]
+pub fn get_active_session_count(state: State<AppState>) -> Result<u32, String> {
+    let config = state
+        .config
+        .lock()
+        .map_err(|e| format!("Failed to lock config: {}", e))?;
+
+    let providers = state
+        .providers
+        .lock()
+        .map_err(|e| format!("Failed to lock providers: {}", e))?;
+
+    let mut count = 0;
+    for profile in config.profiles.iter().filter(|p| p.enabled) {
+        if let Some(provider) = providers.get(&profile.id) {
+            match provider.active_session_count() {
+                Ok(n) => count += n,
+                Err(e) => tracing::warn!("Skipping active session count for {}: {}", profile.id, e),
+            }
+        }
+    }
+
+    Ok(count)
+}
+
+#[tauri::command]
+pub fn get_daily_usage(
+    state: State<AppState>,
+    profile_id: String,
+    days: u32,
+) -> Result<Vec<DailyUsage>, ProviderError> {
+    let settings = current_settings(&state)?;
+
+    let providers = state
+        .providers
+        .lock()
+        .map_err(|e| format!("Failed to lock providers: {}", e))?;
+
+    let provider = providers
+        .get(&profile_id)
+        .ok_or_else(|| missing_provider(&state, &profile_id))?;
+
+    filtered_daily_usage(provider.as_ref(), days, &settings)
+}
+
+/// Per-model daily usage, newest first, for charting the model mix over time.
+#[tauri::command]
+pub fn get_daily_model_usage(
+    state: State<AppState>,
+    profile_id: String,
+    days: u32,
+) -> Result<Vec<DailyModelUsage>, ProviderError> {
+    let settings = current_settings(&state)?;
+
+    let providers = state
+        .providers
+        .lock()
+        .map_err(|e| format!("Failed to lock providers: {}", e))?;
+
+    let provider = providers
+        .get(&profile_id)
+        .ok_or_else(|| missing_provider(&state, &profile_id))?;
+
+    Ok(exclusions::filter_daily_models(provider.get_daily_model_usage(days)?, &settings))
+}
+
+/// Daily usage stored by the background snapshots, for `from..=to`
+/// (YYYY-MM-DD), newest first. Covers ranges the provider no longer retains.
+#[tauri::command]
+pub fn get_usage_history(
+    state: State<AppState>,
+    profile_id: String,
+    from: String,
+    to: String,
+) -> Result<Vec<DailyUsage>, String> {
+    let settings = current_settings(&state)?;
+
+    let mut daily = history::query(&profile_id, &from, &to)?;
+    if settings.cache_counts_as_input {
+        daily.iter_mut().for_each(DailyUsage::fold_cache_into_input);
+    }
+    Ok(daily)
+}
+
+#[tauri::command]
+pub fn get_usage_sparkline(
+    state: State<AppState>,
+    profile_id: String,
+    days: u32,
+) -> Result<Vec<u64>, ProviderError> {
+    let settings = current_settings(&state)?;
+
+    let providers = state
+        .providers
+        .lock()
+        .map_err(|e| format!("Failed to lock providers: {}", e))?;
+
+    let provider = providers
+        .get(&profile_id)
+        .ok_or_else(|| missing_provider(&state, &profile_id))?;
+
+    let totals: HashMap<String, u64> = filtered_daily_usage(provider.as_ref(), days, &settings)?
+        .into_iter()
+        .map(|d| (d.date.clone(), d.total_tokens()))
+        .collect();
+
+    // Oldest to newest, with days that have no usage filled with zero
+    let today = chrono::Local::now().date_naive();
+    Ok((0..days as i64)
+        .rev()
+        .map(|i| {
+            let date = (today - chrono::Duration::days(i)).format("%Y-%m-%d").to_string();
+            totals.get(&date).copied().unwrap_or(0)
+        })
+        .collect())
+}
+
+/// The profile's estimated cost per input/output token, used to price days
+/// the provider has no per-model split for.
+fn cost_per_token(stats: &UsageStats) -> f64 {
+    let total_tokens = stats.total_input_tokens + stats.total_output_tokens;
+    if total_tokens > 0 {
+        stats.estimated_cost_usd / total_tokens as f64
+    } else {
+        0.0
+    }
+}
+
+/// Unrounded cost of each of `daily` (unfolded), summed from the provider's
+/// per-model rows for that day. Rows are scaled down to the day's tokens
+/// when excluded projects took some off; days without per-model rows fall
+/// back to the profile's average cost per token.
+fn daily_costs(
+    provider: &dyn Provider,
+    daily: &[DailyUsage],
+    days: u32,
+    settings: &AppSettings,
+) -> Result<Vec<f64>, ProviderError> {
+    // date -> (cost, tokens) of the models that aren't excluded
+    let mut by_date: HashMap<String, (f64, u64)> = HashMap::new();
+    for u in exclusions::filter_daily_models(provider.get_daily_model_usage(days)?, settings) {
+        let entry = by_date.entry(u.date.clone()).or_default();
+        entry.0 += u.cost_usd;
+        entry.1 += u.input_tokens + u.output_tokens + u.cache_read_tokens + u.cache_write_tokens;
+    }
+
+    // Only needed for days the per-model rows don't cover
+    let mut fallback_rate = None;
+    daily
+        .iter()
+        .map(|day| match by_date.get(&day.date) {
+            Some(&(cost, tokens)) if tokens > 0 => Ok(cost * (day.total_tokens() as f64 / tokens as f64).min(1.0)),
+            _ => {
+                let rate = match fallback_rate {
+                    Some(rate) => rate,
+                    None => *fallback_rate.insert(cost_per_token(&provider.get_usage_stats()?)),
+                };
+                Ok((day.input_tokens + day.output_tokens) as f64 * rate)
+            }
+        })
+        .collect()
+}
+
+/// First day of the week (Monday) or month containing `date`.
+fn period_start(date: NaiveDate, granularity: &str) -> NaiveDate {
+    match granularity {
+        "week" => date - chrono::Duration::days(date.weekday().num_days_from_monday() as i64),
+        _ => date.with_day(1).unwrap_or(date),
+    }
+}
+
+/// Weekly or monthly rollups for a profile. `period` ("week" or "month")
+/// and `count` may also be passed as `granularity` and `periods`; `count`
+/// defaults to 12.
+#[tauri::command]
+pub fn get_usage_rollup(
+    state: State<AppState>,
+    profile_id: String,
+    period: Option<String>,
+    count: Option<u32>,
+    granularity: Option<String>,
+    periods: Option<u32>,
+) -> Result<Vec<UsageRollup>, ProviderError> {
+    let period = period
+        .or(granularity)
+        .ok_or_else(|| "Missing period (expected week or month)".to_string())?;
+    let count = count.or(periods).unwrap_or(12);
+
+    let settings = current_settings(&state)?;
+
+    let provider = state
+        .providers
+        .lock()
+        .map_err(|e| format!("Failed to lock providers: {}", e))?
+        .get(&profile_id)
+        .cloned()
+        .ok_or_else(|| missing_provider(&state, &profile_id))?;
+
+    usage_rollup(provider.as_ref(), &settings, &period, count)
+}
+
+/// The last `count` weeks (ISO, labelled "2025-W07") or calendar months
+/// ("2025-02") of the provider's daily usage, oldest first. The current
+/// period is partial and periods without usage are zero-filled.
+fn usage_rollup(
+    provider: &dyn Provider,
+    settings: &AppSettings,
+    period: &str,
+    count: u32,
+) -> Result<Vec<UsageRollup>, ProviderError> {
+    if period != "week" && period != "month" {
+        return Err(format!("Unknown period: {} (expected week or month)", period).into());
+    }
+
+    // Period starts, oldest first; the last one is the current (partial) period
+    let today = chrono::Local::now().date_naive();
+    let mut starts = vec![period_start(today, period)];
+    for _ in 1..count.max(1) {
+        let prev = *starts.last().unwrap_or(&today) - chrono::Duration::days(1);
+        starts.push(period_start(prev, period));
+    }
+    starts.reverse();
+
+    let days = (today - starts[0]).num_days() as u32 + 1;
+    // Priced before cache folding, so cache reads don't count at the input rate
+    let unfolded = AppSettings {
+        cache_counts_as_input: false,
+        ..settings.clone()
+    };
+    let mut daily = filtered_daily_usage(provider, days, &unfolded)?;
+    let costs = daily_costs(provider, &daily, days, settings)?;
+    if settings.cache_counts_as_input {
+        daily.iter_mut().for_each(DailyUsage::fold_cache_into_input);
+    }
+
+    let mut rollups: Vec<UsageRollup> = starts
+        .iter()
+        .map(|start| UsageRollup {
+            period_label: match period {
+                "week" => start.format("%G-W%V").to_string(),
+                _ => start.format("%Y-%m").to_string(),
+            },
+            period_start: start.format("%Y-%m-%d").to_string(),
+            input_tokens: 0,
+            output_tokens: 0,
+            cache_read_tokens: 0,
+            cache_write_tokens: 0,
+            cost_usd: 0.0,
+            sessions: 0,
+            messages: 0,
+        })
+        .collect();
+
+    for (day, cost) in daily.iter().zip(costs) {
+        let date = match NaiveDate::parse_from_str(&day.date, "%Y-%m-%d") {
+            Ok(d) => d,
+            Err(_) => continue,
+        };
+        let start = period_start(date, period);
+        if let Some(idx) = starts.iter().position(|s| *s == start) {
+            let r = &mut rollups[idx];
+            r.cost_usd += cost;
+            r.input_tokens += day.input_tokens;
+            r.output_tokens += day.output_tokens;
+            r.cache_read_tokens += day.cache_read_tokens;
+            r.cache_write_tokens += day.cache_write_tokens;
+            r.sessions += day.sessions;
+            r.messages += day.messages;
+        }
+    }
+
+    for r in &mut rollups {
+        r.cost_usd = (r.cost_usd * 100.0).round() / 100.0;
+    }
+
+    Ok(rollups)
+}
+
+/// Project this month's spend from its daily costs so far, each day priced
+/// per model. Days are counted from the first one with usage, so a profile
+/// added mid-month isn't diluted by the days before it; a month without
+/// usage, or on a subscription, forecasts zero.
+#[tauri::command]
+pub fn get_cost_forecast(state: State<AppState>, profile_id: String) -> Result<CostForecast, ProviderError> {
+    let settings = current_settings(&state)?;
+
+    let providers = state
+        .providers
         .lock()
-        .map_err(|e| format!("Failed to lock config: {}", e))?;
+        .map_err(|e| format!("Failed to lock providers: {}", e))?;
 
-    // Validate config directory for account-type profiles
-    if profile.source_type != "api" {
-        let dir = std::path::Path::new(&profile.config_dir);
-        if !dir.exists() {
-            return Err(format!("Config directory does not exist: {}", profile.config_dir));
-        }
+    let provider = providers
+        .get(&profile_id)
+        .ok_or_else(|| missing_provider(&state, &profile_id))?;
+
+    let today = chrono::Local::now().date_naive();
+    let month_start = period_start(today, "month");
+    let month_end = month_start
+        .checked_add_months(chrono::Months::new(1))
+        .map(|next| next - chrono::Duration::days(1))
+        .unwrap_or(today);
+
+    // Flat-rate plans pay nothing per token
+    if provider.billing_mode() == BillingMode::Subscription {
+        return Ok(CostForecast::default());
     }
 
-    // Create and register the provider
-    let provider: Box<dyn Provider> = match (profile.provider_type.as_str(), profile.source_type.as_str()) {
-        ("claude", "api") => {
-            let key = profile.api_key.as_ref()
-                .ok_or_else(|| "API key is required for API source type".to_string())?;
-            Box::new(ClaudeApiProvider::new(key.clone()))
-        }
-        ("claude", _) => Box::new(ClaudeProvider::new(profile.config_dir.clone().into())),
-        ("gemini", _) => Box::new(GeminiProvider::new(profile.config_dir.clone().into())),
-        ("zai", "api") => {
-            let key = profile.api_key.as_ref()
-                .ok_or_else(|| "API key is required for z.ai API source type".to_string())?;
-            Box::new(ZaiApiProvider::new(key.clone()))
+    let mut first_day: Option<NaiveDate> = None;
+    let mut month_to_date = 0.0;
+    let usage = exclusions::filter_daily_models(provider.get_daily_model_usage(today.day())?, &settings);
+    for u in usage {
+        let date = match NaiveDate::parse_from_str(&u.date, "%Y-%m-%d") {
+            Ok(d) if d >= month_start && d <= today => d,
+            _ => continue,
+        };
+        if u.input_tokens + u.output_tokens == 0 {
+            continue;
         }
-        ("zai", _) => Box::new(ZaiProvider::new(profile.config_dir.clone().into())),
-        (other, _) => return Err(format!("Unknown provider type: {}", other)),
+        first_day = Some(first_day.map_or(date, |f| f.min(date)));
+        month_to_date += u.cost_usd;
+    }
+    let Some(first_day) = first_day else {
+        return Ok(CostForecast::default());
     };
 
-    let mut providers = state
+    // Today counts as a whole day even though it's still under way
+    let elapsed = (today - first_day).num_days() + 1;
+    let daily_average = month_to_date / elapsed as f64;
+    let projected_total = daily_average * ((month_end - first_day).num_days() + 1) as f64;
+
+    let cents = |v: f64| (v * 100.0).round() / 100.0;
+    Ok(CostForecast {
+        month_to_date: cents(month_to_date),
+        projected_total: cents(projected_total),
+        daily_average: cents(daily_average),
+    })
+}
+
+#[tauri::command]
+pub fn get_session_history(
+    state: State<AppState>,
+    profile_id: String,
+    limit: u32,
+) -> Result<Vec<Session>, ProviderError> {
+    let settings = current_settings(&state)?;
+
+    let providers = state
         .providers
         .lock()
         .map_err(|e| format!("Failed to lock providers: {}", e))?;
 
-    providers.insert(profile.id.clone(), provider);
-    config.profiles.push(profile);
-    profile::save_config(&config)?;
+    let provider = providers
+        .get(&profile_id)
+        .ok_or_else(|| missing_provider(&state, &profile_id))?;
 
-    Ok(())
+    Ok(tag_sessions(provider.get_session_history(limit)?, &profile_id, &settings))
 }
 
+/// The `limit` most expensive projects across the whole session history.
+/// Providers without session data (the API sources) return nothing.
 #[tauri::command]
-pub fn remove_profile(state: State<AppState>, id: String) -> Result<(), String> {
-    let mut config = state
-        .config
-        .lock()
-        .map_err(|e| format!("Failed to lock config: {}", e))?;
+pub fn get_project_breakdown(
+    state: State<AppState>,
+    profile_id: String,
+    limit: u32,
+) -> Result<Vec<ProjectUsage>, ProviderError> {
+    let settings = current_settings(&state)?;
 
-    let mut providers = state
+    let providers = state
         .providers
         .lock()
         .map_err(|e| format!("Failed to lock providers: {}", e))?;
 
-    config.profiles.retain(|p| p.id != id);
-    providers.remove(&id);
-    profile::save_config(&config)?;
+    let provider = providers
+        .get(&profile_id)
+        .ok_or_else(|| missing_provider(&state, &profile_id))?;
 
-    Ok(())
+    let sessions = tag_sessions(provider.get_session_history(u32::MAX)?, &profile_id, &settings);
+    Ok(providers::project_breakdown(sessions, limit))
 }
 
+/// Usage per workspace of the organization, and per API key with
+/// `by_api_key`. Only the Claude Admin API source has workspaces.
 #[tauri::command]
-pub fn get_usage_stats(state: State<AppState>, profile_id: String) -> Result<UsageStats, String> {
+pub fn get_workspace_breakdown(
+    state: State<AppState>,
+    profile_id: String,
+    by_api_key: Option<bool>,
+) -> Result<Vec<WorkspaceUsage>, ProviderError> {
     let providers = state
         .providers
         .lock()
@@ -124,16 +1198,20 @@ pub fn get_usage_stats(state: State<AppState>, profile_id: String) -> Result<Usa
 
     let provider = providers
         .get(&profile_id)
-        .ok_or_else(|| format!("Profile not found: {}", profile_id))?;
+        .ok_or_else(|| missing_provider(&state, &profile_id))?;
 
-    provider.get_usage_stats()
+    provider.get_workspace_breakdown(by_api_key.unwrap_or(false))
 }
 
 #[tauri::command]
-pub fn get_active_sessions(
+pub fn get_top_sessions(
     state: State<AppState>,
     profile_id: String,
-) -> Result<Vec<Session>, String> {
+    days: u32,
+    limit: u32,
+) -> Result<Vec<Session>, ProviderError> {
+    let settings = current_settings(&state)?;
+
     let providers = state
         .providers
         .lock()
@@ -141,17 +1219,25 @@ pub fn get_active_sessions(
 
     let provider = providers
         .get(&profile_id)
-        .ok_or_else(|| format!("Profile not found: {}", profile_id))?;
+        .ok_or_else(|| missing_provider(&state, &profile_id))?;
 
-    provider.get_active_sessions()
+    Ok(tag_sessions(provider.get_top_sessions(days, limit)?, &profile_id, &settings))
 }
 
+/// Raw provider data behind the aggregated stats, for debugging.
+/// Only available when `debug_mode` is enabled in settings.
 #[tauri::command]
-pub fn get_daily_usage(
+pub fn debug_raw_usage(
     state: State<AppState>,
     profile_id: String,
     days: u32,
-) -> Result<Vec<DailyUsage>, String> {
+) -> Result<serde_json::Value, ProviderError> {
+    if !current_settings(&state)?.debug_mode {
+        return Err(ProviderError::NotConfigured(
+            "Debug mode is disabled in settings".to_string(),
+        ));
+    }
+
     let providers = state
         .providers
         .lock()
@@ -159,17 +1245,15 @@ pub fn get_daily_usage(
 
     let provider = providers
         .get(&profile_id)
-        .ok_or_else(|| format!("Profile not found: {}", profile_id))?;
+        .ok_or_else(|| missing_provider(&state, &profile_id))?;
 
-    provider.get_daily_usage(days)
+    provider.debug_raw_usage(days)
 }
 
 #[tauri::command]
-pub fn get_session_history(
-    state: State<AppState>,
-    profile_id: String,
-    limit: u32,
-) -> Result<Vec<Session>, String> {
+pub fn get_known_models(state: State<AppState>, profile_id: String) -> Result<Vec<String>, ProviderError> {
+    let settings = current_settings(&state)?;
+
     let providers = state
         .providers
         .lock()
@@ -177,9 +1261,36 @@ pub fn get_session_history(
 
     let provider = providers
         .get(&profile_id)
-        .ok_or_else(|| format!("Profile not found: {}", profile_id))?;
+        .ok_or_else(|| missing_provider(&state, &profile_id))?;
+
+    Ok(exclusions::filter_models(provider.get_known_models()?, &settings))
+}
+
+/// Cost in USD of the given token counts for a model, priced the way the
+/// provider prices its own usage. Providers without cache pricing ignore
+/// the cache counts.
+#[tauri::command]
+pub fn estimate_cost(
+    provider_type: String,
+    model: String,
+    input: u64,
+    output: u64,
+    cache_read: u64,
+    cache_write: u64,
+) -> Result<f64, String> {
+    match provider_type.as_str() {
+        "claude" => Ok(ClaudeProvider::estimate_cost(&model, input, output, cache_read, cache_write)),
+        "gemini" => Ok(GeminiProvider::estimate_cost(&model, input, output, cache_read)),
+        "openai" => Ok(OpenAiProvider::estimate_cost(&model, input, output, cache_read)),
+        "zai" => Ok(ZaiProvider::estimate_cost(&model, input, output)),
+        other => Err(format!("Cost estimates are not supported for {}", other)),
+    }
+}
 
-    provider.get_session_history(limit)
+/// The pricing rules in use, built-in or from pricing.json.
+#[tauri::command]
+pub fn get_pricing_table() -> PricingTable {
+    pricing::table().clone()
 }
 
 #[tauri::command]
@@ -192,20 +1303,75 @@ pub fn get_settings(state: State<AppState>) -> Result<AppSettings, String> {
 }
 
 #[tauri::command]
-pub fn update_settings(state: State<AppState>, settings: AppSettings) -> Result<(), String> {
+pub fn update_settings(
+    app: AppHandle,
+    state: State<AppState>,
+    mut settings: AppSettings,
+) -> Result<(), String> {
+    // Pick up a newly configured rate endpoint right away instead of
+    // waiting for the background refresh; keep the given rate on failure.
+    if let Some(ref url) = settings.fx_rate_url {
+        if let Ok(rate) = currency::fetch_fx_rate(url, &settings.display_currency) {
+            settings.fx_rate_usd_to_display = rate;
+        }
+    }
+
+    // Refuse a malformed proxy rather than leaving every API profile broken
+    providers::http_client(Duration::from_secs(10), settings.proxy_url.as_deref())?;
+
     let mut config = state
         .config
         .lock()
         .map_err(|e| format!("Failed to lock config: {}", e))?;
 
+    // The window position is tracked by the backend; the frontend's copy may be stale
+    settings.window_x = config.settings.window_x;
+    settings.window_y = config.settings.window_y;
+
+    // Keep the stored flag in sync with the OS, so a failed change isn't saved
+    if config.settings.launch_on_startup != settings.launch_on_startup {
+        autostart::apply(&app, settings.launch_on_startup)?;
+    }
+
+    // A shortcut that can't be registered (e.g. taken by another app) is
+    // reported and the previous one restored
+    if config.settings.toggle_shortcut != settings.toggle_shortcut {
+        if let Err(e) = shortcut::apply(&app, settings.toggle_shortcut.as_deref()) {
+            if let Err(restore) = shortcut::apply(&app, config.settings.toggle_shortcut.as_deref()) {
+                tracing::warn!("{}", restore);
+            }
+            return Err(e);
+        }
+    }
+
+    let rebuild = config.settings.active_threshold_secs != settings.active_threshold_secs
+        || config.settings.proxy_url != settings.proxy_url
+        || config.settings.api_cache_ttl_secs != settings.api_cache_ttl_secs;
     config.settings = settings;
     profile::save_config(&config)?;
 
+    // Providers hold the active threshold, HTTP client and cache TTL, so swap in fresh ones
+    if rebuild {
+        let (providers, errors) = build_providers(&config);
+        *state
+            .providers
+            .lock()
+            .map_err(|e| format!("Failed to lock providers: {}", e))? = providers;
+        if let Ok(mut provider_errors) = state.provider_errors.lock() {
+            *provider_errors = errors;
+        }
+    }
+
     Ok(())
 }
 
-#[tauri::command]
-pub fn get_all_usage_stats(state: State<AppState>) -> Result<Vec<UsageStats>, String> {
+/// Fetch usage stats for every enabled profile, keyed by profile id.
+/// With `include_disabled`, disabled profiles are read too, building their
+/// providers on demand. Providers that fail to load stats are skipped.
+fn collect_usage_stats(
+    state: &AppState,
+    include_disabled: bool,
+) -> Result<Vec<(String, UsageStats)>, String> {
     let config = state
         .config
         .lock()
@@ -216,20 +1382,51 @@ pub fn get_all_usage_stats(state: State<AppState>) -> Result<Vec<UsageStats>, St
         .lock()
         .map_err(|e| format!("Failed to lock providers: {}", e))?;
 
-    let mut all_stats = Vec::new();
-
-    for profile in &config.profiles {
-        if !profile.enabled {
-            continue;
+    // Disabled profiles aren't registered at startup
+    let mut on_demand: HashMap<&str, Arc<dyn Provider>> = HashMap::new();
+    if include_disabled {
+        for profile in config.profiles.iter().filter(|p| !p.enabled) {
+            if let Ok(p) = build_provider(profile, &config.settings) {
+                on_demand.insert(&profile.id, Arc::from(p));
+            }
         }
+    }
 
-        if let Some(provider) = providers.get(&profile.id) {
-            match provider.get_usage_stats() {
-                Ok(stats) => all_stats.push(stats),
-                Err(_) => {
-                    // Skip providers that fail to load stats
-                    continue;
-                }
+    let targets: Vec<(&str, &dyn Provider)> = config
+        .profiles
+        .iter()
+        .filter(|p| p.enabled || include_disabled)
+        .filter_map(|p| {
+            let provider = providers.get(&p.id).or_else(|| on_demand.get(p.id.as_str()))?;
+            Some((p.id.as_str(), provider.as_ref()))
+        })
+        .collect();
+
+    // One thread per provider so network-bound providers don't queue behind each other
+    let settings = &config.settings;
+    let results: Vec<_> = std::thread::scope(|scope| {
+        let handles: Vec<_> = targets
+            .iter()
+            .map(|&(id, provider)| {
+                scope.spawn(move || {
+                    let stats = provider.get_usage_stats();
+                    (id, stats.map(|s| (s, exclusions::excluded_project_sessions(provider, settings))))
+                })
+            })
+            .collect();
+        handles.into_iter().filter_map(|h| h.join().ok()).collect()
+    });
+
+    let mut all_stats = Vec::new();
+    for (id, result) in results {
+        match result {
+            Ok((mut stats, excluded)) => {
+                apply_stats_settings(&mut stats, &excluded, &config.settings);
+                all_stats.push((id.to_string(), stats));
+            }
+            Err(e) => {
+                // Skip providers that fail to load stats
+                tracing::warn!("Skipping stats for {}: {}", id, e);
             }
         }
     }
@@ -238,84 +1435,444 @@ pub fn get_all_usage_stats(state: State<AppState>) -> Result<Vec<UsageStats>, St
 }
 
 #[tauri::command]
-pub fn get_rate_limit_status(state: State<AppState>, profile_id: String) -> Result<RateLimitStatus, String> {
+pub fn get_all_usage_stats(
+    state: State<AppState>,
+    include_disabled: Option<bool>,
+) -> Result<Vec<UsageStats>, String> {
+    Ok(collect_usage_stats(&state, include_disabled.unwrap_or(false))?
+        .into_iter()
+        .map(|(_, stats)| stats)
+        .collect())
+}
+
+/// Sum usage across providers into one `UsageStats` with provider "All".
+/// Model breakdown entries are keyed by `<provider>/<model>`.
+fn merge_usage_stats(all: &[UsageStats]) -> UsageStats {
+    let mut merged = UsageStats {
+        provider: "All".to_string(),
+        ..Default::default()
+    };
+    // Hit ratios are averaged weighted by input, since folded stats no longer
+    // carry the cache reads they were computed from
+    let weight = |input: u64, cache_read: u64| (input + cache_read) as f64;
+    let mut hit_weights: HashMap<String, (f64, f64)> = HashMap::new();
+    let mut total_hits = (0.0, 0.0);
+
+    for stats in all {
+        let w = weight(stats.total_input_tokens, stats.total_cache_read_tokens);
+        total_hits.0 += stats.cache_hit_ratio * w;
+        total_hits.1 += w;
+
+        merged.total_input_tokens += stats.total_input_tokens;
+        merged.total_output_tokens += stats.total_output_tokens;
+        merged.total_cache_read_tokens += stats.total_cache_read_tokens;
+        merged.total_cache_write_tokens += stats.total_cache_write_tokens;
+        merged.total_sessions += stats.total_sessions;
+        merged.total_messages += stats.total_messages;
+        merged.total_tool_calls += stats.total_tool_calls;
+        merged.estimated_cost_usd += stats.estimated_cost_usd;
+        merged.equivalent_api_cost_usd += stats.equivalent_api_cost_usd;
+
+        for (tier, tokens) in &stats.service_tier_tokens {
+            *merged.service_tier_tokens.entry(tier.clone()).or_insert(0) += tokens;
+        }
+
+        for model in stats.model_breakdown.values() {
+            let key = format!("{}/{}", stats.provider, model.model);
+            let entry = merged
+                .model_breakdown
+                .entry(key.clone())
+                .or_insert_with(|| ModelUsage {
+                    model: key,
+                    ..Default::default()
+                });
+            entry.input_tokens += model.input_tokens;
+            entry.output_tokens += model.output_tokens;
+            entry.cache_read_tokens += model.cache_read_tokens;
+            entry.cache_write_tokens += model.cache_write_tokens;
+            entry.cache_write_1h_tokens += model.cache_write_1h_tokens;
+            entry.cost_usd += model.cost_usd;
+
+            let w = weight(model.input_tokens, model.cache_read_tokens);
+            let hits = hit_weights.entry(entry.model.clone()).or_insert((0.0, 0.0));
+            hits.0 += model.cache_hit_ratio * w;
+            hits.1 += w;
+        }
+    }
+
+    let ratio = |(hits, total): (f64, f64)| if total > 0.0 { hits / total } else { 0.0 };
+    merged.cache_hit_ratio = ratio(total_hits);
+    for (key, hits) in hit_weights {
+        if let Some(model) = merged.model_breakdown.get_mut(&key) {
+            model.cache_hit_ratio = ratio(hits);
+        }
+    }
+    // Every estimated_cost_usd is in USD, so the sum is too. Reported costs
+    // in other currencies can't be summed with it and are left out.
+    merged.estimated_cost_usd = (merged.estimated_cost_usd * 100.0).round() / 100.0;
+    merged.equivalent_api_cost_usd = (merged.equivalent_api_cost_usd * 100.0).round() / 100.0;
+    merged
+}
+
+/// One total across every enabled profile, in the display currency.
+pub fn combined_usage_stats(state: &AppState) -> Result<UsageStats, String> {
+    let settings = current_settings(state)?;
+    let all: Vec<UsageStats> = collect_usage_stats(state, false)?
+        .into_iter()
+        .map(|(_, stats)| stats)
+        .collect();
+
+    Ok(combine_usage_stats(&all, &settings))
+}
+
+/// Merge per-profile stats into one total in the display currency.
+pub(crate) fn combine_usage_stats(all: &[UsageStats], settings: &AppSettings) -> UsageStats {
+    let mut merged = merge_usage_stats(all);
+    currency::apply_display_currency(&mut merged, settings);
+    merged
+}
+
+/// Today's estimated spend in USD for one provider, unrounded, priced per
+/// model from its daily model usage. Zero on a subscription, whose tokens
+/// carry no marginal cost.
+pub(crate) fn today_cost_usd(provider: &dyn Provider, settings: &AppSettings) -> Result<f64, ProviderError> {
+    if provider.billing_mode() == BillingMode::Subscription {
+        return Ok(0.0);
+    }
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    Ok(exclusions::filter_daily_models(provider.get_daily_model_usage(1)?, settings)
+        .iter()
+        .filter(|u| u.date == today)
+        .map(|u| u.cost_usd)
+        .sum())
+}
+
+/// One total across every enabled profile, for the tray summary.
+#[tauri::command]
+pub fn get_combined_usage_stats(state: State<AppState>) -> Result<UsageStats, String> {
+    combined_usage_stats(&state)
+}
+
+#[tauri::command]
+pub fn export_prometheus(state: State<AppState>) -> Result<String, String> {
+    Ok(export::prometheus(&collect_usage_stats(&state, false)?))
+}
+
+/// Days of per-model daily usage included in CSV exports.
+const CSV_EXPORT_DAYS: u32 = 30;
+
+/// Write per-day and per-model usage to a CSV file at `path`.
+/// An empty `profile_id` exports every enabled profile, with a leading
+/// provider column holding the profile name.
+#[tauri::command]
+pub fn export_usage_csv(state: State<AppState>, profile_id: String, path: String) -> Result<(), String> {
     let config = state
         .config
         .lock()
         .map_err(|e| format!("Failed to lock config: {}", e))?;
 
-    let profile = config
-        .profiles
-        .iter()
-        .find(|p| p.id == profile_id)
-        .ok_or_else(|| format!("Profile not found: {}", profile_id))?;
-
-    let unavailable = RateLimitStatus {
-        available: false,
-        five_hour: None,
-        seven_day: None,
-        seven_day_opus: None,
-    };
+    let providers = state
+        .providers
+        .lock()
+        .map_err(|e| format!("Failed to lock providers: {}", e))?;
+
+    let all_profiles = profile_id.is_empty();
+    if !all_profiles && !config.profiles.iter().any(|p| p.id == profile_id) {
+        return Err(format!("Profile not found: {}", profile_id));
+    }
 
-    match (profile.provider_type.as_str(), profile.source_type.as_str()) {
-        ("claude", "account") | ("claude", "") => {
-            let provider = ClaudeProvider::new(profile.config_dir.clone().into());
-            Ok(provider.get_rate_limit_status())
+    let mut rows = Vec::new();
+
+    for profile in &config.profiles {
+        let selected = if all_profiles { profile.enabled } else { profile.id == profile_id };
+        if !selected {
+            continue;
         }
-        ("zai", "api") => {
-            let key = match &profile.api_key {
-                Some(k) => k.clone(),
-                None => return Ok(unavailable),
-            };
-            let provider = ZaiApiProvider::new(key);
-            Ok(provider.get_rate_limit_status())
+        let provider = match providers.get(&profile.id) {
+            Some(p) => p,
+            None if all_profiles => continue,
+            None => return Err(missing_provider(&state, &profile.id).into()),
+        };
+
+        let fetched = provider.get_daily_model_usage(CSV_EXPORT_DAYS).map(|usage| {
+            let mut daily = exclusions::filter_daily_models(usage, &config.settings);
+            if config.settings.cache_counts_as_input {
+                daily.iter_mut().for_each(DailyModelUsage::fold_cache_into_input);
+            }
+            export::csv_rows(&profile.name, &daily)
+        });
+        match fetched {
+            Ok(profile_rows) => rows.extend(profile_rows),
+            Err(e) if all_profiles => tracing::warn!("Skipping CSV export for {}: {}", profile.id, e),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    std::fs::write(&path, export::csv(&rows, all_profiles))
+        .map_err(|e| format!("Failed to write CSV file: {}", e))
+}
+
+/// Fetch the current rate-limit status for a profile, through its
+/// registered provider; only unregistered profiles get one built.
+/// Profiles without rate-limit support report `available: false`.
+pub fn fetch_rate_limit_status(state: &AppState, profile: &Profile, settings: &AppSettings) -> RateLimitStatus {
+    // Clone the handle so the request runs after the lock is released
+    let registered = state.providers.lock().ok().and_then(|p| p.get(&profile.id).cloned());
+    let status = match registered {
+        Some(provider) => provider.get_rate_limit_status(),
+        None => build_provider(profile, settings)
+            .map_err(ProviderError::from)
+            .and_then(|p| p.get_rate_limit_status()),
+    };
+    match status {
+        Ok(status) => status,
+        Err(e) => {
+            tracing::warn!("Failed to fetch rate limit status for {}: {}", profile.id, e);
+            RateLimitStatus::default()
+        }
+    }
+}
+
+#[tauri::command]
+pub fn get_rate_limit_status(state: State<AppState>, profile_id: String) -> Result<RateLimitStatus, ProviderError> {
+    let provider = state
+        .providers
+        .lock()
+        .map_err(|e| format!("Failed to lock providers: {}", e))?
+        .get(&profile_id)
+        .cloned()
+        .ok_or_else(|| missing_provider(&state, &profile_id))?;
+
+    provider.get_rate_limit_status()
+}
+
+#[tauri::command]
+pub fn get_rate_limit_history(
+    state: State<AppState>,
+    profile_id: String,
+) -> Result<Vec<(String, RateLimitStatus)>, String> {
+    let history = state
+        .rate_limit_history
+        .lock()
+        .map_err(|e| format!("Failed to lock rate limit history: {}", e))?;
+
+    Ok(history.get(&profile_id))
+}
+
+/// Outcome of an API key check, so the UI can tell a wrong key apart
+/// from a network or server problem.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", content = "reason", rename_all = "camelCase")]
+pub enum KeyValidation {
+    Valid,
+    Invalid,
+    Unreachable(String),
+}
+
+/// Send a validation request through [`providers::send_with_retry`], so
+/// timeouts, 429s and 5xx responses are retried. 401/403 mean the key
+/// itself was rejected.
+fn check_api_key(request: reqwest::blocking::RequestBuilder) -> KeyValidation {
+    match providers::send_with_retry(request) {
+        Ok(resp) => {
+            let status = resp.status();
+            if status.is_success() {
+                KeyValidation::Valid
+            } else if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+                KeyValidation::Invalid
+            } else if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                KeyValidation::Unreachable(format!("Server returned {}", status))
+            } else {
+                KeyValidation::Unreachable(format!("Unexpected response {}", status))
+            }
         }
-        _ => Ok(unavailable),
+        Err(e) => KeyValidation::Unreachable(format!("API validation request failed: {}", e)),
     }
 }
 
+/// Check an API key against its provider with one lightweight request.
+/// `provider_type` defaults to "claude"; types without an API are an error
+/// rather than a key silently checked against the wrong service.
 #[tauri::command]
-pub fn validate_api_key(api_key: String, provider_type: Option<String>) -> Result<bool, String> {
-    let client = reqwest::blocking::Client::builder()
-        .timeout(std::time::Duration::from_secs(10))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+pub fn validate_api_key(
+    state: State<AppState>,
+    api_key: String,
+    provider_type: Option<String>,
+) -> Result<KeyValidation, String> {
+    let settings = current_settings(&state)?;
+    check_provider_key(api_key, provider_type, settings.proxy_url.as_deref())
+}
+
+fn check_provider_key(
+    api_key: String,
+    provider_type: Option<String>,
+    proxy_url: Option<&str>,
+) -> Result<KeyValidation, String> {
+    let client = providers::http_client(std::time::Duration::from_secs(10), proxy_url)?;
 
     let provider = provider_type.unwrap_or_else(|| "claude".to_string());
 
     match provider.as_str() {
         "zai" => {
             // Validate z.ai key by calling the quota endpoint
-            let resp = client
-                .get("https://api.z.ai/api/monitor/usage/quota/limit")
-                .header("Authorization", &api_key)
-                .header("Accept-Language", "en-US,en")
-                .header("Content-Type", "application/json")
-                .send()
-                .map_err(|e| format!("API validation request failed: {}", e))?;
-
-            Ok(resp.status().is_success())
+            Ok(check_api_key(
+                client
+                    .get("https://api.z.ai/api/monitor/usage/quota/limit")
+                    .header("Authorization", &api_key)
+                    .header("Accept-Language", "en-US,en")
+                    .header("Content-Type", "application/json"),
+            ))
         }
-        _ => {
+        "claude" => {
             // Claude Admin API key validation
             let now = chrono::Utc::now();
             let start = now - chrono::Duration::days(1);
             let starting_at = start.format("%Y-%m-%dT00:00:00Z").to_string();
-            let ending_at = now.format("%Y-%m-%dT23:59:59Z").to_string();
+            // End now; the end of today would be a timestamp in the future
+            let ending_at = now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
 
-            let resp = client
-                .get("https://api.anthropic.com/v1/organizations/usage_report/messages")
-                .header("x-api-key", &api_key)
-                .header("anthropic-version", "2023-06-01")
-                .query(&[
-                    ("starting_at", starting_at.as_str()),
-                    ("ending_at", ending_at.as_str()),
-                    ("limit", "1"),
-                ])
-                .send()
-                .map_err(|e| format!("API validation request failed: {}", e))?;
-
-            Ok(resp.status().is_success())
+            Ok(check_api_key(
+                client
+                    .get("https://api.anthropic.com/v1/organizations/usage_report/messages")
+                    .header("x-api-key", &api_key)
+                    .header("anthropic-version", "2023-06-01")
+                    .query(&[
+                        ("starting_at", starting_at.as_str()),
+                        ("ending_at", ending_at.as_str()),
+                        ("limit", "1"),
+                    ]),
+            ))
         }
+        other => Err(format!("API key validation is not supported for {}", other)),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProbeResult {
+    pub ok: bool,
+    pub detail: String,
+}
+
+/// Outcome of a profile's health check, for the status dot.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthStatus {
+    pub healthy: bool,
+    pub message: String,
+    /// When the check ran (RFC 3339).
+    pub checked_at: String,
+}
+
+/// Check that a registered profile can read its usage. A profile without a
+/// provider (disabled or failed to build) is reported as unhealthy.
+#[tauri::command]
+pub fn check_provider_health(state: State<AppState>, profile_id: String) -> Result<HealthStatus, String> {
+    let providers = state
+        .providers
+        .lock()
+        .map_err(|e| format!("Failed to lock providers: {}", e))?;
+
+    let result = match providers.get(&profile_id) {
+        Some(provider) => provider.health_check(),
+        None => Err(missing_provider(&state, &profile_id)),
+    };
+
+    Ok(HealthStatus {
+        healthy: result.is_ok(),
+        message: match result {
+            Ok(()) => "OK".to_string(),
+            Err(e) => e.to_string(),
+        },
+        checked_at: chrono::Utc::now().to_rfc3339(),
+    })
+}
+
+/// Where a registered profile's provider looks for data and what it found.
+#[tauri::command]
+pub fn get_diagnostics(state: State<AppState>, profile_id: String) -> Result<Diagnostics, ProviderError> {
+    let providers = state
+        .providers
+        .lock()
+        .map_err(|e| format!("Failed to lock providers: {}", e))?;
+
+    let provider = providers
+        .get(&profile_id)
+        .ok_or_else(|| missing_provider(&state, &profile_id))?;
+
+    Ok(provider.diagnostics())
+}
+
+/// Check whether a profile can produce data without registering it.
+/// API profiles get a one-item key check; file profiles check their files.
+#[tauri::command]
+pub fn probe_profile(state: State<AppState>, profile: Profile) -> Result<ProbeResult, String> {
+    let settings = current_settings(&state)?;
+    let provider = match build_provider(&profile, &settings) {
+        Ok(p) => p,
+        Err(detail) => return Ok(ProbeResult { ok: false, detail }),
+    };
+
+    if profile.source_type == "api" {
+        let api_key = profile.api_key.clone().unwrap_or_default();
+        let provider_type = Some(profile.provider_type.clone());
+        let result = match check_provider_key(api_key, provider_type, settings.proxy_url.as_deref())? {
+            KeyValidation::Valid => ProbeResult {
+                ok: true,
+                detail: "API key accepted".to_string(),
+            },
+            KeyValidation::Invalid => ProbeResult {
+                ok: false,
+                detail: "API key was rejected".to_string(),
+            },
+            KeyValidation::Unreachable(reason) => ProbeResult { ok: false, detail: reason },
+        };
+        return Ok(result);
     }
+
+    Ok(match provider.probe() {
+        Ok(detail) => ProbeResult { ok: true, detail },
+        Err(e) => ProbeResult {
+            ok: false,
+            detail: e.to_string(),
+        },
+    })
+}
+
+#[tauri::command]
+pub fn check_budget(state: State<AppState>, profile_id: String) -> Result<BudgetStatus, ProviderError> {
+    let budget = {
+        let config = state
+            .config
+            .lock()
+            .map_err(|e| format!("Failed to lock config: {}", e))?;
+        config
+            .profiles
+            .iter()
+            .find(|p| p.id == profile_id)
+            .ok_or_else(|| format!("Profile not found: {}", profile_id))?
+            .daily_token_budget
+    };
+
+    let providers = state
+        .providers
+        .lock()
+        .map_err(|e| format!("Failed to lock providers: {}", e))?;
+
+    let provider = providers
+        .get(&profile_id)
+        .ok_or_else(|| missing_provider(&state, &profile_id))?;
+
+    compute_budget_status(provider.as_ref(), budget)
+}
+
+/// Most recent `limit` log entries, oldest first.
+#[tauri::command]
+pub fn get_recent_logs(state: State<AppState>, limit: u32) -> Result<Vec<LogEntry>, String> {
+    let logs = state
+        .logs
+        .lock()
+        .map_err(|e| format!("Failed to lock logs: {}", e))?;
+    let skip = logs.len().saturating_sub(limit as usize);
+    Ok(logs.iter().skip(skip).cloned().collect())
 }
@@ -0,0 +1,76 @@
+use crate::profile::AppSettings;
+use crate::providers::UsageStats;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Response shape of common FX endpoints (open.er-api.com, frankfurter.app).
+#[derive(Debug, Deserialize)]
+struct FxRatesResponse {
+    #[serde(default)]
+    rates: HashMap<String, f64>,
+}
+
+/// Fetch the USD -> `currency` rate from a rates endpoint.
+pub fn fetch_fx_rate(url: &str, currency: &str) -> Result<f64, String> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(std::time::Duration::from_secs(10))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+    let resp = client
+        .get(url)
+        .send()
+        .map_err(|e| format!("FX rate request failed: {}", e))?;
+
+    if !resp.status().is_success() {
+        return Err(format!("FX rate API error {}", resp.status()));
+    }
+
+    let body: FxRatesResponse = resp
+        .json()
+        .map_err(|e| format!("Failed to parse FX rates: {}", e))?;
+
+    body.rates
+        .get(&currency.to_uppercase())
+        .copied()
+        .filter(|r| r.is_finite() && *r > 0.0)
+        .ok_or_else(|| format!("No FX rate for {}", currency))
+}
+
+/// Format an amount for compact display: `$12.34` for USD,
+/// `12.34 EUR` for other currencies.
+pub fn format_amount(amount: f64, currency: &str) -> String {
+    if currency.is_empty() || currency.eq_ignore_ascii_case("USD") {
+        format!("${:.2}", amount)
+    } else {
+        format!("{:.2} {}", amount, currency.to_uppercase())
+    }
+}
+
+/// Convert a USD amount to the display currency, rounded to cents.
+/// A missing or invalid rate leaves the amount as-is.
+pub fn to_display(usd: f64, settings: &AppSettings) -> f64 {
+    let rate = if settings.fx_rate_usd_to_display.is_finite() && settings.fx_rate_usd_to_display > 0.0 {
+        settings.fx_rate_usd_to_display
+    } else {
+        1.0
+    };
+    (usd * rate * 100.0).round() / 100.0
+}
+
+/// Fill the display-currency fields of `stats` from the USD values.
+/// The USD fields are left untouched as the source of truth. A cost
+/// reported in the display currency itself is shown as reported, since it
+/// is the actual spend rather than a converted estimate.
+pub fn apply_display_currency(stats: &mut UsageStats, settings: &AppSettings) {
+    let convert = |usd: f64| to_display(usd, settings);
+
+    stats.display_currency = settings.display_currency.clone();
+    stats.display_cost = match stats.reported_cost {
+        Some(cost) if stats.reported_currency.eq_ignore_ascii_case(&settings.display_currency) => cost,
+        _ => convert(stats.estimated_cost_usd),
+    };
+    for model in stats.model_breakdown.values_mut() {
+        model.display_cost = convert(model.cost_usd);
+    }
+}
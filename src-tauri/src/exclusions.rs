@@ -0,0 +1,192 @@
+use crate::profile::AppSettings;
+use crate::providers::{
+    parse_last_active, BillingMode, DailyModelUsage, DailyUsage, Provider, Session, UsageStats,
+};
+
+/// Case-insensitive substring match against any non-empty pattern.
+fn matches_any(value: &str, patterns: &[String]) -> bool {
+    let value = value.to_lowercase();
+    patterns
+        .iter()
+        .filter(|p| !p.trim().is_empty())
+        .any(|p| value.contains(&p.trim().to_lowercase()))
+}
+
+/// Sessions of excluded projects, for taking their usage off the totals.
+/// Sessions of excluded models are left out, since their usage already goes
+/// with the model. Empty without scanning history when no project is
+/// excluded; a provider whose history fails excludes nothing.
+pub fn excluded_project_sessions(provider: &dyn Provider, settings: &AppSettings) -> Vec<Session> {
+    if settings.excluded_projects.is_empty() {
+        return Vec::new();
+    }
+    match provider.get_session_history(u32::MAX) {
+        Ok(sessions) => sessions
+            .into_iter()
+            .filter(|s| is_excluded_project(s, settings) && !matches_any(&s.model, &settings.excluded_models))
+            .collect(),
+        Err(e) => {
+            tracing::warn!("Failed to load sessions for project exclusions: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+fn is_excluded_project(session: &Session, settings: &AppSettings) -> bool {
+    matches_any(&session.project_path, &settings.excluded_projects)
+        || matches_any(&session.project, &settings.excluded_projects)
+}
+
+/// Split `tokens` across `parts` in proportion to their sizes, never taking
+/// more than the parts hold. Sessions carry only a token total, so this is
+/// how their usage is taken off per-kind counters.
+fn split_tokens(tokens: u64, parts: [u64; 4]) -> [u64; 4] {
+    let total: u64 = parts.iter().sum();
+    if total == 0 {
+        return [0; 4];
+    }
+    let tokens = tokens.min(total);
+    parts.map(|p| (p as u128 * tokens as u128 / total as u128) as u64)
+}
+
+/// Take `cost` off whichever cost the stats carry per-model costs in.
+fn subtract_cost(stats: &mut UsageStats, cost: f64) {
+    // Subscription stats carry the per-model cost in the API-equivalent figure
+    if stats.billing_mode == BillingMode::Subscription {
+        stats.equivalent_api_cost_usd = ((stats.equivalent_api_cost_usd - cost).max(0.0) * 100.0).round() / 100.0;
+    } else {
+        stats.estimated_cost_usd = ((stats.estimated_cost_usd - cost).max(0.0) * 100.0).round() / 100.0;
+    }
+}
+
+/// Remove excluded models from the breakdown and subtract them from the
+/// totals, then subtract the `excluded` project sessions (see
+/// [`excluded_project_sessions`]) from their model and the totals.
+pub fn filter_stats(stats: &mut UsageStats, excluded: &[Session], settings: &AppSettings) {
+    if settings.excluded_models.is_empty() && excluded.is_empty() {
+        return;
+    }
+
+    let excluded_models: Vec<String> = stats
+        .model_breakdown
+        .keys()
+        .filter(|m| matches_any(m, &settings.excluded_models))
+        .cloned()
+        .collect();
+
+    for model in excluded_models {
+        if let Some(m) = stats.model_breakdown.remove(&model) {
+            stats.total_input_tokens = stats.total_input_tokens.saturating_sub(m.input_tokens);
+            stats.total_output_tokens = stats.total_output_tokens.saturating_sub(m.output_tokens);
+            stats.total_cache_read_tokens = stats
+                .total_cache_read_tokens
+                .saturating_sub(m.cache_read_tokens);
+            stats.total_cache_write_tokens = stats
+                .total_cache_write_tokens
+                .saturating_sub(m.cache_write_tokens);
+            subtract_cost(stats, m.cost_usd);
+        }
+    }
+
+    for session in excluded {
+        // Split by the session's model mix, or the overall one when the
+        // breakdown doesn't list its model
+        let [input, output, cache_read, cache_write] = match stats.model_breakdown.get_mut(&session.model) {
+            Some(m) => {
+                let split = split_tokens(
+                    session.tokens_used,
+                    [m.input_tokens, m.output_tokens, m.cache_read_tokens, m.cache_write_tokens],
+                );
+                m.input_tokens -= split[0];
+                m.output_tokens -= split[1];
+                m.cache_read_tokens -= split[2];
+                m.cache_write_tokens -= split[3];
+                m.cache_write_1h_tokens = m.cache_write_1h_tokens.min(m.cache_write_tokens);
+                m.cost_usd = ((m.cost_usd - session.cost_usd).max(0.0) * 100.0).round() / 100.0;
+                split
+            }
+            None => split_tokens(
+                session.tokens_used,
+                [
+                    stats.total_input_tokens,
+                    stats.total_output_tokens,
+                    stats.total_cache_read_tokens,
+                    stats.total_cache_write_tokens,
+                ],
+            ),
+        };
+        stats.total_input_tokens = stats.total_input_tokens.saturating_sub(input);
+        stats.total_output_tokens = stats.total_output_tokens.saturating_sub(output);
+        stats.total_cache_read_tokens = stats.total_cache_read_tokens.saturating_sub(cache_read);
+        stats.total_cache_write_tokens = stats.total_cache_write_tokens.saturating_sub(cache_write);
+        stats.total_sessions = stats.total_sessions.saturating_sub(1);
+        stats.total_messages = stats.total_messages.saturating_sub(session.message_count);
+        subtract_cost(stats, session.cost_usd);
+    }
+    stats.compute_cache_hit_ratios();
+}
+
+/// Subtract excluded models and the `excluded` project sessions from daily
+/// usage. Models come off using the provider's per-model daily usage. A
+/// session comes off the day it was last active, split across token kinds
+/// in that day's proportions.
+pub fn filter_daily(
+    daily: &mut [DailyUsage],
+    daily_models: &[DailyModelUsage],
+    excluded: &[Session],
+    settings: &AppSettings,
+) {
+    for usage in daily_models.iter().filter(|u| matches_any(&u.model, &settings.excluded_models)) {
+        if let Some(day) = daily.iter_mut().find(|d| d.date == usage.date) {
+            day.input_tokens = day.input_tokens.saturating_sub(usage.input_tokens);
+            day.output_tokens = day.output_tokens.saturating_sub(usage.output_tokens);
+            day.cache_read_tokens = day.cache_read_tokens.saturating_sub(usage.cache_read_tokens);
+            day.cache_write_tokens = day.cache_write_tokens.saturating_sub(usage.cache_write_tokens);
+        }
+    }
+
+    for session in excluded {
+        let Some(date) = parse_last_active(&session.last_active)
+            .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d").to_string())
+        else {
+            continue;
+        };
+        if let Some(day) = daily.iter_mut().find(|d| d.date == date) {
+            let split = split_tokens(
+                session.tokens_used,
+                [day.input_tokens, day.output_tokens, day.cache_read_tokens, day.cache_write_tokens],
+            );
+            day.input_tokens -= split[0];
+            day.output_tokens -= split[1];
+            day.cache_read_tokens -= split[2];
+            day.cache_write_tokens -= split[3];
+            day.sessions = day.sessions.saturating_sub(1);
+            day.messages = day.messages.saturating_sub(session.message_count);
+        }
+    }
+}
+
+/// Drop sessions whose model or project is excluded.
+pub fn filter_sessions(sessions: Vec<Session>, settings: &AppSettings) -> Vec<Session> {
+    sessions
+        .into_iter()
+        .filter(|s| !matches_any(&s.model, &settings.excluded_models))
+        .filter(|s| !is_excluded_project(s, settings))
+        .collect()
+}
+
+/// Drop excluded models from a model list.
+pub fn filter_models(models: Vec<String>, settings: &AppSettings) -> Vec<String> {
+    models
+        .into_iter()
+        .filter(|m| !matches_any(m, &settings.excluded_models))
+        .collect()
+}
+
+/// Drop per-model daily entries for excluded models.
+pub fn filter_daily_models(usage: Vec<DailyModelUsage>, settings: &AppSettings) -> Vec<DailyModelUsage> {
+    usage
+        .into_iter()
+        .filter(|u| !matches_any(&u.model, &settings.excluded_models))
+        .collect()
+}
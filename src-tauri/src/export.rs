@@ -0,0 +1,151 @@
+use crate::providers::{DailyModelUsage, ModelUsage, UsageStats};
+use std::fmt::Write;
+
+type ModelValue = fn(&ModelUsage) -> f64;
+type ProviderValue = fn(&UsageStats) -> f64;
+
+/// Per-model metric families: (name, type, help, value).
+const MODEL_METRICS: &[(&str, &str, &str, ModelValue)] = &[
+    ("cldbar_input_tokens_total", "counter", "Input tokens consumed, by model.", |m| m.input_tokens as f64),
+    ("cldbar_output_tokens_total", "counter", "Output tokens generated, by model.", |m| m.output_tokens as f64),
+    ("cldbar_cache_read_tokens_total", "counter", "Prompt-cache read tokens, by model.", |m| m.cache_read_tokens as f64),
+    ("cldbar_cache_write_tokens_total", "counter", "Prompt-cache write tokens, by model.", |m| m.cache_write_tokens as f64),
+    ("cldbar_model_cost_usd", "gauge", "Estimated cost in USD, by model.", |m| m.cost_usd),
+];
+
+/// Per-provider metric families: (name, type, help, value).
+const PROVIDER_METRICS: &[(&str, &str, &str, ProviderValue)] = &[
+    ("cldbar_cost_usd", "gauge", "Estimated cost in USD, by provider.", |s| s.estimated_cost_usd),
+    ("cldbar_sessions_total", "counter", "Sessions recorded, by provider.", |s| s.total_sessions as f64),
+    ("cldbar_messages_total", "counter", "Messages recorded, by provider.", |s| s.total_messages as f64),
+];
+
+/// Escape a label value per the Prometheus text format:
+/// backslash, double quote and line feed must be escaped.
+fn escape_label_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Render usage stats keyed by profile id in Prometheus text exposition format.
+pub fn prometheus(stats: &[(String, UsageStats)]) -> String {
+    let mut out = String::new();
+
+    let mut sorted: Vec<&(String, UsageStats)> = stats.iter().collect();
+    sorted.sort_by(|a, b| a.0.cmp(&b.0));
+
+    for (name, kind, help, value) in MODEL_METRICS {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        for (profile_id, s) in &sorted {
+            let mut models: Vec<&ModelUsage> = s.model_breakdown.values().collect();
+            models.sort_by(|a, b| a.model.cmp(&b.model));
+            for m in models {
+                let _ = writeln!(
+                    out,
+                    "{}{{profile=\"{}\",provider=\"{}\",model=\"{}\"}} {}",
+                    name,
+                    escape_label_value(profile_id),
+                    escape_label_value(&s.provider),
+                    escape_label_value(&m.model),
+                    value(m)
+                );
+            }
+        }
+    }
+
+    for (name, kind, help, value) in PROVIDER_METRICS {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        for (profile_id, s) in &sorted {
+            let _ = writeln!(
+                out,
+                "{}{{profile=\"{}\",provider=\"{}\"}} {}",
+                name,
+                escape_label_value(profile_id),
+                escape_label_value(&s.provider),
+                value(s)
+            );
+        }
+    }
+
+    out
+}
+
+/// One CSV line: a model's usage on a date.
+pub struct CsvRow {
+    pub provider: String,
+    pub date: String,
+    pub model: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_write_tokens: u64,
+    pub cost_usd: f64,
+}
+
+/// One row per model and day. Totals are left to the spreadsheet, so
+/// summing a column never counts a day twice.
+pub fn csv_rows(provider: &str, daily: &[DailyModelUsage]) -> Vec<CsvRow> {
+    daily
+        .iter()
+        .map(|d| CsvRow {
+            provider: provider.to_string(),
+            date: d.date.clone(),
+            model: d.model.clone(),
+            input_tokens: d.input_tokens,
+            output_tokens: d.output_tokens,
+            cache_read_tokens: d.cache_read_tokens,
+            cache_write_tokens: d.cache_write_tokens,
+            cost_usd: d.cost_usd,
+        })
+        .collect()
+}
+
+/// Quote a CSV field if it contains a comma, quote or line break,
+/// doubling any embedded quotes.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Render rows as CSV with a header line. `with_provider` adds a leading
+/// provider column, for exports covering several profiles.
+pub fn csv(rows: &[CsvRow], with_provider: bool) -> String {
+    let mut out = String::new();
+
+    if with_provider {
+        out.push_str("provider,");
+    }
+    out.push_str("date,model,input_tokens,output_tokens,cache_read,cache_write,estimated_cost_usd\n");
+
+    for row in rows {
+        if with_provider {
+            let _ = write!(out, "{},", csv_field(&row.provider));
+        }
+        let _ = writeln!(
+            out,
+            "{},{},{},{},{},{},{:.4}",
+            csv_field(&row.date),
+            csv_field(&row.model),
+            row.input_tokens,
+            row.output_tokens,
+            row.cache_read_tokens,
+            row.cache_write_tokens,
+            row.cost_usd
+        );
+    }
+
+    out
+}
@@ -0,0 +1,132 @@
+use crate::profile;
+use crate::providers::{DailyUsage, UsageStats};
+use rusqlite::{params, Connection};
+use std::fs;
+use std::path::PathBuf;
+
+/// Long-term per-day usage: %APPDATA%/cldbar/history.db
+fn history_db_path() -> Result<PathBuf, String> {
+    Ok(profile::data_dir()?.join("history.db"))
+}
+
+fn open() -> Result<Connection, String> {
+    let path = history_db_path()?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create data directory: {}", e))?;
+    }
+
+    let conn = Connection::open(&path)
+        .map_err(|e| format!("Failed to open usage history: {}", e))?;
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS daily_usage ( \
+             profile_id TEXT NOT NULL, \
+             date TEXT NOT NULL, \
+             input_tokens INTEGER NOT NULL, \
+             output_tokens INTEGER NOT NULL, \
+             cache_read_tokens INTEGER NOT NULL, \
+             cache_write_tokens INTEGER NOT NULL, \
+             sessions INTEGER NOT NULL, \
+             messages INTEGER NOT NULL, \
+             PRIMARY KEY (profile_id, date) \
+         )",
+    )
+    .map_err(|e| format!("Failed to create usage history table: {}", e))?;
+
+    Ok(conn)
+}
+
+/// Store daily usage rows for a profile, so trends outlive what the provider
+/// retains (30 days for the Claude Admin API, 24 hours for z.ai).
+/// Rows for dates already stored are replaced.
+pub fn record(profile_id: &str, days: &[DailyUsage]) -> Result<(), String> {
+    let mut conn = open()?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+    {
+        let mut stmt = tx
+            .prepare_cached(
+                "INSERT INTO daily_usage \
+                 (profile_id, date, input_tokens, output_tokens, cache_read_tokens, \
+                  cache_write_tokens, sessions, messages) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8) \
+                 ON CONFLICT (profile_id, date) DO UPDATE SET \
+                 input_tokens = excluded.input_tokens, \
+                 output_tokens = excluded.output_tokens, \
+                 cache_read_tokens = excluded.cache_read_tokens, \
+                 cache_write_tokens = excluded.cache_write_tokens, \
+                 sessions = excluded.sessions, \
+                 messages = excluded.messages",
+            )
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        for day in days {
+            stmt.execute(params![
+                profile_id,
+                day.date,
+                day.input_tokens,
+                day.output_tokens,
+                day.cache_read_tokens,
+                day.cache_write_tokens,
+                day.sessions,
+                day.messages,
+            ])
+            .map_err(|e| format!("Failed to write usage history: {}", e))?;
+        }
+    }
+
+    tx.commit()
+        .map_err(|e| format!("Failed to write usage history: {}", e))
+}
+
+/// Today's row built from usage stats, for providers without a daily
+/// breakdown (e.g. the z.ai API's rolling 24h window).
+pub fn today_from_stats(stats: &UsageStats) -> DailyUsage {
+    DailyUsage {
+        date: chrono::Local::now().format("%Y-%m-%d").to_string(),
+        input_tokens: stats.total_input_tokens,
+        output_tokens: stats.total_output_tokens,
+        sessions: stats.total_sessions,
+        messages: stats.total_messages,
+        cache_read_tokens: stats.total_cache_read_tokens,
+        cache_write_tokens: stats.total_cache_write_tokens,
+        tool_calls: stats.total_tool_calls,
+    }
+}
+
+/// Stored rows for a profile with `from <= date <= to` (YYYY-MM-DD), newest first.
+pub fn query(profile_id: &str, from: &str, to: &str) -> Result<Vec<DailyUsage>, String> {
+    let conn = open()?;
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT date, input_tokens, output_tokens, sessions, messages, \
+             cache_read_tokens, cache_write_tokens \
+             FROM daily_usage \
+             WHERE profile_id = ?1 AND date >= ?2 AND date <= ?3 \
+             ORDER BY date DESC",
+        )
+        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+    let rows = stmt
+        .query_map(params![profile_id, from, to], |row| {
+            Ok(DailyUsage {
+                date: row.get(0)?,
+                input_tokens: row.get(1)?,
+                output_tokens: row.get(2)?,
+                sessions: row.get(3)?,
+                messages: row.get(4)?,
+                cache_read_tokens: row.get(5)?,
+                cache_write_tokens: row.get(6)?,
+                // Not kept in the history table
+                tool_calls: 0,
+            })
+        })
+        .map_err(|e| format!("Failed to query usage history: {}", e))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(rows)
+}
@@ -1,77 +1,122 @@
+mod autostart;
+mod background;
+mod cli;
 mod commands;
+mod currency;
+mod exclusions;
+mod export;
+mod history;
+mod logs;
+mod pricing;
 mod profile;
 mod providers;
+mod rate_history;
+mod secrets;
+mod shortcut;
+mod watcher;
+mod window_state;
 
 use commands::AppState;
 use profile::load_config;
-use providers::claude::ClaudeProvider;
-use providers::claude_api::ClaudeApiProvider;
-use providers::gemini::GeminiProvider;
-use providers::zai::ZaiProvider;
-use providers::zai_api::ZaiApiProvider;
-use providers::Provider;
-use std::collections::HashMap;
+use rate_history::RateLimitHistory;
 use std::sync::Mutex;
 use tauri::{
     menu::{MenuBuilder, MenuItemBuilder},
     tray::{MouseButton, MouseButtonState, TrayIconEvent},
-    Manager,
+    AppHandle, Manager, RunEvent,
 };
+use tauri_plugin_global_shortcut::ShortcutState;
+
+/// Entry point of the headless `cldbar-cli` binary; returns the exit code.
+pub fn run_cli() -> i32 {
+    cli::run(&std::env::args().skip(1).collect::<Vec<_>>())
+}
 
 pub fn run() {
-    let config = load_config().unwrap_or_else(|_| profile::default_config());
+    let log_buffer = logs::init();
+    let config = load_config().unwrap_or_else(|e| {
+        tracing::warn!("{}; using default config", e);
+        profile::default_config()
+    });
 
-    // Create providers from config
-    let mut provider_map: HashMap<String, Box<dyn Provider>> = HashMap::new();
-    for p in &config.profiles {
-        if !p.enabled {
-            continue;
-        }
-        let provider: Box<dyn Provider> = match (p.provider_type.as_str(), p.source_type.as_str()) {
-            ("claude", "api") => {
-                if let Some(ref key) = p.api_key {
-                    Box::new(ClaudeApiProvider::new(key.clone()))
-                } else {
-                    continue;
-                }
-            }
-            ("claude", _) => Box::new(ClaudeProvider::new(p.config_dir.clone().into())),
-            ("gemini", _) => Box::new(GeminiProvider::new(p.config_dir.clone().into())),
-            ("zai", "api") => {
-                if let Some(ref key) = p.api_key {
-                    Box::new(ZaiApiProvider::new(key.clone()))
-                } else {
-                    continue;
-                }
-            }
-            ("zai", _) => Box::new(ZaiProvider::new(p.config_dir.clone().into())),
-            _ => continue,
-        };
-        provider_map.insert(p.id.clone(), provider);
-    }
+    // Create providers from config, remembering why any profile has none
+    let (provider_map, provider_errors) = commands::build_providers(&config);
 
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_autostart::init(
+            tauri_plugin_autostart::MacosLauncher::LaunchAgent,
+            None,
+        ))
+        .plugin(
+            tauri_plugin_global_shortcut::Builder::new()
+                .with_handler(|app, _shortcut, event| {
+                    if event.state == ShortcutState::Pressed {
+                        window_state::toggle(app);
+                    }
+                })
+                .build(),
+        )
         .manage(AppState {
             config: Mutex::new(config),
             providers: Mutex::new(provider_map),
+            rate_limit_history: Mutex::new(RateLimitHistory::load()),
+            provider_errors: Mutex::new(provider_errors),
+            logs: log_buffer,
         })
         .invoke_handler(tauri::generate_handler![
             commands::get_profiles,
             commands::add_profile,
+            commands::update_profile,
+            commands::set_profile_enabled,
             commands::remove_profile,
+            commands::reorder_profiles,
+            commands::export_config,
+            commands::import_config,
             commands::get_usage_stats,
+            commands::get_usage_stats_range,
+            commands::get_model_breakdown,
+            commands::get_project_breakdown,
+            commands::get_workspace_breakdown,
+            commands::refresh_provider,
+            commands::get_dashboard,
             commands::get_active_sessions,
+            commands::get_all_active_sessions,
+            commands::get_active_session_count,
             commands::get_daily_usage,
+            commands::get_daily_model_usage,
+            commands::get_usage_history,
+            commands::get_usage_sparkline,
+            commands::get_usage_rollup,
+            commands::get_cost_forecast,
             commands::get_session_history,
+            commands::get_top_sessions,
+            commands::get_known_models,
+            commands::estimate_cost,
+            commands::get_pricing_table,
+            commands::debug_raw_usage,
             commands::get_settings,
             commands::update_settings,
             commands::get_all_usage_stats,
+            commands::get_combined_usage_stats,
+            commands::export_prometheus,
+            commands::export_usage_csv,
             commands::validate_api_key,
+            commands::probe_profile,
+            commands::get_diagnostics,
+            commands::check_provider_health,
             commands::get_rate_limit_status,
+            commands::get_rate_limit_history,
+            commands::check_budget,
+            commands::get_recent_logs,
         ])
         .setup(|app| {
+            if let Some(window) = app.get_webview_window("main") {
+                window_state::track(&window);
+            }
+
             // Set up tray icon with context menu and click handler.
             if let Some(tray) = app.tray_by_id("main") {
                 let quit_item = MenuItemBuilder::with_id("quit", "Quit").build(app)?;
@@ -91,30 +136,52 @@ pub fn run() {
                         ..
                     } = event
                     {
-                        let app = tray.app_handle();
-                        if let Some(window) = app.get_webview_window("main") {
-                            if window.is_visible().unwrap_or(false) {
-                                let _ = window.hide();
-                            } else {
-                                // Position window so its bottom edge sits just above the tray icon.
-                                if let Ok(Some(rect)) = tray.rect() {
-                                    let tray_pos: tauri::PhysicalPosition<i32> = rect.position.to_physical(1.0);
-                                    let win_size = window.outer_size().unwrap_or(tauri::PhysicalSize { width: 380, height: 490 });
-                                    let x = tray_pos.x - (win_size.width as i32 / 2);
-                                    let y = tray_pos.y - win_size.height as i32;
-                                    let _ = window.set_position(tauri::Position::Physical(
-                                        tauri::PhysicalPosition { x, y },
-                                    ));
-                                }
-                                let _ = window.show();
-                                let _ = window.set_focus();
-                            }
-                        }
+                        window_state::toggle(tray.app_handle());
                     }
                 });
             }
+
+            // The OS entry can be changed outside the app; the setting wins
+            let (launch_on_startup, toggle_shortcut) = app
+                .state::<AppState>()
+                .config
+                .lock()
+                .map(|c| (c.settings.launch_on_startup, c.settings.toggle_shortcut.clone()))
+                .unwrap_or_default();
+            if let Err(e) = autostart::apply(app.handle(), launch_on_startup) {
+                tracing::warn!("{}", e);
+            }
+            if let Err(e) = shortcut::apply(app.handle(), toggle_shortcut.as_deref()) {
+                tracing::warn!("{}", e);
+            }
+
+            background::spawn(app.handle().clone());
+            watcher::spawn(app.handle().clone());
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running cldbar");
+        .build(tauri::generate_context!())
+        .expect("error while building cldbar")
+        .run(|app, event| {
+            if let RunEvent::Exit = event {
+                persist_state(app);
+            }
+        });
+}
+
+/// Write in-memory state to disk so the next launch starts warm.
+/// Runs once on exit, including quitting from the tray menu.
+fn persist_state(app: &AppHandle) {
+    let state = app.state::<AppState>();
+
+    if let Ok(history) = state.rate_limit_history.lock() {
+        if let Err(e) = history.save() {
+            tracing::warn!("{}", e);
+        }
+    };
+
+    if let Ok(config) = state.config.lock() {
+        if let Err(e) = profile::save_config(&config) {
+            tracing::warn!("{}", e);
+        }
+    };
 }
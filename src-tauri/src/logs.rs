@@ -0,0 +1,185 @@
+use crate::profile;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+/// Maximum number of log entries kept in memory.
+const CAPACITY: usize = 500;
+
+/// Number of daily log files kept on disk.
+const MAX_LOG_FILES: usize = 7;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEntry {
+    pub timestamp: String,
+    pub level: String,
+    /// Module that emitted the entry, e.g. `cldbar_lib::providers::claude`.
+    pub target: String,
+    pub message: String,
+}
+
+/// Recent log entries, oldest first. Shared between the logger and `AppState`.
+pub type LogBuffer = Arc<Mutex<VecDeque<LogEntry>>>;
+
+/// Layer that keeps the app's own events in a ring buffer for the UI.
+struct RingLayer {
+    buffer: LogBuffer,
+}
+
+impl<S: Subscriber> Layer<S> for RingLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        // Only our own records; dependencies are too noisy to be useful here
+        if *metadata.level() > Level::INFO || !metadata.target().starts_with("cldbar") {
+            return;
+        }
+
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+
+        let entry = LogEntry {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            level: metadata.level().to_string(),
+            target: metadata.target().to_string(),
+            message: redact(&visitor.message),
+        };
+
+        if let Ok(mut buffer) = self.buffer.lock() {
+            if buffer.len() >= CAPACITY {
+                buffer.pop_front();
+            }
+            buffer.push_back(entry);
+        }
+    }
+}
+
+/// Collects an event's message, with any other fields appended as `key=value`.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message.insert_str(0, &format!("{:?}", value));
+        } else {
+            self.message.push_str(&format!(" {}={:?}", field.name(), value));
+        }
+    }
+}
+
+/// Writer that masks secrets with [`redact`] before they reach the log
+/// file. The fmt layer writes each event in one call, so a token is never
+/// split across writes.
+struct RedactingWriter<W: Write>(W);
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write_all(redact(&String::from_utf8_lossy(buf)).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Hands out [`RedactingWriter`]s over another writer, e.g. the log file.
+struct RedactingMakeWriter<M>(M);
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for RedactingMakeWriter<M> {
+    type Writer = RedactingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter(self.0.make_writer())
+    }
+}
+
+/// Install the tracing subscriber and return the in-memory log buffer.
+/// Events also go to a daily rolling file in %APPDATA%/cldbar/logs, with
+/// debug output from the app itself and secrets redacted as in the
+/// buffer. If a subscriber is already installed, the returned buffer stays
+/// empty.
+pub fn init() -> LogBuffer {
+    let buffer: LogBuffer = Arc::new(Mutex::new(VecDeque::with_capacity(CAPACITY)));
+    let ring = RingLayer {
+        buffer: buffer.clone(),
+    };
+
+    let file = match open_log_file() {
+        Ok(appender) => Some(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(RedactingMakeWriter(appender))
+                .with_filter(
+                    Targets::new()
+                        .with_target("cldbar", Level::DEBUG)
+                        .with_default(Level::INFO),
+                ),
+        ),
+        Err(e) => {
+            eprintln!("{}; logging to memory only", e);
+            None
+        }
+    };
+
+    let _ = tracing_subscriber::registry().with(ring).with(file).try_init();
+    buffer
+}
+
+fn open_log_file() -> Result<RollingFileAppender, String> {
+    RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("cldbar")
+        .filename_suffix("log")
+        .max_log_files(MAX_LOG_FILES)
+        .build(profile::logs_dir()?)
+        .map_err(|e| format!("Failed to open log file: {}", e))
+}
+
+/// Mask anything that looks like a secret: `sk-...` API keys and other long
+/// opaque tokens (e.g. z.ai keys, bearer tokens). Dashed tokens such as
+/// session UUIDs are left alone. Keeps a short prefix for recognition.
+pub fn redact(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut token = String::new();
+
+    let flush = |token: &mut String, out: &mut String| {
+        let looks_secret = token.starts_with("sk-")
+            || (token.len() >= 32
+                && !token.contains('-')
+                && token.chars().any(|c| c.is_ascii_digit())
+                && token.chars().any(|c| c.is_ascii_alphabetic()));
+        if looks_secret {
+            out.extend(token.chars().take(6));
+            out.push_str("…[redacted]");
+        } else {
+            out.push_str(token);
+        }
+        token.clear();
+    };
+
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+            token.push(c);
+        } else {
+            flush(&mut token, &mut out);
+            out.push(c);
+        }
+    }
+    flush(&mut token, &mut out);
+
+    out
+}
@@ -0,0 +1,210 @@
+use crate::profile;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+
+/// USD per million tokens.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelRates {
+    pub input: f64,
+    pub output: f64,
+    #[serde(default)]
+    pub cache_read: f64,
+    /// 5-minute cache writes, and all cache writes from providers that don't
+    /// report the TTL.
+    #[serde(default)]
+    pub cache_write: f64,
+    /// 1-hour cache writes. Defaults to twice the input rate.
+    #[serde(default)]
+    pub cache_write_1h: Option<f64>,
+}
+
+impl ModelRates {
+    const fn new(input: f64, output: f64, cache_read: f64, cache_write: f64) -> Self {
+        Self {
+            input,
+            output,
+            cache_read,
+            cache_write,
+            cache_write_1h: None,
+        }
+    }
+
+    /// Unrounded cost in USD for the given token counts.
+    pub fn cost(
+        &self,
+        input_tokens: u64,
+        output_tokens: u64,
+        cache_read_tokens: u64,
+        cache_write_tokens: u64,
+    ) -> f64 {
+        (input_tokens as f64 * self.input
+            + output_tokens as f64 * self.output
+            + cache_read_tokens as f64 * self.cache_read
+            + cache_write_tokens as f64 * self.cache_write)
+            / 1_000_000.0
+    }
+
+    /// Unrounded cost in USD for cache writes with a 1-hour TTL.
+    pub fn cache_write_1h_cost(&self, tokens: u64) -> f64 {
+        let rate = self.cache_write_1h.unwrap_or(self.input * 2.0);
+        tokens as f64 * rate / 1_000_000.0
+    }
+}
+
+/// Rates for models whose lowercased name contains `pattern`.
+/// An empty pattern matches every model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PricingRule {
+    pub pattern: String,
+    #[serde(flatten)]
+    pub rates: ModelRates,
+}
+
+/// Per-provider pricing rules, checked in order; the first match wins.
+/// Loaded from %APPDATA%/cldbar/pricing.json, where each provider's list
+/// is optional and replaces the built-in one when present.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PricingTable {
+    #[serde(default = "default_claude_rules")]
+    pub claude: Vec<PricingRule>,
+    #[serde(default = "default_gemini_rules")]
+    pub gemini: Vec<PricingRule>,
+    #[serde(default = "default_openai_rules")]
+    pub openai: Vec<PricingRule>,
+    #[serde(default = "default_zai_rules")]
+    pub zai: Vec<PricingRule>,
+}
+
+fn rule(pattern: &str, rates: ModelRates) -> PricingRule {
+    PricingRule {
+        pattern: pattern.to_string(),
+        rates,
+    }
+}
+
+fn default_claude_rules() -> Vec<PricingRule> {
+    vec![
+        // Cache read is 90% discount, 5-minute cache write is 25% premium
+        rule("opus", ModelRates::new(15.0, 75.0, 1.50, 18.75)),
+        rule("haiku", ModelRates::new(0.25, 1.25, 0.025, 0.3125)),
+        // Sonnet and default
+        rule("", ModelRates::new(3.0, 15.0, 0.30, 3.75)),
+    ]
+}
+
+fn default_gemini_rules() -> Vec<PricingRule> {
+    // Cached input is billed at a quarter of the input rate.
+    // Most specific first: "flash-lite" must not fall into the "flash" rule
+    vec![
+        // Experimental models are free during preview
+        rule("exp", ModelRates::new(0.0, 0.0, 0.0, 0.0)),
+        rule("flash-lite", ModelRates::new(0.10, 0.40, 0.025, 0.0)),
+        rule("2.0-flash", ModelRates::new(0.10, 0.40, 0.025, 0.0)),
+        rule("flash", ModelRates::new(0.15, 0.60, 0.0375, 0.0)),
+        rule("1.5-pro", ModelRates::new(1.25, 5.0, 0.3125, 0.0)),
+        // gemini-2.5-pro and default
+        rule("", ModelRates::new(1.25, 10.0, 0.3125, 0.0)),
+    ]
+}
+
+fn default_openai_rules() -> Vec<PricingRule> {
+    // Cached input is billed at the discounted rate; mini variants first so
+    // they don't fall into their base model's rule
+    vec![
+        rule("gpt-5-nano", ModelRates::new(0.05, 0.40, 0.005, 0.0)),
+        rule("gpt-5-mini", ModelRates::new(0.25, 2.0, 0.025, 0.0)),
+        rule("gpt-5", ModelRates::new(1.25, 10.0, 0.125, 0.0)),
+        rule("gpt-4.1-mini", ModelRates::new(0.40, 1.60, 0.10, 0.0)),
+        rule("gpt-4.1", ModelRates::new(2.0, 8.0, 0.50, 0.0)),
+        rule("gpt-4o-mini", ModelRates::new(0.15, 0.60, 0.075, 0.0)),
+        rule("o4-mini", ModelRates::new(1.10, 4.40, 0.275, 0.0)),
+        rule("codex-mini", ModelRates::new(1.50, 6.0, 0.375, 0.0)),
+        rule("o3-mini", ModelRates::new(1.10, 4.40, 0.55, 0.0)),
+        rule("o3", ModelRates::new(2.0, 8.0, 0.50, 0.0)),
+        rule("o1", ModelRates::new(15.0, 60.0, 7.50, 0.0)),
+        // gpt-4o and default
+        rule("", ModelRates::new(2.50, 10.0, 1.25, 0.0)),
+    ]
+}
+
+fn default_zai_rules() -> Vec<PricingRule> {
+    // z.ai doesn't report cache usage
+    vec![
+        // GLM-4-Flash / GLM-4.5-Flash are free
+        rule("flash", ModelRates::new(0.0, 0.0, 0.0, 0.0)),
+        rule("air", ModelRates::new(0.20, 1.10, 0.0, 0.0)),
+        // GLM-4.6, GLM-4.5 and default
+        rule("", ModelRates::new(0.60, 2.20, 0.0, 0.0)),
+    ]
+}
+
+impl Default for PricingTable {
+    fn default() -> Self {
+        Self {
+            claude: default_claude_rules(),
+            gemini: default_gemini_rules(),
+            openai: default_openai_rules(),
+            zai: default_zai_rules(),
+        }
+    }
+}
+
+impl PricingTable {
+    /// Load pricing.json, falling back to the built-in rates if it is
+    /// missing or invalid.
+    fn load() -> Self {
+        let path = match pricing_file_path() {
+            Ok(p) => p,
+            Err(_) => return Self::default(),
+        };
+        let data = match fs::read_to_string(&path) {
+            Ok(d) => d,
+            Err(_) => return Self::default(),
+        };
+        serde_json::from_str(&data).unwrap_or_else(|e| {
+            tracing::warn!("Failed to parse {}: {}; using built-in rates", path.display(), e);
+            Self::default()
+        })
+    }
+
+    pub fn claude_rates(&self, model: &str) -> ModelRates {
+        find_rates(&self.claude, model)
+    }
+
+    pub fn gemini_rates(&self, model: &str) -> ModelRates {
+        find_rates(&self.gemini, model)
+    }
+
+    pub fn openai_rates(&self, model: &str) -> ModelRates {
+        find_rates(&self.openai, model)
+    }
+
+    pub fn zai_rates(&self, model: &str) -> ModelRates {
+        find_rates(&self.zai, model)
+    }
+}
+
+fn find_rates(rules: &[PricingRule], model: &str) -> ModelRates {
+    let model_lower = model.to_lowercase();
+    rules
+        .iter()
+        .find(|r| model_lower.contains(&r.pattern.to_lowercase()))
+        .map(|r| r.rates)
+        .unwrap_or_default()
+}
+
+fn pricing_file_path() -> Result<PathBuf, String> {
+    Ok(profile::data_dir()?.join("pricing.json"))
+}
+
+static TABLE: LazyLock<PricingTable> = LazyLock::new(PricingTable::load);
+
+/// The pricing table, read from disk on first use.
+pub fn table() -> &'static PricingTable {
+    &TABLE
+}
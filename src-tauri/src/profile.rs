@@ -1,3 +1,4 @@
+use crate::secrets;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
@@ -21,12 +22,42 @@ pub struct Profile {
     pub source_type: String,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub api_key: Option<String>,
+    /// Self-imposed daily token cap. `None` disables budget tracking.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_token_budget: Option<u64>,
+    /// Claude only: folder under `config_dir` holding session files.
+    /// May be a symlink, e.g. to project data moved to another drive.
+    #[serde(default = "default_projects_subdir")]
+    pub projects_subdir: String,
+    /// Claude only: more config directories aggregated with `config_dir`,
+    /// e.g. an old one kept after a migration.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub config_dirs: Vec<String>,
+    /// `api_key` is kept in the OS keychain and left out of config.json.
+    #[serde(default)]
+    pub api_key_in_keychain: bool,
+}
+
+impl Profile {
+    /// `config_dir` with `~` and environment variables expanded.
+    pub fn expanded_config_dir(&self) -> PathBuf {
+        expand_path(&self.config_dir)
+    }
+
+    /// `config_dirs` with `~` and environment variables expanded.
+    pub fn expanded_extra_dirs(&self) -> Vec<PathBuf> {
+        self.config_dirs.iter().map(|d| expand_path(d)).collect()
+    }
 }
 
 fn default_source_type() -> String {
     "account".to_string()
 }
 
+fn default_projects_subdir() -> String {
+    "projects".to_string()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AppSettings {
@@ -35,15 +66,163 @@ pub struct AppSettings {
     pub launch_on_startup: bool,
     pub notifications_enabled: bool,
     pub token_alert_threshold: u64,
+    /// ISO 4217 code costs are displayed in. Costs are always computed in USD.
+    #[serde(default = "default_display_currency")]
+    pub display_currency: String,
+    /// BCP 47 tag (e.g. `de-DE`) used to format numbers and costs in the UI.
+    /// Empty follows the system locale.
+    #[serde(default)]
+    pub locale: String,
+    /// Multiplier from USD to `display_currency`. User-provided unless
+    /// `fx_rate_url` is set, in which case it is refreshed from that endpoint.
+    #[serde(default = "default_fx_rate")]
+    pub fx_rate_usd_to_display: f64,
+    /// Optional endpoint returning `{ "rates": { "<CURRENCY>": <rate> } }` for USD.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fx_rate_url: Option<String>,
+    /// Models to leave out of all stats (case-insensitive substring match).
+    #[serde(default)]
+    pub excluded_models: Vec<String>,
+    /// Projects to leave out of session lists and stats (case-insensitive
+    /// substring match against the project path or name).
+    #[serde(default)]
+    pub excluded_projects: Vec<String>,
+    /// Report cache read/write tokens as part of input tokens instead of in
+    /// their own fields. Applies to usage stats and daily usage alike.
+    #[serde(default)]
+    pub cache_counts_as_input: bool,
+    /// Enables diagnostic commands such as `debug_raw_usage`.
+    #[serde(default)]
+    pub debug_mode: bool,
+    /// Seconds since a session's last activity for it to still count as active.
+    #[serde(default = "default_active_threshold_secs")]
+    pub active_threshold_secs: u64,
+    /// Proxy for API requests, e.g. `http://proxy.corp:8080`. When unset,
+    /// the HTTPS_PROXY/HTTP_PROXY/NO_PROXY environment variables apply.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_url: Option<String>,
+    /// How long API providers reuse fetched usage, in seconds. Zero always fetches.
+    #[serde(default = "default_api_cache_ttl_secs")]
+    pub api_cache_ttl_secs: u64,
+    /// Where the popover was last left (physical pixels). Unset until it's
+    /// first shown, when it opens above the tray icon instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_x: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_y: Option<i32>,
+    /// Hide the popover when it loses focus, like a menu.
+    #[serde(default = "default_hide_on_blur")]
+    pub hide_on_blur: bool,
+    /// Global shortcut toggling the popover, e.g. `CmdOrCtrl+Shift+U`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub toggle_shortcut: Option<String>,
+}
+
+fn default_display_currency() -> String {
+    "USD".to_string()
+}
+
+fn default_fx_rate() -> f64 {
+    1.0
+}
+
+fn default_active_threshold_secs() -> u64 {
+    30 * 60
+}
+
+fn default_api_cache_ttl_secs() -> u64 {
+    60
+}
+
+fn default_hide_on_blur() -> bool {
+    true
 }
 
 /// Get the path to the cldbar config file: %APPDATA%/cldbar/config.json
+/// The `CLDBAR_CONFIG_PATH` env var overrides this with a full file path
+/// (not a directory), e.g. for portable installs or tests.
 fn config_file_path() -> Result<PathBuf, String> {
+    if let Ok(path) = std::env::var("CLDBAR_CONFIG_PATH") {
+        if !path.is_empty() {
+            return Ok(PathBuf::from(path));
+        }
+    }
+
     let config_dir = dirs::config_dir()
         .ok_or_else(|| "Could not determine config directory".to_string())?;
     Ok(config_dir.join("cldbar").join("config.json"))
 }
 
+/// Directory holding cldbar's own data files (the config file's directory).
+pub fn data_dir() -> Result<PathBuf, String> {
+    config_file_path()?
+        .parent()
+        .map(|p| p.to_path_buf())
+        .ok_or_else(|| "Could not determine data directory".to_string())
+}
+
+/// Directory for disk-backed caches: %APPDATA%/cldbar/cache
+pub fn cache_dir() -> Result<PathBuf, String> {
+    Ok(data_dir()?.join("cache"))
+}
+
+/// Directory for the rolling log files: %APPDATA%/cldbar/logs
+pub fn logs_dir() -> Result<PathBuf, String> {
+    Ok(data_dir()?.join("logs"))
+}
+
+/// Expand a leading `~` to the home directory and substitute `$VAR`,
+/// `${VAR}` and `%VAR%` environment variables. Unset variables are left as
+/// written, so the path fails validation instead of silently changing.
+pub fn expand_path(path: &str) -> PathBuf {
+    let expanded = expand_env_vars(path);
+
+    if expanded == "~" || expanded.starts_with("~/") || expanded.starts_with("~\\") {
+        if let Some(home) = dirs::home_dir() {
+            let rest = expanded[1..].trim_start_matches(['/', '\\']);
+            return if rest.is_empty() { home } else { home.join(rest) };
+        }
+    }
+
+    PathBuf::from(expanded)
+}
+
+fn expand_env_vars(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    let mut rest = path;
+
+    while let Some(pos) = rest.find(['$', '%']) {
+        out.push_str(&rest[..pos]);
+        let tail = &rest[pos..];
+
+        // (variable name, length of the whole reference)
+        let reference = if let Some(braced) = tail.strip_prefix("${") {
+            braced.find('}').map(|end| (&braced[..end], end + 3))
+        } else if let Some(unix) = tail.strip_prefix('$') {
+            let end = unix
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(unix.len());
+            Some((&unix[..end], end + 1))
+        } else {
+            tail[1..].find('%').map(|end| (&tail[1..end + 1], end + 2))
+        };
+
+        match reference {
+            Some((name, len)) if !name.is_empty() => match std::env::var(name) {
+                Ok(value) => out.push_str(&value),
+                Err(_) => out.push_str(&tail[..len]),
+            },
+            Some((_, len)) => out.push_str(&tail[..len]),
+            None => out.push_str(&tail[..1]),
+        }
+        let consumed = reference.map(|(_, len)| len).unwrap_or(1);
+        rest = &tail[consumed..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
 /// Load the app configuration from disk.
 /// Creates a default config if the file does not exist.
 pub fn load_config() -> Result<AppConfig, String> {
@@ -58,8 +237,16 @@ pub fn load_config() -> Result<AppConfig, String> {
     let data = fs::read_to_string(&path)
         .map_err(|e| format!("Failed to read config file: {}", e))?;
 
-    serde_json::from_str(&data)
-        .map_err(|e| format!("Failed to parse config file: {}", e))
+    let mut config: AppConfig = serde_json::from_str(&data)
+        .map_err(|e| format!("Failed to parse config file: {}", e))?;
+
+    secrets::load_api_keys(&mut config);
+    // Move keys left in the file by older versions into the keychain
+    if secrets::secure_api_keys(&mut config) {
+        save_config(&config)?;
+    }
+
+    Ok(config)
 }
 
 /// Save the app configuration to disk.
@@ -72,7 +259,15 @@ pub fn save_config(config: &AppConfig) -> Result<(), String> {
             .map_err(|e| format!("Failed to create config directory: {}", e))?;
     }
 
-    let data = serde_json::to_string_pretty(config)
+    // Keychain-backed keys stay out of the file
+    let mut on_disk = config.clone();
+    for profile in &mut on_disk.profiles {
+        if profile.api_key_in_keychain {
+            profile.api_key = None;
+        }
+    }
+
+    let data = serde_json::to_string_pretty(&on_disk)
         .map_err(|e| format!("Failed to serialize config: {}", e))?;
 
     fs::write(&path, data)
@@ -83,9 +278,13 @@ pub fn save_config(config: &AppConfig) -> Result<(), String> {
 pub fn default_config() -> AppConfig {
     let mut profiles = Vec::new();
 
-    // Auto-detect Claude: check if ~/.claude/ exists
+    // Auto-detect Claude: check CLAUDE_CONFIG_DIR, then ~/.claude/
     if let Some(home) = dirs::home_dir() {
-        let claude_dir = home.join(".claude");
+        let claude_dir = std::env::var("CLAUDE_CONFIG_DIR")
+            .ok()
+            .filter(|d| !d.is_empty())
+            .map(|d| expand_path(&d))
+            .unwrap_or_else(|| home.join(".claude"));
         if claude_dir.exists() {
             profiles.push(Profile {
                 id: "claude-default".to_string(),
@@ -95,6 +294,10 @@ pub fn default_config() -> AppConfig {
                 enabled: true,
                 source_type: "account".to_string(),
                 api_key: None,
+                daily_token_budget: None,
+                projects_subdir: default_projects_subdir(),
+                config_dirs: Vec::new(),
+                api_key_in_keychain: false,
             });
         }
 
@@ -109,6 +312,28 @@ pub fn default_config() -> AppConfig {
                 enabled: true,
                 source_type: "account".to_string(),
                 api_key: None,
+                daily_token_budget: None,
+                projects_subdir: default_projects_subdir(),
+                config_dirs: Vec::new(),
+                api_key_in_keychain: false,
+            });
+        }
+
+        // Auto-detect the Codex CLI: check if ~/.codex/ exists
+        let codex_dir = home.join(".codex");
+        if codex_dir.exists() {
+            profiles.push(Profile {
+                id: "openai-default".to_string(),
+                name: "OpenAI".to_string(),
+                provider_type: "openai".to_string(),
+                config_dir: codex_dir.to_string_lossy().to_string(),
+                enabled: true,
+                source_type: "account".to_string(),
+                api_key: None,
+                daily_token_budget: None,
+                projects_subdir: default_projects_subdir(),
+                config_dirs: Vec::new(),
+                api_key_in_keychain: false,
             });
         }
     }
@@ -125,6 +350,10 @@ pub fn default_config() -> AppConfig {
                 enabled: true,
                 source_type: "account".to_string(),
                 api_key: None,
+                daily_token_budget: None,
+                projects_subdir: default_projects_subdir(),
+                config_dirs: Vec::new(),
+                api_key_in_keychain: false,
             });
         }
     }
@@ -137,6 +366,21 @@ pub fn default_config() -> AppConfig {
             launch_on_startup: false,
             notifications_enabled: true,
             token_alert_threshold: 1_000_000,
+            display_currency: default_display_currency(),
+            locale: String::new(),
+            fx_rate_usd_to_display: default_fx_rate(),
+            fx_rate_url: None,
+            excluded_models: Vec::new(),
+            excluded_projects: Vec::new(),
+            cache_counts_as_input: false,
+            debug_mode: false,
+            active_threshold_secs: default_active_threshold_secs(),
+            proxy_url: None,
+            api_cache_ttl_secs: default_api_cache_ttl_secs(),
+            window_x: None,
+            window_y: None,
+            hide_on_blur: default_hide_on_blur(),
+            toggle_shortcut: None,
         },
     }
 }
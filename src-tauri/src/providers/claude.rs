@@ -1,17 +1,35 @@
-use super::{DailyUsage, ModelUsage, Provider, RateLimitStatus, RateLimitWindow, Session, UsageStats};
+use super::source::{FileSource, RealFs};
+use super::{
+    BillingMode, DailyModelUsage, DailyUsage, Diagnostics, FileStatus, JsonlLines,
+    ModelUsage, Provider, ProviderError, RateLimitStatus, RateLimitWindow, Session, UsageStats,
+};
+use crate::pricing;
+use crate::profile;
+use chrono::{DateTime, Local, NaiveDate, Utc};
 use serde::Deserialize;
-use std::collections::HashMap;
-use std::fs;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
 use std::time::{Duration, SystemTime};
 
 pub struct ClaudeProvider {
     config_dir: PathBuf,
+    /// More config directories read alongside `config_dir`, e.g. one left
+    /// behind after moving `CLAUDE_CONFIG_DIR`.
+    extra_dirs: Vec<PathBuf>,
+    /// Session folder under `config_dir`, normally "projects".
+    projects_subdir: String,
+    /// How recently a session must have had activity to count as active.
+    active_threshold: Duration,
+    /// Where config and session files are read from.
+    source: Arc<dyn FileSource>,
+    /// Malformed lines skipped in each session file, as of its last parse.
+    skipped_lines: Mutex<HashMap<PathBuf, u32>>,
 }
 
 // --- Deserialization types for stats-cache.json ---
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct StatsCache {
     #[serde(default)]
@@ -22,11 +40,12 @@ struct StatsCache {
     total_messages: u32,
     #[serde(default)]
     daily_activity: Vec<DailyActivity>,
-    #[serde(default)]
-    daily_model_tokens: Vec<DailyModelTokens>,
+    /// Not in stats-cache.json; set when the totals are rebuilt from session files.
+    #[serde(skip)]
+    total_tool_calls: Option<u32>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct StatsModelUsage {
     #[serde(default)]
@@ -51,16 +70,41 @@ struct DailyActivity {
     #[serde(default)]
     session_count: u32,
     #[serde(default, rename = "toolCallCount")]
-    _tool_call_count: Option<u32>,
+    tool_call_count: Option<u32>,
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct DailyModelTokens {
-    #[serde(default)]
-    date: String,
-    #[serde(default)]
-    tokens_by_model: HashMap<String, u64>,
+impl StatsCache {
+    /// Add the totals of another config directory's cache to this one.
+    fn merge(&mut self, other: StatsCache) {
+        for (model, usage) in other.model_usage {
+            let entry = self.model_usage.entry(model).or_default();
+            entry.input_tokens += usage.input_tokens;
+            entry.output_tokens += usage.output_tokens;
+            entry.cache_read_input_tokens += usage.cache_read_input_tokens;
+            entry.cache_creation_input_tokens += usage.cache_creation_input_tokens;
+        }
+        self.total_sessions += other.total_sessions;
+        self.total_messages += other.total_messages;
+
+        for day in other.daily_activity {
+            match self.daily_activity.iter_mut().find(|d| d.date == day.date) {
+                Some(existing) => {
+                    existing.message_count += day.message_count;
+                    existing.session_count += day.session_count;
+                    existing.tool_call_count = match (existing.tool_call_count, day.tool_call_count) {
+                        (Some(a), Some(b)) => Some(a + b),
+                        (a, b) => a.or(b),
+                    };
+                }
+                None => self.daily_activity.push(day),
+            }
+        }
+
+        self.total_tool_calls = match (self.total_tool_calls, other.total_tool_calls) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+    }
 }
 
 // --- Deserialization types for JSONL session lines ---
@@ -83,6 +127,25 @@ struct SessionMessage {
     model: Option<String>,
     #[serde(default)]
     usage: Option<SessionUsage>,
+    /// Content blocks; kept loose since user messages may be a plain string.
+    #[serde(default)]
+    content: Option<serde_json::Value>,
+}
+
+impl SessionMessage {
+    /// Number of `tool_use` blocks in the message content.
+    fn tool_use_count(&self) -> u32 {
+        self.content
+            .as_ref()
+            .and_then(|c| c.as_array())
+            .map(|blocks| {
+                blocks
+                    .iter()
+                    .filter(|b| b.get("type").and_then(|t| t.as_str()) == Some("tool_use"))
+                    .count() as u32
+            })
+            .unwrap_or(0)
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -103,6 +166,9 @@ struct SessionUsage {
 struct OAuthCredentials {
     #[serde(default, rename = "accessToken")]
     access_token: Option<String>,
+    /// Plan of the logged-in account ("pro", "max", ...); absent for API-key logins.
+    #[serde(default, rename = "subscriptionType")]
+    subscription_type: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -118,26 +184,170 @@ struct OAuthUsageWindow {
     resets_at: Option<String>,
 }
 
+/// Decode a Claude project directory name back into the original path.
+/// Claude replaces path separators (and `:` / `.`) with `-`, so
+/// `-Users-me-code-myrepo` came from `/Users/me/code/myrepo` and
+/// `C--Users-me-code` from `C:\Users\me\code`. The encoding is lossy, so when
+/// the naive decoding doesn't exist locally, dashes that belong to a
+/// directory name are recovered by probing the filesystem.
+fn decode_project_dir(encoded: &str) -> String {
+    let (root, rest, sep) = if let Some(rest) = encoded.strip_prefix('-') {
+        ("/".to_string(), rest, "/")
+    } else if encoded.len() > 3
+        && encoded.as_bytes()[0].is_ascii_alphabetic()
+        && encoded.get(1..3) == Some("--")
+    {
+        (format!("{}:\\", &encoded[..1]), &encoded[3..], "\\")
+    } else {
+        return encoded.to_string();
+    };
+
+    // A doubled dash marks a dot-prefixed component (`/.config` -> `--config`).
+    let mut parts: Vec<String> = Vec::new();
+    let mut hidden = false;
+    for token in rest.split('-') {
+        if token.is_empty() {
+            hidden = true;
+        } else if hidden {
+            parts.push(format!(".{}", token));
+            hidden = false;
+        } else {
+            parts.push(token.to_string());
+        }
+    }
+
+    let naive = format!("{}{}", root, parts.join(sep));
+    if PathBuf::from(&naive).exists() {
+        return naive;
+    }
+
+    // Greedily join tokens with `-` until the component exists on disk.
+    let mut base = PathBuf::from(&root);
+    let mut segments: Vec<String> = Vec::new();
+    let mut component: Option<String> = None;
+    for part in parts {
+        component = Some(match component {
+            None => part,
+            Some(c) if base.join(&c).is_dir() => {
+                base.push(&c);
+                segments.push(c);
+                part
+            }
+            Some(c) => format!("{}-{}", c, part),
+        });
+    }
+    segments.extend(component);
+
+    let resolved = format!("{}{}", root, segments.join(sep));
+    if PathBuf::from(&resolved).exists() {
+        resolved
+    } else {
+        naive
+    }
+}
+
 impl ClaudeProvider {
-    pub fn new(config_dir: PathBuf) -> Self {
-        Self { config_dir }
+    pub fn new(
+        config_dir: PathBuf,
+        extra_dirs: Vec<PathBuf>,
+        projects_subdir: String,
+        active_threshold: Duration,
+    ) -> Self {
+        Self::with_source(config_dir, extra_dirs, projects_subdir, active_threshold, Arc::new(RealFs))
+    }
+
+    /// A provider reading its files from `source` instead of the disk.
+    pub fn with_source(
+        config_dir: PathBuf,
+        extra_dirs: Vec<PathBuf>,
+        projects_subdir: String,
+        active_threshold: Duration,
+        source: Arc<dyn FileSource>,
+    ) -> Self {
+        Self {
+            config_dir,
+            extra_dirs,
+            projects_subdir,
+            active_threshold,
+            source,
+            skipped_lines: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Determine the Claude config directory.
+    /// CLAUDE_CONFIG_DIR only replaces the default `~/.claude`; a profile
+    /// pointed at another directory keeps it.
+    fn effective_dir(&self) -> PathBuf {
+        let is_default = dirs::home_dir().is_some_and(|home| self.config_dir == home.join(".claude"));
+        if !is_default {
+            return self.config_dir.clone();
+        }
+        std::env::var("CLAUDE_CONFIG_DIR")
+            .ok()
+            .filter(|d| !d.is_empty())
+            .map(|d| profile::expand_path(&d))
+            .unwrap_or_else(|| self.config_dir.clone())
+    }
+
+    /// The session folder, resolved through symlinks so a `projects` link to
+    /// another volume is scanned at its real location.
+    fn projects_dir(&self) -> PathBuf {
+        self.projects_dir_in(&self.effective_dir())
+    }
+
+    fn projects_dir_in(&self, config_dir: &Path) -> PathBuf {
+        let dir = config_dir.join(&self.projects_subdir);
+        self.source.canonicalize(&dir).unwrap_or(dir)
+    }
+
+    /// The main config directory followed by the extra ones, without repeats.
+    fn config_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = vec![self.effective_dir()];
+        for dir in &self.extra_dirs {
+            if !dirs.contains(dir) {
+                dirs.push(dir.clone());
+            }
+        }
+        dirs
+    }
+
+    /// The session folder of every config directory, without repeats (two
+    /// config directories may link to the same one).
+    fn projects_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        for dir in self.config_dirs().iter().map(|d| self.projects_dir_in(d)) {
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+        dirs
+    }
+
+    fn read_credentials(&self) -> Option<OAuthCredentials> {
+        let creds_path = self.effective_dir().join(".credentials.json");
+        let data = self.source.read_to_string(&creds_path)
+            .map_err(|e| tracing::debug!("No credentials at {}: {}", creds_path.display(), e))
+            .ok()?;
+        serde_json::from_str(&data)
+            .map_err(|e| tracing::warn!("Failed to parse {}: {}", creds_path.display(), e))
+            .ok()
     }
 
     /// Read OAuth access token from .credentials.json
     fn read_oauth_token(&self) -> Option<String> {
-        let creds_path = self.config_dir.join(".credentials.json");
-        let data = fs::read_to_string(&creds_path).ok()?;
-        let creds: OAuthCredentials = serde_json::from_str(&data).ok()?;
-        creds.access_token
+        self.read_credentials()?.access_token
     }
 
     /// Fetch rate limit utilization from Claude OAuth usage API.
-    pub fn get_rate_limit_status(&self) -> RateLimitStatus {
+    fn fetch_rate_limit_status(&self) -> RateLimitStatus {
         let unavailable = RateLimitStatus {
             available: false,
             five_hour: None,
             seven_day: None,
             seven_day_opus: None,
+            window_started_at: None,
+            sessions_in_window: None,
+            messages_in_window: None,
         };
 
         let token = match self.read_oauth_token() {
@@ -150,7 +360,10 @@ impl ClaudeProvider {
             .build()
         {
             Ok(c) => c,
-            Err(_) => return unavailable,
+            Err(e) => {
+                tracing::warn!("Failed to build HTTP client: {}", e);
+                return unavailable;
+            }
         };
 
         let resp = client
@@ -161,137 +374,478 @@ impl ClaudeProvider {
 
         match resp {
             Ok(r) if r.status().is_success() => match r.json::<OAuthUsageResponse>() {
-                Ok(usage) => RateLimitStatus {
-                    available: true,
-                    five_hour: usage.five_hour.map(|w| RateLimitWindow {
-                        label: "5-Hour".to_string(),
-                        utilization: w.utilization,
-                        resets_at: w.resets_at,
-                    }),
-                    seven_day: usage.seven_day.map(|w| RateLimitWindow {
-                        label: "7-Day".to_string(),
-                        utilization: w.utilization,
-                        resets_at: w.resets_at,
-                    }),
-                    seven_day_opus: usage.seven_day_opus.map(|w| RateLimitWindow {
-                        label: "7-Day Opus".to_string(),
-                        utilization: w.utilization,
-                        resets_at: w.resets_at,
-                    }),
-                },
-                Err(_) => unavailable,
+                Ok(usage) => {
+                    // The window resets 5 hours after it starts
+                    let window_start = usage
+                        .five_hour
+                        .as_ref()
+                        .and_then(|w| w.resets_at.as_deref())
+                        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+                        .map(|t| t.with_timezone(&Utc) - chrono::Duration::hours(5))
+                        .unwrap_or_else(|| Utc::now() - chrono::Duration::hours(5));
+                    let (sessions, messages) = self.activity_since(window_start);
+
+                    RateLimitStatus {
+                        available: true,
+                        five_hour: usage.five_hour.map(|w| RateLimitWindow {
+                            label: "5-Hour".to_string(),
+                            utilization: w.utilization,
+                            resets_at: w.resets_at,
+                        }),
+                        seven_day: usage.seven_day.map(|w| RateLimitWindow {
+                            label: "7-Day".to_string(),
+                            utilization: w.utilization,
+                            resets_at: w.resets_at,
+                        }),
+                        seven_day_opus: usage.seven_day_opus.map(|w| RateLimitWindow {
+                            label: "7-Day Opus".to_string(),
+                            utilization: w.utilization,
+                            resets_at: w.resets_at,
+                        }),
+                        window_started_at: Some(window_start.to_rfc3339()),
+                        sessions_in_window: Some(sessions),
+                        messages_in_window: Some(messages),
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to parse Claude OAuth usage response: {}", e);
+                    unavailable
+                }
             },
-            _ => unavailable,
+            Ok(r) => {
+                tracing::warn!("Claude OAuth usage request returned {}", r.status());
+                unavailable
+            }
+            Err(e) => {
+                tracing::warn!("Claude OAuth usage request failed: {}", e);
+                unavailable
+            }
         }
     }
 
+    /// The stats-cache.json of every config directory merged into one, or
+    /// `None` when none of them has one.
     fn read_stats_cache(&self) -> Option<StatsCache> {
-        let path = self.config_dir.join("stats-cache.json");
-        let data = fs::read_to_string(&path).ok()?;
-        serde_json::from_str(&data).ok()
+        self.config_dirs()
+            .iter()
+            .filter_map(|dir| self.read_stats_cache_in(dir))
+            .reduce(|mut merged, cache| {
+                merged.merge(cache);
+                merged
+            })
     }
 
-    /// Estimate cost in USD for a given model name and token counts.
-    fn estimate_cost(
+    fn read_stats_cache_in(&self, config_dir: &Path) -> Option<StatsCache> {
+        let path = config_dir.join("stats-cache.json");
+        let data = self.source.read_to_string(&path).ok()?;
+        serde_json::from_str(&data)
+            .map_err(|e| tracing::warn!("Failed to parse {}: {}", path.display(), e))
+            .ok()
+    }
+
+    /// Whether any config directory's stats-cache.json is missing or older
+    /// than the newest of its `session_files`, i.e. sessions have run since
+    /// Claude last rewrote it. A directory with no sessions needs no cache.
+    fn stats_cache_outdated(&self, session_files: &[PathBuf]) -> bool {
+        let modified = |p: &PathBuf| self.source.modified(p).ok();
+        self.config_dirs().iter().any(|dir| {
+            let projects_dir = self.projects_dir_in(dir);
+            let mut files = session_files.iter().filter(|f| f.starts_with(&projects_dir));
+            match modified(&dir.join("stats-cache.json")) {
+                Some(cache_modified) => files.filter_map(modified).any(|m| m > cache_modified),
+                None => files.next().is_some(),
+            }
+        })
+    }
+
+    /// Rebuild the stats-cache.json totals from the assistant lines of the
+    /// JSONL session files. Used when the cache is missing or outdated.
+    fn stats_from_session_files(&self, session_files: &[PathBuf]) -> StatsCache {
+        let mut model_usage: HashMap<String, StatsModelUsage> = HashMap::new();
+        let mut session_ids: HashSet<String> = HashSet::new();
+        let mut total_messages: u32 = 0;
+        let mut total_tool_calls: u32 = 0;
+
+        for path in session_files {
+            let entries = match JsonlLines::<SessionLine>::open(self.source.as_ref(), path) {
+                Ok(e) => e,
+                Err(e) => {
+                    tracing::warn!("Failed to read {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+
+            for entry in entries {
+                if entry.line_type.as_deref() != Some("assistant") {
+                    continue;
+                }
+                let msg = match entry.message {
+                    Some(m) => m,
+                    None => continue,
+                };
+                total_tool_calls += msg.tool_use_count();
+                let usage = match msg.usage {
+                    Some(u) => u,
+                    None => continue,
+                };
+
+                let model = msg.model.unwrap_or_else(|| "unknown".to_string());
+                let totals = model_usage.entry(model).or_default();
+                totals.input_tokens += usage.input_tokens;
+                totals.output_tokens += usage.output_tokens;
+                totals.cache_read_input_tokens += usage.cache_read_input_tokens;
+                totals.cache_creation_input_tokens += usage.cache_creation_input_tokens;
+                total_messages += 1;
+
+                // Older files may lack sessionId; the file name is the session UUID
+                let session_id = entry.session_id.unwrap_or_else(|| {
+                    path.file_stem()
+                        .map(|s| s.to_string_lossy().to_string())
+                        .unwrap_or_default()
+                });
+                session_ids.insert(session_id);
+            }
+        }
+
+        StatsCache {
+            model_usage,
+            total_sessions: session_ids.len() as u32,
+            total_messages,
+            total_tool_calls: Some(total_tool_calls),
+            ..Default::default()
+        }
+    }
+
+    /// Call `f` with every assistant line of the session files that carries
+    /// usage and falls on a local date on or after `since`, along with the
+    /// file, the date, the line's session id and its usage. Files untouched
+    /// since the window opened are skipped without being read.
+    fn for_each_usage_line_since(
+        &self,
+        since: NaiveDate,
+        mut f: impl FnMut(&Path, NaiveDate, Option<String>, SessionMessage, SessionUsage),
+    ) {
+        let since_time = since
+            .and_hms_opt(0, 0, 0)
+            .and_then(|t| t.and_local_timezone(Local).earliest())
+            .map(SystemTime::from);
+
+        for path in self.find_session_files() {
+            // Files untouched since the window opened can't contain lines in it
+            let modified = self.source.modified(&path).ok();
+            if let (Some(modified), Some(since_time)) = (modified, since_time) {
+                if modified < since_time {
+                    continue;
+                }
+            }
+            let entries = match JsonlLines::<SessionLine>::open(self.source.as_ref(), &path) {
+                Ok(e) => e,
+                Err(e) => {
+                    tracing::warn!("Failed to read {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+
+            for entry in entries {
+                if entry.line_type.as_deref() != Some("assistant") {
+                    continue;
+                }
+                let mut msg = match entry.message {
+                    Some(m) => m,
+                    None => continue,
+                };
+                let usage = match msg.usage.take() {
+                    Some(u) => u,
+                    None => continue,
+                };
+                // Bucket by local date so days line up with stats-cache.json
+                let date = match entry
+                    .timestamp
+                    .as_deref()
+                    .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+                {
+                    Some(t) => t.with_timezone(&Local).date_naive(),
+                    None => continue,
+                };
+                if date < since {
+                    continue;
+                }
+                f(&path, date, entry.session_id, msg, usage);
+            }
+        }
+    }
+
+    /// Per-day token totals from the assistant lines of the session files,
+    /// for local dates on or after `since`. Sessions and messages are counted
+    /// too, for when stats-cache.json has no activity for a day.
+    fn daily_usage_from_session_files(&self, since: NaiveDate) -> HashMap<String, DailyUsage> {
+        let mut daily: HashMap<String, DailyUsage> = HashMap::new();
+        let mut sessions_by_date: HashSet<(String, String)> = HashSet::new();
+
+        self.for_each_usage_line_since(since, |path, date, session_id, msg, usage| {
+            let date = date.format("%Y-%m-%d").to_string();
+
+            let day = daily.entry(date.clone()).or_insert_with(|| DailyUsage {
+                date: date.clone(),
+                ..Default::default()
+            });
+            day.input_tokens += usage.input_tokens;
+            day.output_tokens += usage.output_tokens;
+            day.cache_read_tokens += usage.cache_read_input_tokens;
+            day.cache_write_tokens += usage.cache_creation_input_tokens;
+            day.messages += 1;
+            day.tool_calls += msg.tool_use_count();
+
+            let session_id = session_id.unwrap_or_else(|| path.to_string_lossy().to_string());
+            if sessions_by_date.insert((date, session_id)) {
+                day.sessions += 1;
+            }
+        });
+
+        daily
+    }
+
+    /// Per-day, per-model token counts from the assistant lines of the
+    /// session files, for local dates on or after `since`, newest first.
+    /// stats-cache.json only keeps a per-model total for each day, so the
+    /// split into input, output and cache tokens has to come from here.
+    fn daily_model_usage_from_session_files(&self, since: NaiveDate) -> Vec<DailyModelUsage> {
+        // (date, model) -> usage, with the cost summed unrounded
+        let mut usage: HashMap<(String, String), (DailyModelUsage, f64)> = HashMap::new();
+
+        self.for_each_usage_line_since(since, |_, date, _, msg, line| {
+            let date = date.format("%Y-%m-%d").to_string();
+            let model = msg.model.unwrap_or_else(|| "unknown".to_string());
+            let rates = pricing::table().claude_rates(&model);
+
+            let (entry, cost) = usage.entry((date.clone(), model.clone())).or_insert_with(|| {
+                let entry = DailyModelUsage {
+                    date,
+                    model,
+                    input_tokens: 0,
+                    output_tokens: 0,
+                    cache_read_tokens: 0,
+                    cache_write_tokens: 0,
+                    cost_usd: 0.0,
+                };
+                (entry, 0.0)
+            });
+            entry.input_tokens += line.input_tokens;
+            entry.output_tokens += line.output_tokens;
+            entry.cache_read_tokens += line.cache_read_input_tokens;
+            entry.cache_write_tokens += line.cache_creation_input_tokens;
+            *cost += rates.cost(
+                line.input_tokens,
+                line.output_tokens,
+                line.cache_read_input_tokens,
+                line.cache_creation_input_tokens,
+            );
+        });
+
+        let mut usage: Vec<DailyModelUsage> = usage
+            .into_values()
+            .map(|(mut entry, cost)| {
+                entry.cost_usd = (cost * 100.0).round() / 100.0;
+                entry
+            })
+            .collect();
+        usage.sort_by(|a, b| b.date.cmp(&a.date).then_with(|| a.model.cmp(&b.model)));
+        usage
+    }
+
+    /// Estimate cost in USD for a given model name and token counts,
+    /// using the Claude rates from the pricing table.
+    pub(crate) fn estimate_cost(
         model: &str,
         input_tokens: u64,
         output_tokens: u64,
         cache_read_tokens: u64,
         cache_write_tokens: u64,
     ) -> f64 {
-        let model_lower = model.to_lowercase();
-
-        let (input_rate, output_rate, cache_read_rate, cache_write_rate) =
-            if model_lower.contains("opus") {
-                // $15 input, $75 output per million tokens
-                // Cache read is 90% discount, cache write is 25% premium
-                (15.0, 75.0, 1.50, 18.75)
-            } else if model_lower.contains("haiku") {
-                (0.25, 1.25, 0.025, 0.3125)
-            } else {
-                // Sonnet and default
-                (3.0, 15.0, 0.30, 3.75)
-            };
-
-        let cost = (input_tokens as f64 * input_rate
-            + output_tokens as f64 * output_rate
-            + cache_read_tokens as f64 * cache_read_rate
-            + cache_write_tokens as f64 * cache_write_rate)
-            / 1_000_000.0;
+        let cost = pricing::table().claude_rates(model).cost(
+            input_tokens,
+            output_tokens,
+            cache_read_tokens,
+            cache_write_tokens,
+        );
 
         (cost * 100.0).round() / 100.0
     }
 
-    /// Scan the projects directory for JSONL session files.
+    /// Scan the projects directory of every config directory for JSONL
+    /// session files.
+    /// Symlinked subdirectories are followed by the glob walk.
     fn find_session_files(&self) -> Vec<PathBuf> {
-        let projects_dir = self.config_dir.join("projects");
-        if !projects_dir.exists() {
-            return Vec::new();
+        let mut files = Vec::new();
+        for projects_dir in self.projects_dirs() {
+            if !self.source.exists(&projects_dir) {
+                continue;
+            }
+
+            // Escape the directory so glob metacharacters in it (e.g. `[`) match literally
+            let pattern = PathBuf::from(glob::Pattern::escape(&projects_dir.to_string_lossy()))
+                .join("**")
+                .join("*.jsonl")
+                .to_string_lossy()
+                .to_string();
+
+            files.extend(self.source.glob(&pattern));
         }
+        files.sort();
+        files.dedup();
+        files
+    }
 
-        let pattern = projects_dir
-            .join("**")
-            .join("*.jsonl")
-            .to_string_lossy()
-            .to_string();
+    /// Count sessions and assistant messages with activity at or after `since`.
+    /// Files not modified since then are skipped without being read.
+    fn activity_since(&self, since: DateTime<Utc>) -> (u32, u32) {
+        let since_time = SystemTime::from(since);
+        let mut sessions: u32 = 0;
+        let mut messages: u32 = 0;
+
+        for path in self.find_session_files() {
+            let modified = self.source.modified(&path);
+            if modified.map(|m| m < since_time).unwrap_or(true) {
+                continue;
+            }
+            let entries = match JsonlLines::<SessionLine>::open(self.source.as_ref(), &path) {
+                Ok(e) => e,
+                Err(e) => {
+                    tracing::warn!("Failed to read {}: {}", path.display(), e);
+                    continue;
+                }
+            };
 
-        glob::glob(&pattern)
-            .map(|paths| paths.filter_map(|p| p.ok()).collect())
-            .unwrap_or_default()
+            let in_window = entries
+                .filter(|entry| entry.line_type.as_deref() == Some("assistant"))
+                .filter(|entry| {
+                    entry
+                        .timestamp
+                        .as_deref()
+                        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+                        .is_some_and(|t| t >= since)
+                })
+                .count() as u32;
+
+            if in_window > 0 {
+                sessions += 1;
+                messages += in_window;
+            }
+        }
+
+        (sessions, messages)
     }
 
-    /// Parse a single JSONL session file and return aggregated session info.
-    fn parse_session_file(&self, path: &PathBuf) -> Option<Session> {
-        let data = fs::read_to_string(path).ok()?;
-        if data.trim().is_empty() {
-            return None;
+    /// The session a file belongs to: the first `sessionId` in it, or the
+    /// file name for older files without one. Usually only the first line
+    /// is read.
+    fn session_id_of(&self, path: &Path) -> String {
+        JsonlLines::<SessionLine>::open(self.source.as_ref(), path)
+            .ok()
+            .and_then(|mut entries| entries.find_map(|e| e.session_id))
+            .unwrap_or_else(|| {
+                path.file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "unknown".to_string())
+            })
+    }
+
+    /// Session files grouped by session id, with their modification times.
+    /// Groups are ordered by their newest file, newest first.
+    fn files_by_session(&self, files: Vec<PathBuf>) -> Vec<(String, Vec<(SystemTime, PathBuf)>)> {
+        let mut groups: Vec<(String, Vec<(SystemTime, PathBuf)>)> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+
+        for path in files {
+            let Ok(modified) = self.source.modified(&path) else {
+                continue;
+            };
+            let id = self.session_id_of(&path);
+            match index.get(&id) {
+                Some(&i) => groups[i].1.push((modified, path)),
+                None => {
+                    index.insert(id.clone(), groups.len());
+                    groups.push((id, vec![(modified, path)]));
+                }
+            }
         }
 
+        let newest = |files: &[(SystemTime, PathBuf)]| files.iter().map(|(m, _)| *m).max();
+        groups.sort_by_key(|(_, files)| std::cmp::Reverse(newest(files)));
+        groups
+    }
+
+    /// Whether a file modified at `modified` is within the active threshold.
+    fn recently_modified(&self, modified: SystemTime) -> bool {
+        SystemTime::now()
+            .duration_since(modified)
+            .is_ok_and(|age| age < self.active_threshold)
+    }
+
+    /// Remember how many malformed lines `path` had when last parsed.
+    fn record_skipped_lines(&self, path: &Path, skipped: u32) {
+        let mut skipped_lines = self.skipped_lines.lock().unwrap_or_else(PoisonError::into_inner);
+        if skipped > 0 {
+            tracing::debug!("Skipped {} malformed lines in {}", skipped, path.display());
+            skipped_lines.insert(path.to_path_buf(), skipped);
+        } else {
+            skipped_lines.remove(path);
+        }
+    }
+
+    /// Parse a single JSONL session file and return aggregated session info.
+    fn parse_session_file(&self, path: &Path) -> Option<Session> {
+        let mut entries = JsonlLines::<SessionLine>::open(self.source.as_ref(), path)
+            .map_err(|e| tracing::warn!("Failed to read {}: {}", path.display(), e))
+            .ok()?;
+
         let mut total_tokens: u64 = 0;
+        let mut total_cost: f64 = 0.0;
         let mut message_count: u32 = 0;
         let mut last_model = String::new();
         let mut last_timestamp = String::new();
         let mut session_id = String::new();
 
-        for line in data.lines() {
-            let line = line.trim();
-            if line.is_empty() {
-                continue;
+        for entry in entries.by_ref() {
+            if let Some(ref sid) = entry.session_id {
+                if session_id.is_empty() {
+                    session_id = sid.clone();
+                }
             }
 
-            if let Ok(entry) = serde_json::from_str::<SessionLine>(line) {
-                if let Some(ref sid) = entry.session_id {
-                    if session_id.is_empty() {
-                        session_id = sid.clone();
-                    }
-                }
+            if let Some(ref ts) = entry.timestamp {
+                last_timestamp = ts.clone();
+            }
 
-                if let Some(ref ts) = entry.timestamp {
-                    last_timestamp = ts.clone();
-                }
+            let is_assistant = entry
+                .line_type
+                .as_deref()
+                .map(|t| t == "assistant")
+                .unwrap_or(false);
 
-                let is_assistant = entry
-                    .line_type
-                    .as_deref()
-                    .map(|t| t == "assistant")
-                    .unwrap_or(false);
-
-                if is_assistant {
-                    if let Some(ref msg) = entry.message {
-                        if let Some(ref model) = msg.model {
-                            last_model = model.clone();
-                        }
-                        if let Some(ref usage) = msg.usage {
-                            total_tokens += usage.input_tokens
-                                + usage.output_tokens
-                                + usage.cache_read_input_tokens
-                                + usage.cache_creation_input_tokens;
-                            message_count += 1;
-                        }
+            if is_assistant {
+                if let Some(ref msg) = entry.message {
+                    if let Some(ref model) = msg.model {
+                        last_model = model.clone();
+                    }
+                    if let Some(ref usage) = msg.usage {
+                        total_tokens += usage.input_tokens
+                            + usage.output_tokens
+                            + usage.cache_read_input_tokens
+                            + usage.cache_creation_input_tokens;
+                        // Priced per message, since the model can change mid-session
+                        total_cost += pricing::table().claude_rates(&last_model).cost(
+                            usage.input_tokens,
+                            usage.output_tokens,
+                            usage.cache_read_input_tokens,
+                            usage.cache_creation_input_tokens,
+                        );
+                        message_count += 1;
                     }
                 }
             }
         }
+        self.record_skipped_lines(path, entries.skipped());
 
         if message_count == 0 {
             return None;
@@ -299,11 +853,21 @@ impl ClaudeProvider {
 
         // Derive project name from the file path.
         // Session files live under projects/<encoded-path>/<uuid>.jsonl
-        let project = path
+        let project_dir_encoded = path
             .parent()
             .and_then(|p| p.file_name())
             .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_else(|| "unknown".to_string());
+            .unwrap_or_default();
+        let project_path = if project_dir_encoded.is_empty() {
+            "unknown".to_string()
+        } else {
+            decode_project_dir(&project_dir_encoded)
+        };
+        let project = project_path
+            .rsplit(['/', '\\'])
+            .find(|s| !s.is_empty())
+            .unwrap_or(&project_path)
+            .to_string();
 
         if session_id.is_empty() {
             session_id = path
@@ -312,20 +876,30 @@ impl ClaudeProvider {
                 .unwrap_or_else(|| "unknown".to_string());
         }
 
-        // Check if session is active (file modified in last 30 minutes)
-        let is_active = fs::metadata(path)
-            .and_then(|m| m.modified())
-            .map(|modified| {
-                SystemTime::now()
-                    .duration_since(modified)
-                    .unwrap_or(Duration::from_secs(u64::MAX))
-                    < Duration::from_secs(30 * 60)
-            })
-            .unwrap_or(false);
+        // Check if session is active (last message within the active threshold).
+        // Sync clients and backup tools touch mtimes without adding messages,
+        // so the file mtime is only a fallback when no timestamp was parsed.
+        // Times in the future count as inactive either way, like
+        // `recently_modified`.
+        let threshold = self.active_threshold;
+        let is_active = match chrono::DateTime::parse_from_rfc3339(&last_timestamp) {
+            Ok(ts) => chrono::Utc::now()
+                .signed_duration_since(ts)
+                .to_std()
+                .is_ok_and(|age| age < threshold),
+            Err(_) => self
+                .source
+                .modified(path)
+                .is_ok_and(|modified| self.recently_modified(modified)),
+        };
 
         Some(Session {
             id: session_id,
+            provider: "Claude".to_string(),
+            profile_id: String::new(),
             project,
+            project_path,
+            project_dir_encoded,
             model: if last_model.is_empty() {
                 "unknown".to_string()
             } else {
@@ -335,10 +909,41 @@ impl ClaudeProvider {
             last_active: last_timestamp,
             is_active,
             message_count,
+            cost_usd: (total_cost * 100.0).round() / 100.0,
         })
     }
 }
 
+/// Combine sessions sharing an id. Claude can split one session across
+/// several files after compaction or resume; counts are summed, the latest
+/// activity (and its model) wins and the session is active if any part is.
+fn merge_sessions(sessions: Vec<Session>) -> Vec<Session> {
+    let mut merged: Vec<Session> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+
+    for session in sessions {
+        match index.get(&session.id) {
+            Some(&i) => {
+                let existing = &mut merged[i];
+                existing.tokens_used += session.tokens_used;
+                existing.message_count += session.message_count;
+                existing.cost_usd = ((existing.cost_usd + session.cost_usd) * 100.0).round() / 100.0;
+                existing.is_active |= session.is_active;
+                if session.last_active > existing.last_active {
+                    existing.last_active = session.last_active;
+                    existing.model = session.model;
+                }
+            }
+            None => {
+                index.insert(session.id.clone(), merged.len());
+                merged.push(session);
+            }
+        }
+    }
+
+    merged
+}
+
 impl Provider for ClaudeProvider {
     fn name(&self) -> &str {
         "Claude"
@@ -352,14 +957,82 @@ impl Provider for ClaudeProvider {
         &self.config_dir
     }
 
-    fn get_usage_stats(&self) -> Result<UsageStats, String> {
-        let cache = self.read_stats_cache().unwrap_or(StatsCache {
-            model_usage: HashMap::new(),
-            total_sessions: 0,
-            total_messages: 0,
-            daily_activity: Vec::new(),
-            daily_model_tokens: Vec::new(),
-        });
+    fn watch_dirs(&self) -> Vec<PathBuf> {
+        self.projects_dirs()
+    }
+
+    fn probe(&self) -> Result<String, ProviderError> {
+        let dir = self.effective_dir();
+        if !dir.is_dir() {
+            return Err(ProviderError::NotConfigured(format!(
+                "Config directory not found: {}",
+                dir.display()
+            )));
+        }
+        if dir.join("stats-cache.json").is_file() {
+            Ok("Found stats-cache.json".to_string())
+        } else if self.projects_dir().is_dir() {
+            Ok(format!("Found {} directory", self.projects_subdir))
+        } else {
+            Err(ProviderError::NotConfigured(format!(
+                "No stats-cache.json or {} directory in {}",
+                self.projects_subdir,
+                dir.display()
+            )))
+        }
+    }
+
+    fn health_check(&self) -> Result<(), ProviderError> {
+        self.probe()?;
+        if self.read_stats_cache().is_some()
+            || self.find_session_files().iter().any(|f| self.parse_session_file(f).is_some())
+        {
+            return Ok(());
+        }
+        Err(ProviderError::Parse(format!(
+            "No readable stats-cache.json or session file in {}",
+            self.effective_dir().display()
+        )))
+    }
+
+    fn diagnostics(&self) -> Diagnostics {
+        let dir = self.effective_dir();
+        let session_files = self.find_session_files();
+        // Only files still on disk, in case some were deleted since parsing
+        let skipped_lines = {
+            let skipped = self.skipped_lines.lock().unwrap_or_else(PoisonError::into_inner);
+            session_files.iter().filter_map(|f| skipped.get(f)).sum()
+        };
+        Diagnostics {
+            config_dir: dir.display().to_string(),
+            config_dir_exists: dir.is_dir(),
+            session_files: Some(session_files.len() as u32),
+            files: vec![
+                FileStatus::of(&dir.join("stats-cache.json")),
+                FileStatus::of(&self.projects_dir()),
+            ],
+            last_fetch: None,
+            skipped_lines: Some(skipped_lines),
+        }
+    }
+
+    fn billing_mode(&self) -> BillingMode {
+        let plan = self.read_credentials().and_then(|c| c.subscription_type);
+        if plan.is_some_and(|p| !p.is_empty()) {
+            BillingMode::Subscription
+        } else {
+            BillingMode::PerToken
+        }
+    }
+
+    fn get_usage_stats(&self) -> Result<UsageStats, ProviderError> {
+        // Newer Claude Code versions sometimes delete or stop rewriting the
+        // cache; the session files have everything needed to rebuild it
+        let session_files = self.find_session_files();
+        let cache = self
+            .read_stats_cache()
+            .filter(|_| !self.stats_cache_outdated(&session_files))
+            .unwrap_or_else(|| self.stats_from_session_files(&session_files));
 
         let mut total_input: u64 = 0;
         let mut total_output: u64 = 0;
@@ -392,11 +1065,28 @@ impl Provider for ClaudeProvider {
                     cache_read_tokens: usage.cache_read_input_tokens,
                     cache_write_tokens: usage.cache_creation_input_tokens,
                     cost_usd: cost,
+                    ..Default::default()
                 },
             );
         }
 
-        Ok(UsageStats {
+        let total_tool_calls = cache.total_tool_calls.unwrap_or_else(|| {
+            cache
+                .daily_activity
+                .iter()
+                .filter_map(|d| d.tool_call_count)
+                .sum()
+        });
+
+        let api_cost = (total_cost * 100.0).round() / 100.0;
+        let billing_mode = self.billing_mode();
+        let (estimated_cost_usd, equivalent_api_cost_usd) = match billing_mode {
+            // Flat-rate plans pay nothing per token; report the API price separately
+            BillingMode::Subscription => (0.0, api_cost),
+            BillingMode::PerToken => (api_cost, 0.0),
+        };
+
+        let mut stats = UsageStats {
             provider: "Claude".to_string(),
             total_input_tokens: total_input,
             total_output_tokens: total_output,
@@ -404,64 +1094,56 @@ impl Provider for ClaudeProvider {
             total_cache_write_tokens: total_cache_write,
             total_sessions: cache.total_sessions,
             total_messages: cache.total_messages,
-            estimated_cost_usd: (total_cost * 100.0).round() / 100.0,
+            estimated_cost_usd,
             model_breakdown,
-        })
+            billing_mode,
+            equivalent_api_cost_usd,
+            total_tool_calls,
+            ..Default::default()
+        };
+        stats.compute_cache_hit_ratios();
+        Ok(stats)
     }
 
-    fn get_active_sessions(&self) -> Result<Vec<Session>, String> {
-        let files = self.find_session_files();
-        let now = SystemTime::now();
-        let threshold = Duration::from_secs(30 * 60);
-
-        let mut active_sessions = Vec::new();
-
-        for file in files {
-            // Quick check: only parse files modified recently
-            let is_recent = fs::metadata(&file)
-                .and_then(|m| m.modified())
-                .map(|modified| {
-                    now.duration_since(modified)
-                        .unwrap_or(Duration::from_secs(u64::MAX))
-                        < threshold
-                })
-                .unwrap_or(false);
-
-            if !is_recent {
-                continue;
-            }
-
-            if let Some(session) = self.parse_session_file(&file) {
-                active_sessions.push(session);
-            }
-        }
+    fn get_active_sessions(&self) -> Result<Vec<Session>, ProviderError> {
+        // Quick check: only sessions with a recently modified file are active,
+        // but every part of those is parsed so a resumed session is complete
+        let groups = self.files_by_session(self.find_session_files());
+        let sessions: Vec<Session> = groups
+            .iter()
+            .filter(|(_, files)| files.iter().any(|(modified, _)| self.recently_modified(*modified)))
+            .flat_map(|(_, files)| files.iter().filter_map(|(_, path)| self.parse_session_file(path)))
+            .collect();
 
-        Ok(active_sessions)
+        Ok(merge_sessions(sessions))
     }
 
-    fn get_daily_usage(&self, days: u32) -> Result<Vec<DailyUsage>, String> {
-        let cache = match self.read_stats_cache() {
-            Some(c) => c,
-            None => return Ok(Vec::new()),
-        };
+    fn active_session_count(&self) -> Result<u32, ProviderError> {
+        // Distinct sessions, as in get_active_sessions, without parsing the
+        // files in full; a resumed session spans several files
+        let ids: HashSet<String> = self
+            .find_session_files()
+            .iter()
+            .filter(|f| self.source.modified(f).is_ok_and(|m| self.recently_modified(m)))
+            .map(|f| self.session_id_of(f))
+            .collect();
+        Ok(ids.len() as u32)
+    }
 
-        // Build a map of date -> token totals from daily_model_tokens
-        let mut token_map: HashMap<String, (u64, u64)> = HashMap::new();
-        for entry in &cache.daily_model_tokens {
-            let total: u64 = entry.tokens_by_model.values().sum();
-            // We only have aggregate tokens per model per day, so approximate
-            // a 30/70 input/output split
-            let input_est = total * 30 / 100;
-            let output_est = total - input_est;
-            let e = token_map.entry(entry.date.clone()).or_insert((0, 0));
-            e.0 += input_est;
-            e.1 += output_est;
-        }
+    fn get_daily_usage(&self, days: u32) -> Result<Vec<DailyUsage>, ProviderError> {
+        let since = Local::now().date_naive() - chrono::Duration::days(days.saturating_sub(1) as i64);
+        let token_map = self.daily_usage_from_session_files(since);
 
         // Build a map of date -> activity
-        let mut activity_map: HashMap<String, (u32, u32)> = HashMap::new();
-        for entry in &cache.daily_activity {
-            activity_map.insert(entry.date.clone(), (entry.session_count, entry.message_count));
+        let since_str = since.format("%Y-%m-%d").to_string();
+        let mut activity_map: HashMap<String, (u32, u32, Option<u32>)> = HashMap::new();
+        if let Some(cache) = self.read_stats_cache() {
+            for entry in cache.daily_activity.iter().filter(|e| e.date >= since_str) {
+                activity_map.insert(
+                    entry.date.clone(),
+                    (entry.session_count, entry.message_count, entry.tool_call_count),
+                );
+            }
         }
 
         // Merge into DailyUsage, limited to the last N days
@@ -479,42 +1161,216 @@ impl Provider for ClaudeProvider {
         let daily: Vec<DailyUsage> = all_dates
             .into_iter()
             .map(|date| {
-                let (input, output) = token_map.get(&date).copied().unwrap_or((0, 0));
-                let (sessions, messages) = activity_map.get(&date).copied().unwrap_or((0, 0));
-                DailyUsage {
-                    date,
-                    input_tokens: input,
-                    output_tokens: output,
-                    sessions,
-                    messages,
+                let mut day = token_map.get(&date).cloned().unwrap_or_else(|| DailyUsage {
+                    date: date.clone(),
+                    ..Default::default()
+                });
+                // Prefer Claude's own activity counts where it has them
+                if let Some(&(sessions, messages, tool_calls)) = activity_map.get(&date) {
+                    day.sessions = sessions;
+                    day.messages = messages;
+                    if let Some(tool_calls) = tool_calls {
+                        day.tool_calls = tool_calls;
+                    }
                 }
+                day
             })
             .collect();
 
         Ok(daily)
     }
 
-    fn get_session_history(&self, limit: u32) -> Result<Vec<Session>, String> {
-        let files = self.find_session_files();
+    fn get_rate_limit_status(&self) -> Result<RateLimitStatus, ProviderError> {
+        Ok(self.fetch_rate_limit_status())
+    }
 
-        // Collect (modified_time, path) so we can sort by recency
-        let mut timed_files: Vec<(SystemTime, PathBuf)> = files
-            .into_iter()
-            .filter_map(|p| {
-                let modified = fs::metadata(&p).ok()?.modified().ok()?;
-                Some((modified, p))
-            })
-            .collect();
+    fn get_daily_model_usage(&self, days: u32) -> Result<Vec<DailyModelUsage>, ProviderError> {
+        let since = Local::now().date_naive() - chrono::Duration::days(days.saturating_sub(1) as i64);
+        Ok(self.daily_model_usage_from_session_files(since))
+    }
 
-        // Sort by modified time, newest first
-        timed_files.sort_by(|a, b| b.0.cmp(&a.0));
-        timed_files.truncate(limit as usize);
+    fn get_session_history(&self, limit: u32) -> Result<Vec<Session>, ProviderError> {
+        // Groups come newest first; every file of a session is merged before
+        // it counts towards the limit, so a split session is never cut short
+        let mut sessions = Vec::new();
+        for (_, files) in self.files_by_session(self.find_session_files()) {
+            if sessions.len() >= limit as usize {
+                break;
+            }
+            let parts: Vec<Session> = files.iter().filter_map(|(_, path)| self.parse_session_file(path)).collect();
+            sessions.extend(merge_sessions(parts));
+        }
+        Ok(sessions)
+    }
 
-        let sessions: Vec<Session> = timed_files
-            .iter()
-            .filter_map(|(_, path)| self.parse_session_file(path))
-            .collect();
+    fn get_known_models(&self) -> Result<Vec<String>, ProviderError> {
+        let mut models: Vec<String> = self
+            .read_stats_cache()
+            .map(|c| c.model_usage.into_keys().collect())
+            .unwrap_or_default();
+        models.sort();
+        Ok(models)
+    }
+}
 
-        Ok(sessions)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::source::MemoryFs;
+
+    const CONFIG_DIR: &str = "/home/me/.claude";
+
+    fn provider(fs: MemoryFs) -> ClaudeProvider {
+        provider_in(CONFIG_DIR, fs)
+    }
+
+    fn provider_in(config_dir: &str, fs: MemoryFs) -> ClaudeProvider {
+        ClaudeProvider::with_source(
+            PathBuf::from(config_dir),
+            Vec::new(),
+            "projects".to_string(),
+            Duration::from_secs(300),
+            Arc::new(fs),
+        )
+    }
+
+    fn assistant_line(session_id: &str, model: &str, input: u64, output: u64, cache_read: u64) -> String {
+        serde_json::json!({
+            "type": "assistant",
+            "sessionId": session_id,
+            "timestamp": Utc::now().to_rfc3339(),
+            "message": {
+                "model": model,
+                "usage": {
+                    "input_tokens": input,
+                    "output_tokens": output,
+                    "cache_read_input_tokens": cache_read,
+                },
+            },
+        })
+        .to_string()
+    }
+
+    /// Two sessions in two projects, with a user line and a malformed line
+    /// that must not be counted.
+    fn session_files(fs: &MemoryFs, modified: SystemTime) {
+        let first = [
+            assistant_line("s1", "claude-opus-4", 100, 10, 1000),
+            r#"{"type":"user","sessionId":"s1"}"#.to_string(),
+            "{not json".to_string(),
+            assistant_line("s1", "claude-opus-4", 100, 10, 0),
+        ];
+        fs.insert_modified(
+            format!("{}/projects/-home-me-app/s1.jsonl", CONFIG_DIR),
+            first.join("\n") + "\n",
+            modified,
+        );
+        fs.insert_modified(
+            format!("{}/projects/-home-me-web/s2.jsonl", CONFIG_DIR),
+            assistant_line("s2", "claude-sonnet-4", 200, 20, 0) + "\n",
+            modified,
+        );
+    }
+
+    #[test]
+    fn usage_stats_are_rebuilt_from_session_files_without_a_cache() {
+        let fs = MemoryFs::default();
+        session_files(&fs, SystemTime::now());
+
+        let stats = provider(fs).get_usage_stats().unwrap();
+        assert_eq!(stats.total_input_tokens, 400);
+        assert_eq!(stats.total_output_tokens, 40);
+        assert_eq!(stats.total_cache_read_tokens, 1000);
+        assert_eq!(stats.total_sessions, 2);
+        assert_eq!(stats.total_messages, 3);
+        assert_eq!(stats.model_breakdown["claude-opus-4"].input_tokens, 200);
+        assert_eq!(stats.model_breakdown["claude-sonnet-4"].output_tokens, 20);
+    }
+
+    #[test]
+    fn usage_stats_prefer_an_up_to_date_cache() {
+        let fs = MemoryFs::default();
+        session_files(&fs, SystemTime::now() - Duration::from_secs(3600));
+        fs.insert(
+            format!("{}/stats-cache.json", CONFIG_DIR),
+            r#"{"modelUsage":{"claude-opus-4":{"inputTokens":5,"outputTokens":7}},"totalSessions":9,"totalMessages":11}"#,
+        );
+
+        let stats = provider(fs).get_usage_stats().unwrap();
+        assert_eq!(stats.total_input_tokens, 5);
+        assert_eq!(stats.total_output_tokens, 7);
+        assert_eq!(stats.total_sessions, 9);
+        assert_eq!(stats.total_messages, 11);
+    }
+
+    #[test]
+    fn daily_usage_sums_todays_session_lines() {
+        let fs = MemoryFs::default();
+        session_files(&fs, SystemTime::now());
+
+        let daily = provider(fs).get_daily_usage(1).unwrap();
+        assert_eq!(daily.len(), 1);
+        assert_eq!(daily[0].date, Local::now().format("%Y-%m-%d").to_string());
+        assert_eq!(daily[0].input_tokens, 400);
+        assert_eq!(daily[0].cache_read_tokens, 1000);
+        assert_eq!(daily[0].sessions, 2);
+        assert_eq!(daily[0].messages, 3);
+    }
+
+    #[test]
+    fn session_files_are_found_under_a_config_dir_with_glob_metacharacters() {
+        let config_dir = "/home/me/[work]/.claude";
+        let fs = MemoryFs::default();
+        fs.insert(
+            format!("{}/projects/-home-me-app/s1.jsonl", config_dir),
+            assistant_line("s1", "claude-opus-4", 100, 10, 0) + "\n",
+        );
+
+        let provider = provider_in(config_dir, fs);
+        assert_eq!(provider.find_session_files().len(), 1);
+        assert_eq!(provider.get_usage_stats().unwrap().total_input_tokens, 100);
+    }
+
+    #[test]
+    fn daily_model_usage_splits_tokens_per_model() {
+        let fs = MemoryFs::default();
+        session_files(&fs, SystemTime::now());
+
+        let usage = provider(fs).get_daily_model_usage(1).unwrap();
+        assert_eq!(usage.len(), 2);
+        let opus = usage.iter().find(|u| u.model == "claude-opus-4").unwrap();
+        assert_eq!(opus.date, Local::now().format("%Y-%m-%d").to_string());
+        assert_eq!(opus.input_tokens, 200);
+        assert_eq!(opus.output_tokens, 20);
+        assert_eq!(opus.cache_read_tokens, 1000);
+        let sonnet = usage.iter().find(|u| u.model == "claude-sonnet-4").unwrap();
+        assert_eq!(sonnet.input_tokens, 200);
+        assert_eq!(sonnet.cache_read_tokens, 0);
+    }
+
+    #[test]
+    fn touched_file_with_an_old_last_message_is_not_active() {
+        let fs = MemoryFs::default();
+        let line = serde_json::json!({
+            "type": "assistant",
+            "sessionId": "s1",
+            "timestamp": (Utc::now() - chrono::Duration::hours(3)).to_rfc3339(),
+            "message": {
+                "model": "claude-opus-4",
+                "usage": { "input_tokens": 100, "output_tokens": 10 },
+            },
+        });
+        // A sync client touched the file just now
+        fs.insert_modified(
+            format!("{}/projects/-home-me-app/s1.jsonl", CONFIG_DIR),
+            line.to_string() + "\n",
+            SystemTime::now(),
+        );
+
+        let provider = provider(fs);
+        let sessions = provider.get_session_history(10).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert!(!sessions[0].is_active);
+        assert!(provider.get_active_sessions().unwrap().iter().all(|s| !s.is_active));
     }
 }
@@ -1,8 +1,12 @@
-use super::{DailyUsage, ModelUsage, Provider, Session, UsageStats};
-use serde::Deserialize;
+use super::{
+    http_client, send_with_retry, DailyUsage, Diagnostics, FetchStatus, ModelUsage, Provider, ProviderError,
+    RateLimitStatus, RateLimitWindow, Session, UsageStats, WorkspaceUsage,
+};
+use crate::pricing;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::Mutex;
+use std::sync::{Mutex, PoisonError};
 use std::time::{Duration, Instant};
 
 /// Cache entry with TTL
@@ -18,10 +22,29 @@ pub struct ClaudeApiProvider {
     client: reqwest::blocking::Client,
     usage_cache: Mutex<Option<CacheEntry<UsageStats>>>,
     daily_cache: Mutex<Option<CacheEntry<Vec<DailyUsage>>>>,
+    /// Parsed from the `anthropic-ratelimit-*` headers of the last usage report response.
+    rate_limit: Mutex<Option<RateLimitStatus>>,
+    /// How long cached reports are reused. Zero disables caching.
+    cache_ttl: Duration,
+    /// Outcome of the last usage report request, for diagnostics.
+    last_fetch: Mutex<Option<FetchStatus>>,
 }
 
-const CACHE_TTL: Duration = Duration::from_secs(60);
 const API_BASE: &str = "https://api.anthropic.com";
+/// Number of days summed into the overall usage stats.
+const STATS_DAYS: u32 = 30;
+
+/// Report window covering the last `days` UTC days, today included, so a
+/// `1d` bucket width yields exactly `days` buckets. `ending_at` is clamped
+/// to now rather than end of day so the window never reaches into the future.
+fn report_window(days: u32) -> (String, String) {
+    let now = chrono::Utc::now();
+    let start = now.date_naive() - chrono::Duration::days(days.max(1) as i64 - 1);
+    (
+        start.format("%Y-%m-%dT00:00:00Z").to_string(),
+        now.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+    )
+}
 
 // --- API response types ---
 
@@ -34,15 +57,14 @@ struct UsageReport {
     next_page: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 struct UsageBucket {
     starting_at: String,
-    #[allow(dead_code)]
     ending_at: String,
     results: Vec<UsageResult>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 struct UsageResult {
     #[serde(default)]
     model: Option<String>,
@@ -54,9 +76,29 @@ struct UsageResult {
     cache_read_input_tokens: u64,
     #[serde(default)]
     cache_creation: Option<CacheCreation>,
+    /// Number of API requests in this row, when the report includes it.
+    #[serde(default)]
+    request_count: Option<u32>,
+    #[serde(default)]
+    service_tier: Option<String>,
+    /// Set when grouped by workspace; `None` is the default workspace.
+    #[serde(default)]
+    workspace_id: Option<String>,
+    #[serde(default)]
+    api_key_id: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+impl UsageResult {
+    /// Requests represented by this row. Falls back to counting a row with
+    /// tokens as one request when the report has no request count.
+    fn requests(&self) -> u32 {
+        self.request_count.unwrap_or(
+            (self.output_tokens > 0 || self.uncached_input_tokens > 0) as u32,
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 struct CacheCreation {
     #[serde(default)]
     ephemeral_5m_input_tokens: u64,
@@ -64,6 +106,22 @@ struct CacheCreation {
     ephemeral_1h_input_tokens: u64,
 }
 
+#[derive(Debug, Deserialize)]
+struct WorkspaceList {
+    data: Vec<Workspace>,
+    #[serde(default)]
+    has_more: bool,
+    #[serde(default)]
+    last_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Workspace {
+    id: String,
+    #[serde(default)]
+    name: String,
+}
+
 #[derive(Debug, Deserialize)]
 struct CostReport {
     data: Vec<CostBucket>,
@@ -89,27 +147,158 @@ struct CostResult {
     currency: Option<String>,
 }
 
+/// One rate-limit window from the `anthropic-ratelimit-{kind}-*` headers,
+/// where `kind` is e.g. `tokens` or `requests`.
+fn rate_limit_window(headers: &reqwest::header::HeaderMap, kind: &str, label: &str) -> Option<RateLimitWindow> {
+    let header = |name: &str| {
+        headers
+            .get(format!("anthropic-ratelimit-{}-{}", kind, name))
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+
+    let limit: f64 = header("limit")?.parse().ok()?;
+    let remaining: f64 = header("remaining")?.parse().ok()?;
+    if limit <= 0.0 {
+        return None;
+    }
+
+    Some(RateLimitWindow {
+        label: label.to_string(),
+        utilization: ((limit - remaining) / limit * 100.0).clamp(0.0, 100.0),
+        resets_at: header("reset"),
+    })
+}
+
+/// Rate-limit status from Admin API response headers, if any were sent.
+fn rate_limit_from_headers(headers: &reqwest::header::HeaderMap) -> Option<RateLimitStatus> {
+    let tokens = rate_limit_window(headers, "tokens", "Token Limit");
+    let requests = rate_limit_window(headers, "requests", "Request Limit");
+    if tokens.is_none() && requests.is_none() {
+        return None;
+    }
+
+    Some(RateLimitStatus {
+        available: true,
+        five_hour: tokens,
+        seven_day: requests,
+        ..Default::default()
+    })
+}
+
+/// Report dimensions for per-model usage and cost.
+const GROUP_BY_MODEL: &[&str] = &["model", "service_tier"];
+
+/// Input, output, cache read, 5-minute and 1-hour cache write tokens.
+type TokenCounts = (u64, u64, u64, u64, u64);
+
+/// `service_tier` of rows sent through the Message Batches API.
+const BATCH_SERVICE_TIER: &str = "batch";
+
+/// Share of the regular price charged for batch tokens.
+const BATCH_PRICE_FACTOR: f64 = 0.5;
+
+/// Unrounded cost estimate in USD for one model, pricing 5-minute and
+/// 1-hour cache writes at their separate rates and batch usage at the batch
+/// discount. Callers round once they've summed.
+fn estimate_cost(
+    model: &str,
+    batch: bool,
+    input_tokens: u64,
+    output_tokens: u64,
+    cache_read_tokens: u64,
+    cache_write_5m_tokens: u64,
+    cache_write_1h_tokens: u64,
+) -> f64 {
+    let rates = pricing::table().claude_rates(model);
+    let mut cost = rates.cost(input_tokens, output_tokens, cache_read_tokens, cache_write_5m_tokens)
+        + rates.cache_write_1h_cost(cache_write_1h_tokens);
+    if batch {
+        cost *= BATCH_PRICE_FACTOR;
+    }
+    cost
+}
+
+/// Upper bound on report pages fetched in one call.
+const MAX_PAGES: u32 = 100;
+
+/// Page token for the request after page number `pages`, or `None` when
+/// pagination is done. Stops on a missing token and fails on a repeated
+/// token or after `MAX_PAGES`, so a misbehaving API can't loop forever.
+fn next_page(
+    current: Option<String>,
+    has_more: bool,
+    next: Option<String>,
+    pages: u32,
+) -> Result<Option<String>, ProviderError> {
+    if !has_more {
+        return Ok(None);
+    }
+    let next = match next {
+        Some(n) => n,
+        None => {
+            tracing::warn!("Report has more pages but no next_page token; stopping");
+            return Ok(None);
+        }
+    };
+    if current.as_deref() == Some(next.as_str()) {
+        return Err(ProviderError::Other(format!(
+            "Report pagination returned the same page token twice: {}",
+            next
+        )));
+    }
+    if pages >= MAX_PAGES {
+        return Err(ProviderError::Other(format!(
+            "Report pagination exceeded {} pages",
+            MAX_PAGES
+        )));
+    }
+    Ok(Some(next))
+}
+
+/// Warn when the API returned a different number of daily buckets than the
+/// window spans; a missing current-day bucket shows up here.
+fn warn_on_bucket_mismatch(buckets: &[UsageBucket], days: u32) {
+    if buckets.len() != days.max(1) as usize {
+        tracing::warn!(
+            "Claude usage report returned {} daily buckets, expected {}",
+            buckets.len(),
+            days.max(1)
+        );
+    }
+}
+
 impl ClaudeApiProvider {
-    pub fn new(api_key: String) -> Self {
-        let client = reqwest::blocking::Client::builder()
-            .timeout(Duration::from_secs(30))
-            .build()
-            .unwrap_or_default();
+    pub fn new(api_key: String, proxy_url: Option<&str>, cache_ttl: Duration) -> Result<Self, String> {
+        let client = http_client(Duration::from_secs(30), proxy_url)?;
 
-        Self {
+        Ok(Self {
             api_key,
             client,
             usage_cache: Mutex::new(None),
             daily_cache: Mutex::new(None),
-        }
+            rate_limit: Mutex::new(None),
+            cache_ttl,
+            last_fetch: Mutex::new(None),
+        })
+    }
+
+    /// Fetch usage report from Anthropic Admin API, recording the outcome.
+    /// `group_by` lists the report dimensions, e.g. `model` or `workspace_id`.
+    fn fetch_usage_report(&self, starting_at: &str, ending_at: &str, group_by: &[&str]) -> Result<Vec<UsageBucket>, ProviderError> {
+        let result = self.fetch_usage_pages(starting_at, ending_at, group_by);
+        *self.last_fetch.lock().unwrap_or_else(PoisonError::into_inner) = Some(FetchStatus::of(&result));
+        result
     }
 
-    /// Fetch usage report from Anthropic Admin API with pagination support.
-    fn fetch_usage_report(&self, starting_at: &str, ending_at: &str, group_by_model: bool) -> Result<Vec<UsageBucket>, String> {
+    /// Fetch every page of the usage report.
+    fn fetch_usage_pages(&self, starting_at: &str, ending_at: &str, group_by: &[&str]) -> Result<Vec<UsageBucket>, ProviderError> {
         let mut all_buckets = Vec::new();
         let mut page: Option<String> = None;
+        let mut pages: u32 = 0;
 
         loop {
+            pages += 1;
             let mut req = self.client
                 .get(format!("{}/v1/organizations/usage_report/messages", API_BASE))
                 .header("x-api-key", &self.api_key)
@@ -121,33 +310,37 @@ impl ClaudeApiProvider {
                     ("limit", "31"),
                 ]);
 
-            if group_by_model {
-                req = req.query(&[("group_by[]", "model")]);
+            for dimension in group_by {
+                req = req.query(&[("group_by[]", dimension)]);
             }
 
             if let Some(ref p) = page {
                 req = req.query(&[("page", p.as_str())]);
             }
 
-            let resp = req
-                .send()
-                .map_err(|e| format!("API request failed: {}", e))?;
+            let resp = send_with_retry(req)
+                .map_err(|e| ProviderError::Network(format!("API request failed: {}", e)))?;
 
             if !resp.status().is_success() {
                 let status = resp.status();
                 let body = resp.text().unwrap_or_default();
-                return Err(format!("API error {}: {}", status, body));
+                return Err(ProviderError::from_status(status, format!("API error {}: {}", status, body)));
+            }
+
+            if let Some(status) = rate_limit_from_headers(resp.headers()) {
+                if let Ok(mut rate_limit) = self.rate_limit.lock() {
+                    *rate_limit = Some(status);
+                }
             }
 
             let report: UsageReport = resp
                 .json()
-                .map_err(|e| format!("Failed to parse usage report: {}", e))?;
+                .map_err(|e| ProviderError::Parse(format!("Failed to parse usage report: {}", e)))?;
 
             all_buckets.extend(report.data);
 
-            if report.has_more {
-                page = report.next_page;
-            } else {
+            page = next_page(page, report.has_more, report.next_page, pages)?;
+            if page.is_none() {
                 break;
             }
         }
@@ -156,11 +349,15 @@ impl ClaudeApiProvider {
     }
 
     /// Fetch cost report from Anthropic Admin API with pagination support.
-    fn fetch_cost_report(&self, starting_at: &str, ending_at: &str) -> Result<f64, String> {
-        let mut total_cents: f64 = 0.0;
+    /// Returns the total and the currency it is reported in. A report mixing
+    /// currencies can't be summed and is an error.
+    fn fetch_cost_report(&self, starting_at: &str, ending_at: &str) -> Result<(f64, String), ProviderError> {
+        let mut totals: HashMap<String, f64> = HashMap::new();
         let mut page: Option<String> = None;
+        let mut pages: u32 = 0;
 
         loop {
+            pages += 1;
             let mut req = self.client
                 .get(format!("{}/v1/organizations/cost_report", API_BASE))
                 .header("x-api-key", &self.api_key)
@@ -176,112 +373,166 @@ impl ClaudeApiProvider {
                 req = req.query(&[("page", p.as_str())]);
             }
 
-            let resp = req
-                .send()
-                .map_err(|e| format!("Cost API request failed: {}", e))?;
+            let resp = send_with_retry(req)
+                .map_err(|e| ProviderError::Network(format!("Cost API request failed: {}", e)))?;
 
             if !resp.status().is_success() {
                 let status = resp.status();
                 let body = resp.text().unwrap_or_default();
-                return Err(format!("Cost API error {}: {}", status, body));
+                return Err(ProviderError::from_status(status, format!("Cost API error {}: {}", status, body)));
             }
 
             let report: CostReport = resp
                 .json()
-                .map_err(|e| format!("Failed to parse cost report: {}", e))?;
+                .map_err(|e| ProviderError::Parse(format!("Failed to parse cost report: {}", e)))?;
 
             for bucket in &report.data {
                 for result in &bucket.results {
                     if let Ok(amount) = result.amount.parse::<f64>() {
-                        // amount is in cents for USD
-                        let _currency = result.currency.as_deref().unwrap_or("USD");
-                        total_cents += amount;
+                        let currency = result.currency.as_deref().unwrap_or("USD").to_uppercase();
+                        *totals.entry(currency).or_insert(0.0) += amount;
                     }
                 }
             }
 
-            if report.has_more {
-                page = report.next_page;
-            } else {
+            page = next_page(page, report.has_more, report.next_page, pages)?;
+            if page.is_none() {
                 break;
             }
         }
 
-        // Convert cents to dollars
-        Ok((total_cents / 100.0 * 100.0).round() / 100.0)
+        if totals.len() > 1 {
+            let mut currencies: Vec<&str> = totals.keys().map(String::as_str).collect();
+            currencies.sort();
+            return Err(ProviderError::Parse(format!(
+                "Cost report mixes currencies ({})",
+                currencies.join(", ")
+            )));
+        }
+
+        let (currency, amount) = totals.into_iter().next().unwrap_or_else(|| ("USD".to_string(), 0.0));
+        // USD amounts are in cents; other currencies are taken as reported
+        let total = if currency == "USD" { amount / 100.0 } else { amount };
+        Ok(((total * 100.0).round() / 100.0, currency))
     }
 
     /// Build UsageStats from API data, using cache if available.
-    fn build_usage_stats(&self) -> Result<UsageStats, String> {
+    fn build_usage_stats(&self) -> Result<UsageStats, ProviderError> {
         // Check cache
         if let Ok(cache) = self.usage_cache.lock() {
             if let Some(ref entry) = *cache {
-                if entry.fetched_at.elapsed() < CACHE_TTL {
+                if entry.fetched_at.elapsed() < self.cache_ttl {
                     return Ok(entry.data.clone());
                 }
             }
         }
 
-        let now = chrono::Utc::now();
-        let start = now - chrono::Duration::days(30);
-        let starting_at = start.format("%Y-%m-%dT00:00:00Z").to_string();
-        let ending_at = now.format("%Y-%m-%dT23:59:59Z").to_string();
+        let (starting_at, ending_at) = report_window(STATS_DAYS);
+        let stats = self.usage_stats_between(&starting_at, &ending_at, Some(STATS_DAYS))?;
+
+        // Update cache
+        if let Ok(mut cache) = self.usage_cache.lock() {
+            *cache = Some(CacheEntry {
+                data: stats.clone(),
+                fetched_at: Instant::now(),
+            });
+        }
+
+        Ok(stats)
+    }
 
+    /// Build UsageStats for `starting_at..ending_at` (RFC 3339), uncached.
+    /// `expected_days` enables the bucket count sanity check.
+    fn usage_stats_between(
+        &self,
+        starting_at: &str,
+        ending_at: &str,
+        expected_days: Option<u32>,
+    ) -> Result<UsageStats, ProviderError> {
         // Fetch usage grouped by model
-        let buckets = self.fetch_usage_report(&starting_at, &ending_at, true)?;
+        let buckets = self.fetch_usage_report(starting_at, ending_at, GROUP_BY_MODEL)?;
+        if let Some(days) = expected_days {
+            warn_on_bucket_mismatch(&buckets, days);
+        }
 
         let mut total_input: u64 = 0;
         let mut total_output: u64 = 0;
         let mut total_cache_read: u64 = 0;
         let mut total_cache_write: u64 = 0;
-        let mut model_map: HashMap<String, (u64, u64, u64, u64)> = HashMap::new();
+        // (model, batch) -> token counts
+        let mut model_map: HashMap<(String, bool), TokenCounts> = HashMap::new();
         let mut total_messages: u32 = 0;
+        let mut service_tier_tokens: HashMap<String, u64> = HashMap::new();
 
         for bucket in &buckets {
             for result in &bucket.results {
                 let model = result.model.as_deref().unwrap_or("unknown").to_string();
-                let cache_write = result.cache_creation.as_ref()
-                    .map(|c| c.ephemeral_5m_input_tokens + c.ephemeral_1h_input_tokens)
-                    .unwrap_or(0);
+                let (write_5m, write_1h) = result.cache_creation.as_ref()
+                    .map(|c| (c.ephemeral_5m_input_tokens, c.ephemeral_1h_input_tokens))
+                    .unwrap_or((0, 0));
+                let cache_write = write_5m + write_1h;
 
                 total_input += result.uncached_input_tokens;
                 total_output += result.output_tokens;
                 total_cache_read += result.cache_read_input_tokens;
                 total_cache_write += cache_write;
 
-                let entry = model_map.entry(model).or_insert((0, 0, 0, 0));
+                let batch = result.service_tier.as_deref() == Some(BATCH_SERVICE_TIER);
+                let entry = model_map.entry((model, batch)).or_insert((0, 0, 0, 0, 0));
                 entry.0 += result.uncached_input_tokens;
                 entry.1 += result.output_tokens;
                 entry.2 += result.cache_read_input_tokens;
-                entry.3 += cache_write;
+                entry.3 += write_5m;
+                entry.4 += write_1h;
+
+                total_messages += result.requests();
 
-                // Each result row with tokens likely represents at least one request
-                if result.output_tokens > 0 || result.uncached_input_tokens > 0 {
-                    total_messages += 1;
+                if let Some(ref tier) = result.service_tier {
+                    *service_tier_tokens.entry(tier.clone()).or_insert(0) += result.uncached_input_tokens
+                        + result.output_tokens
+                        + result.cache_read_input_tokens
+                        + cache_write;
                 }
             }
         }
 
-        // Fetch actual cost
-        let total_cost = self.fetch_cost_report(&starting_at, &ending_at).unwrap_or(0.0);
-
-        // Build model breakdown
+        // Build model breakdown, with batch usage listed as "<model> (batch)".
+        // The cost report has no per-model split, so model costs are
+        // estimated from the pricing table.
         let model_breakdown: HashMap<String, ModelUsage> = model_map
             .into_iter()
-            .map(|(model, (input, output, cache_read, cache_write))| {
+            .map(|((model, batch), (input, output, cache_read, write_5m, write_1h))| {
+                let name = if batch { format!("{} (batch)", model) } else { model.clone() };
                 let mu = ModelUsage {
-                    model: model.clone(),
+                    model: name.clone(),
                     input_tokens: input,
                     output_tokens: output,
                     cache_read_tokens: cache_read,
-                    cache_write_tokens: cache_write,
-                    cost_usd: 0.0, // Individual model cost not available from cost report
+                    cache_write_tokens: write_5m + write_1h,
+                    cache_write_1h_tokens: write_1h,
+                    cost_usd: (estimate_cost(&model, batch, input, output, cache_read, write_5m, write_1h) * 100.0)
+                        .round()
+                        / 100.0,
+                    ..Default::default()
                 };
-                (model, mu)
+                (name, mu)
             })
             .collect();
 
-        let stats = UsageStats {
+        // Fetch actual cost, falling back to the per-model estimates. A cost
+        // in another currency is kept aside so the USD field stays USD.
+        let estimate: f64 = model_breakdown.values().map(|m| m.cost_usd).sum();
+        let estimate = (estimate * 100.0).round() / 100.0;
+        let (total_cost, reported_cost, reported_currency) = match self.fetch_cost_report(starting_at, ending_at) {
+            Ok((cost, currency)) if currency == "USD" => (cost, None, String::new()),
+            Ok((cost, currency)) => (estimate, Some(cost), currency),
+            Err(e) => {
+                tracing::warn!("{}; using estimated cost", e);
+                (estimate, None, String::new())
+            }
+        };
+
+        let mut stats = UsageStats {
             provider: "Claude (API)".to_string(),
             total_input_tokens: total_input,
             total_output_tokens: total_output,
@@ -291,25 +542,124 @@ impl ClaudeApiProvider {
             total_messages,
             estimated_cost_usd: total_cost,
             model_breakdown,
+            service_tier_tokens,
+            reported_cost,
+            reported_currency,
+            ..Default::default()
         };
+        stats.compute_cache_hit_ratios();
+        Ok(stats)
+    }
 
-        // Update cache
-        if let Ok(mut cache) = self.usage_cache.lock() {
-            *cache = Some(CacheEntry {
-                data: stats.clone(),
-                fetched_at: Instant::now(),
+    /// Names of the organization's workspaces by id, following pagination.
+    fn fetch_workspace_names(&self) -> Result<HashMap<String, String>, ProviderError> {
+        let mut names = HashMap::new();
+        let mut after: Option<String> = None;
+        let mut pages: u32 = 0;
+
+        loop {
+            pages += 1;
+            let mut req = self.client
+                .get(format!("{}/v1/organizations/workspaces", API_BASE))
+                .header("x-api-key", &self.api_key)
+                .header("anthropic-version", "2023-06-01")
+                .query(&[("limit", "100")]);
+
+            if let Some(ref id) = after {
+                req = req.query(&[("after_id", id.as_str())]);
+            }
+
+            let resp = send_with_retry(req)
+                .map_err(|e| ProviderError::Network(format!("Workspace list request failed: {}", e)))?;
+
+            if !resp.status().is_success() {
+                let status = resp.status();
+                let body = resp.text().unwrap_or_default();
+                return Err(ProviderError::from_status(status, format!("Workspace list error {}: {}", status, body)));
+            }
+
+            let list: WorkspaceList = resp
+                .json()
+                .map_err(|e| ProviderError::Parse(format!("Failed to parse workspace list: {}", e)))?;
+
+            names.extend(list.data.into_iter().map(|w| (w.id, w.name)));
+
+            after = next_page(after, list.has_more, list.last_id, pages)?;
+            if after.is_none() {
+                break;
+            }
+        }
+
+        Ok(names)
+    }
+
+    /// Usage over the stats window per workspace, and per API key with
+    /// `by_api_key`, most expensive first. Costs are estimated per model.
+    fn build_workspace_breakdown(&self, by_api_key: bool) -> Result<Vec<WorkspaceUsage>, ProviderError> {
+        let (starting_at, ending_at) = report_window(STATS_DAYS);
+        let mut group_by = vec!["workspace_id"];
+        if by_api_key {
+            group_by.push("api_key_id");
+        }
+        group_by.extend_from_slice(GROUP_BY_MODEL);
+        let buckets = self.fetch_usage_report(&starting_at, &ending_at, &group_by)?;
+
+        // Names are a nicety; fall back to the ids
+        let names = self.fetch_workspace_names().unwrap_or_else(|e| {
+            tracing::warn!("{}; showing workspace ids", e);
+            HashMap::new()
+        });
+
+        let mut totals: HashMap<(Option<String>, Option<String>), WorkspaceUsage> = HashMap::new();
+        for result in buckets.iter().flat_map(|b| &b.results) {
+            let (write_5m, write_1h) = result.cache_creation.as_ref()
+                .map(|c| (c.ephemeral_5m_input_tokens, c.ephemeral_1h_input_tokens))
+                .unwrap_or((0, 0));
+            let cost = estimate_cost(
+                result.model.as_deref().unwrap_or("unknown"),
+                result.service_tier.as_deref() == Some(BATCH_SERVICE_TIER),
+                result.uncached_input_tokens,
+                result.output_tokens,
+                result.cache_read_input_tokens,
+                write_5m,
+                write_1h,
+            );
+
+            let key = (result.workspace_id.clone(), result.api_key_id.clone());
+            let entry = totals.entry(key).or_insert_with(|| WorkspaceUsage {
+                workspace_id: result.workspace_id.clone(),
+                workspace_name: match result.workspace_id {
+                    Some(ref id) => names.get(id).cloned().unwrap_or_else(|| id.clone()),
+                    None => "Default".to_string(),
+                },
+                api_key_id: result.api_key_id.clone(),
+                ..Default::default()
             });
+            entry.input_tokens += result.uncached_input_tokens;
+            entry.output_tokens += result.output_tokens;
+            entry.cache_read_tokens += result.cache_read_input_tokens;
+            entry.cache_write_tokens += write_5m + write_1h;
+            entry.cost_usd += cost;
         }
 
-        Ok(stats)
+        let mut breakdown: Vec<WorkspaceUsage> = totals.into_values().collect();
+        for w in &mut breakdown {
+            w.cost_usd = (w.cost_usd * 100.0).round() / 100.0;
+        }
+        breakdown.sort_by(|a, b| {
+            b.cost_usd
+                .total_cmp(&a.cost_usd)
+                .then_with(|| a.workspace_name.cmp(&b.workspace_name))
+        });
+        Ok(breakdown)
     }
 
     /// Build daily usage from API data, using cache if available.
-    fn build_daily_usage(&self, days: u32) -> Result<Vec<DailyUsage>, String> {
+    fn build_daily_usage(&self, days: u32) -> Result<Vec<DailyUsage>, ProviderError> {
         // Check cache
         if let Ok(cache) = self.daily_cache.lock() {
             if let Some(ref entry) = *cache {
-                if entry.fetched_at.elapsed() < CACHE_TTL {
+                if entry.fetched_at.elapsed() < self.cache_ttl {
                     let mut data = entry.data.clone();
                     data.truncate(days as usize);
                     return Ok(data);
@@ -317,30 +667,29 @@ impl ClaudeApiProvider {
             }
         }
 
-        let now = chrono::Utc::now();
-        let start = now - chrono::Duration::days(days as i64);
-        let starting_at = start.format("%Y-%m-%dT00:00:00Z").to_string();
-        let ending_at = now.format("%Y-%m-%dT23:59:59Z").to_string();
+        let (starting_at, ending_at) = report_window(days);
 
-        let buckets = self.fetch_usage_report(&starting_at, &ending_at, false)?;
+        let buckets = self.fetch_usage_report(&starting_at, &ending_at, &[])?;
+        warn_on_bucket_mismatch(&buckets, days);
 
         let mut daily: Vec<DailyUsage> = buckets
             .into_iter()
             .map(|bucket| {
                 let mut input: u64 = 0;
                 let mut output: u64 = 0;
+                let mut cache_read: u64 = 0;
+                let mut cache_write: u64 = 0;
                 let mut messages: u32 = 0;
 
                 for result in &bucket.results {
-                    input += result.uncached_input_tokens + result.cache_read_input_tokens;
-                    let cache_write = result.cache_creation.as_ref()
+                    // Keep cache tokens separate, as build_usage_stats does
+                    input += result.uncached_input_tokens;
+                    cache_read += result.cache_read_input_tokens;
+                    cache_write += result.cache_creation.as_ref()
                         .map(|c| c.ephemeral_5m_input_tokens + c.ephemeral_1h_input_tokens)
                         .unwrap_or(0);
-                    input += cache_write;
                     output += result.output_tokens;
-                    if result.output_tokens > 0 || result.uncached_input_tokens > 0 {
-                        messages += 1;
-                    }
+                    messages += result.requests();
                 }
 
                 // Extract date from starting_at (RFC 3339)
@@ -352,6 +701,9 @@ impl ClaudeApiProvider {
                     output_tokens: output,
                     sessions: 0,
                     messages,
+                    cache_read_tokens: cache_read,
+                    cache_write_tokens: cache_write,
+                    tool_calls: 0,
                 }
             })
             .collect();
@@ -388,21 +740,73 @@ impl Provider for ClaudeApiProvider {
         &DUMMY
     }
 
-    fn get_usage_stats(&self) -> Result<UsageStats, String> {
+    fn get_usage_stats(&self) -> Result<UsageStats, ProviderError> {
         self.build_usage_stats()
     }
 
-    fn get_active_sessions(&self) -> Result<Vec<Session>, String> {
+    fn diagnostics(&self) -> Diagnostics {
+        Diagnostics {
+            last_fetch: self.last_fetch.lock().unwrap_or_else(PoisonError::into_inner).clone(),
+            ..Default::default()
+        }
+    }
+
+    fn health_check(&self) -> Result<(), ProviderError> {
+        // A one-day ungrouped report is the smallest authenticated request
+        let (starting_at, ending_at) = report_window(1);
+        self.fetch_usage_report(&starting_at, &ending_at, &[]).map(|_| ())
+    }
+
+    fn clear_cache(&self) {
+        // A poisoned cache only ever held stale data, so reset it anyway
+        *self.usage_cache.lock().unwrap_or_else(PoisonError::into_inner) = None;
+        self.usage_cache.clear_poison();
+        *self.daily_cache.lock().unwrap_or_else(PoisonError::into_inner) = None;
+        self.daily_cache.clear_poison();
+    }
+
+    fn get_usage_stats_range(&self, start: &str, end: &str) -> Result<UsageStats, ProviderError> {
+        self.usage_stats_between(start, end, None)
+    }
+
+    fn get_active_sessions(&self) -> Result<Vec<Session>, ProviderError> {
         // API does not have a session concept
         Ok(Vec::new())
     }
 
-    fn get_daily_usage(&self, days: u32) -> Result<Vec<DailyUsage>, String> {
+    fn get_daily_usage(&self, days: u32) -> Result<Vec<DailyUsage>, ProviderError> {
         self.build_daily_usage(days)
     }
 
-    fn get_session_history(&self, _limit: u32) -> Result<Vec<Session>, String> {
+    fn get_session_history(&self, _limit: u32) -> Result<Vec<Session>, ProviderError> {
         // API does not have session history
         Ok(Vec::new())
     }
+
+    fn get_rate_limit_status(&self) -> Result<RateLimitStatus, ProviderError> {
+        // A one-day report is the cheapest request that returns the headers
+        let (starting_at, ending_at) = report_window(1);
+        self.fetch_usage_report(&starting_at, &ending_at, &[])?;
+
+        let rate_limit = self
+            .rate_limit
+            .lock()
+            .map_err(|e| format!("Failed to lock rate limit: {}", e))?;
+        Ok(rate_limit.clone().unwrap_or_default())
+    }
+
+    fn get_workspace_breakdown(&self, by_api_key: bool) -> Result<Vec<WorkspaceUsage>, ProviderError> {
+        self.build_workspace_breakdown(by_api_key)
+    }
+
+    fn debug_raw_usage(&self, days: u32) -> Result<serde_json::Value, ProviderError> {
+        let (starting_at, ending_at) = report_window(days);
+
+        let buckets = self.fetch_usage_report(&starting_at, &ending_at, GROUP_BY_MODEL)?;
+        Ok(serde_json::json!({
+            "starting_at": starting_at,
+            "ending_at": ending_at,
+            "buckets": buckets,
+        }))
+    }
 }
@@ -1,12 +1,21 @@
-use super::{DailyUsage, ModelUsage, Provider, Session, UsageStats};
+use super::source::{FileSource, RealFs};
+use super::{
+    count_recently_modified, DailyUsage, Diagnostics, JsonlLines, ModelUsage, Provider, ProviderError, Session, UsageStats,
+};
+use crate::pricing;
+use chrono::{DateTime, Local};
 use serde::Deserialize;
-use std::collections::HashMap;
-use std::fs;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 
 pub struct GeminiProvider {
     config_dir: PathBuf,
+    /// How recently a session file must have been written to count as active.
+    active_threshold: Duration,
+    /// Where session files are read from.
+    source: Arc<dyn FileSource>,
 }
 
 // --- Deserialization types for Gemini session JSONL ---
@@ -14,8 +23,10 @@ pub struct GeminiProvider {
 #[derive(Debug, Deserialize)]
 struct GeminiSessionLine {
     #[serde(default, rename = "type")]
+    #[allow(dead_code)]
     line_type: Option<String>,
     #[serde(default)]
+    #[allow(dead_code)]
     id: Option<String>,
     #[serde(default)]
     tokens: Option<GeminiTokens>,
@@ -31,6 +42,25 @@ struct GeminiTokens {
     input: u64,
     #[serde(default)]
     output: u64,
+    /// Part of `input` served from the context cache. Recent CLI versions only.
+    #[serde(default, alias = "cachedContentTokenCount")]
+    cached: u64,
+}
+
+impl GeminiTokens {
+    /// `input` less the cached part, which is reported separately.
+    fn uncached_input(&self) -> u64 {
+        self.input.saturating_sub(self.cached)
+    }
+}
+
+/// A parsed session together with its token split, which `Session` only
+/// carries as a single total.
+struct GeminiSession {
+    session: Session,
+    input_tokens: u64,
+    output_tokens: u64,
+    cached_tokens: u64,
 }
 
 // --- Deserialization types for legacy session JSON ---
@@ -52,8 +82,17 @@ struct GeminiLegacyMessage {
 }
 
 impl GeminiProvider {
-    pub fn new(config_dir: PathBuf) -> Self {
-        Self { config_dir }
+    pub fn new(config_dir: PathBuf, active_threshold: Duration) -> Self {
+        Self::with_source(config_dir, active_threshold, Arc::new(RealFs))
+    }
+
+    /// A provider reading its files from `source` instead of the disk.
+    pub fn with_source(config_dir: PathBuf, active_threshold: Duration, source: Arc<dyn FileSource>) -> Self {
+        Self {
+            config_dir,
+            active_threshold,
+            source,
+        }
     }
 
     /// Determine the Gemini config directory.
@@ -67,84 +106,75 @@ impl GeminiProvider {
     /// Find all session JSONL files under tmp/<hash>/chats/
     fn find_session_jsonl_files(&self) -> Vec<PathBuf> {
         let base = self.effective_dir().join("tmp");
-        if !base.exists() {
+        if !self.source.exists(&base) {
             return Vec::new();
         }
 
-        let pattern = base
+        // Escape the directory so glob metacharacters in it (e.g. `[`) match literally
+        let pattern = PathBuf::from(glob::Pattern::escape(&base.to_string_lossy()))
             .join("*")
             .join("chats")
             .join("session-*.jsonl")
             .to_string_lossy()
             .to_string();
 
-        glob::glob(&pattern)
-            .map(|paths| paths.filter_map(|p| p.ok()).collect())
-            .unwrap_or_default()
+        self.source.glob(&pattern)
     }
 
     /// Find legacy session JSON files under tmp/<hash>/chats/
     fn find_legacy_session_files(&self) -> Vec<PathBuf> {
         let base = self.effective_dir().join("tmp");
-        if !base.exists() {
+        if !self.source.exists(&base) {
             return Vec::new();
         }
 
-        let pattern = base
+        // Escape the directory so glob metacharacters in it (e.g. `[`) match literally
+        let pattern = PathBuf::from(glob::Pattern::escape(&base.to_string_lossy()))
             .join("*")
             .join("chats")
             .join("session-*.json")
             .to_string_lossy()
             .to_string();
 
-        glob::glob(&pattern)
-            .map(|paths| {
-                paths
-                    .filter_map(|p| p.ok())
-                    // Exclude .jsonl files matched by accident
-                    .filter(|p| {
-                        p.extension()
-                            .map(|e| e == "json")
-                            .unwrap_or(false)
-                    })
-                    .collect()
+        self.source
+            .glob(&pattern)
+            .into_iter()
+            // Exclude .jsonl files matched by accident
+            .filter(|p| {
+                p.extension()
+                    .map(|e| e == "json")
+                    .unwrap_or(false)
             })
-            .unwrap_or_default()
+            .collect()
     }
 
     /// Parse a JSONL session file.
-    fn parse_jsonl_session(&self, path: &PathBuf) -> Option<Session> {
-        let data = fs::read_to_string(path).ok()?;
-        if data.trim().is_empty() {
-            return None;
-        }
+    fn parse_jsonl_session(&self, path: &Path) -> Option<GeminiSession> {
+        let entries = JsonlLines::<GeminiSessionLine>::open(self.source.as_ref(), path)
+            .map_err(|e| tracing::warn!("Failed to read {}: {}", path.display(), e))
+            .ok()?;
 
         let mut total_input: u64 = 0;
         let mut total_output: u64 = 0;
+        let mut total_cached: u64 = 0;
         let mut message_count: u32 = 0;
         let mut last_model = String::new();
         let mut last_timestamp = String::new();
 
-        for line in data.lines() {
-            let line = line.trim();
-            if line.is_empty() {
-                continue;
+        for entry in entries {
+            if let Some(ref ts) = entry.timestamp {
+                last_timestamp = ts.clone();
             }
 
-            if let Ok(entry) = serde_json::from_str::<GeminiSessionLine>(line) {
-                if let Some(ref ts) = entry.timestamp {
-                    last_timestamp = ts.clone();
-                }
-
-                if let Some(ref model) = entry.model {
-                    last_model = model.clone();
-                }
+            if let Some(ref model) = entry.model {
+                last_model = model.clone();
+            }
 
-                if let Some(ref tokens) = entry.tokens {
-                    total_input += tokens.input;
-                    total_output += tokens.output;
-                    message_count += 1;
-                }
+            if let Some(ref tokens) = entry.tokens {
+                total_input += tokens.uncached_input();
+                total_output += tokens.output;
+                total_cached += tokens.cached;
+                message_count += 1;
             }
         }
 
@@ -165,44 +195,65 @@ impl GeminiProvider {
             .map(|n| n.to_string_lossy().to_string())
             .unwrap_or_else(|| "unknown".to_string());
 
-        let is_active = fs::metadata(path)
-            .and_then(|m| m.modified())
+        let is_active = self
+            .source
+            .modified(path)
             .map(|modified| {
                 SystemTime::now()
                     .duration_since(modified)
                     .unwrap_or(Duration::from_secs(u64::MAX))
-                    < Duration::from_secs(30 * 60)
+                    < self.active_threshold
             })
             .unwrap_or(false);
 
-        Some(Session {
+        let model = if last_model.is_empty() {
+            "gemini-unknown".to_string()
+        } else {
+            last_model
+        };
+
+        let session = Session {
             id: session_id,
+            provider: "Gemini".to_string(),
+            profile_id: String::new(),
             project,
-            model: if last_model.is_empty() {
-                "gemini-unknown".to_string()
-            } else {
-                last_model
-            },
-            tokens_used: total_input + total_output,
+            project_path: String::new(),
+            project_dir_encoded: String::new(),
+            cost_usd: Self::estimate_cost(&model, total_input, total_output, total_cached),
+            model,
+            tokens_used: total_input + total_output + total_cached,
             last_active: last_timestamp,
             is_active,
             message_count,
+        };
+
+        Some(GeminiSession {
+            session,
+            input_tokens: total_input,
+            output_tokens: total_output,
+            cached_tokens: total_cached,
         })
     }
 
     /// Parse a legacy JSON session file.
-    fn parse_legacy_session(&self, path: &PathBuf) -> Option<Session> {
-        let data = fs::read_to_string(path).ok()?;
-        let session: GeminiLegacySession = serde_json::from_str(&data).ok()?;
+    fn parse_legacy_session(&self, path: &Path) -> Option<GeminiSession> {
+        let reader = self.source.open(path)
+            .map_err(|e| tracing::warn!("Failed to read {}: {}", path.display(), e))
+            .ok()?;
+        let session: GeminiLegacySession = serde_json::from_reader(reader)
+            .map_err(|e| tracing::warn!("Failed to parse {}: {}", path.display(), e))
+            .ok()?;
 
         let mut total_input: u64 = 0;
         let mut total_output: u64 = 0;
+        let mut total_cached: u64 = 0;
         let message_count = session.messages.len() as u32;
 
         for msg in &session.messages {
             if let Some(ref tokens) = msg.tokens {
-                total_input += tokens.input;
+                total_input += tokens.uncached_input();
                 total_output += tokens.output;
+                total_cached += tokens.cached;
             }
         }
 
@@ -222,13 +273,14 @@ impl GeminiProvider {
             .map(|n| n.to_string_lossy().to_string())
             .unwrap_or_else(|| "unknown".to_string());
 
-        let is_active = fs::metadata(path)
-            .and_then(|m| m.modified())
+        let is_active = self
+            .source
+            .modified(path)
             .map(|modified| {
                 SystemTime::now()
                     .duration_since(modified)
                     .unwrap_or(Duration::from_secs(u64::MAX))
-                    < Duration::from_secs(30 * 60)
+                    < self.active_threshold
             })
             .unwrap_or(false);
 
@@ -237,19 +289,70 @@ impl GeminiProvider {
             .clone()
             .unwrap_or_default();
 
-        Some(Session {
+        let model = session.model.unwrap_or_else(|| "gemini-unknown".to_string());
+
+        let session = Session {
             id: session_id,
+            provider: "Gemini".to_string(),
+            profile_id: String::new(),
             project,
-            model: session.model.unwrap_or_else(|| "gemini-unknown".to_string()),
-            tokens_used: total_input + total_output,
+            project_path: String::new(),
+            project_dir_encoded: String::new(),
+            cost_usd: Self::estimate_cost(&model, total_input, total_output, total_cached),
+            model,
+            tokens_used: total_input + total_output + total_cached,
             last_active,
             is_active,
             message_count,
+        };
+
+        Some(GeminiSession {
+            session,
+            input_tokens: total_input,
+            output_tokens: total_output,
+            cached_tokens: total_cached,
         })
     }
 
+    /// Add each message of a JSONL session file to the day of its own
+    /// timestamp, so a session spanning midnight is split across both days.
+    /// Lines without a timestamp count towards the last one seen.
+    fn add_jsonl_daily_usage(&self, path: &Path, date_map: &mut HashMap<String, DailyUsage>) {
+        let entries = match JsonlLines::<GeminiSessionLine>::open(self.source.as_ref(), path) {
+            Ok(e) => e,
+            Err(e) => {
+                tracing::warn!("Failed to read {}: {}", path.display(), e);
+                return;
+            }
+        };
+
+        let mut current_day: Option<String> = None;
+        let mut days_seen: HashSet<String> = HashSet::new();
+
+        for entry in entries {
+            if let Some(day) = entry.timestamp.as_deref().and_then(day_of) {
+                current_day = Some(day);
+            }
+            let (Some(tokens), Some(day)) = (entry.tokens, current_day.as_ref()) else {
+                continue;
+            };
+
+            let usage = date_map.entry(day.clone()).or_insert_with(|| DailyUsage {
+                date: day.clone(),
+                ..Default::default()
+            });
+            usage.input_tokens += tokens.uncached_input();
+            usage.output_tokens += tokens.output;
+            usage.cache_read_tokens += tokens.cached;
+            usage.messages += 1;
+            if days_seen.insert(day.clone()) {
+                usage.sessions += 1;
+            }
+        }
+    }
+
     /// Collect all sessions from both JSONL and legacy JSON formats.
-    fn all_sessions(&self) -> Vec<Session> {
+    fn all_sessions(&self) -> Vec<GeminiSession> {
         let mut sessions = Vec::new();
 
         for path in self.find_session_jsonl_files() {
@@ -267,24 +370,25 @@ impl GeminiProvider {
         sessions
     }
 
-    /// Estimate cost for Gemini models (per million tokens).
-    fn estimate_cost(model: &str, input_tokens: u64, output_tokens: u64) -> f64 {
-        let model_lower = model.to_lowercase();
-
-        let (input_rate, output_rate) = if model_lower.contains("flash") {
-            (0.15, 0.60)
-        } else {
-            // gemini-2.5-pro and default
-            (1.25, 10.0)
-        };
-
-        let cost =
-            (input_tokens as f64 * input_rate + output_tokens as f64 * output_rate) / 1_000_000.0;
+    /// Estimate cost for Gemini models using the pricing table.
+    pub(crate) fn estimate_cost(model: &str, input_tokens: u64, output_tokens: u64, cached_tokens: u64) -> f64 {
+        let cost = pricing::table()
+            .gemini_rates(model)
+            .cost(input_tokens, output_tokens, cached_tokens, 0);
 
         (cost * 100.0).round() / 100.0
     }
 }
 
+/// Local calendar day of an ISO 8601 timestamp, falling back to its date
+/// prefix when it doesn't parse as RFC 3339.
+fn day_of(timestamp: &str) -> Option<String> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|t| t.with_timezone(&Local).format("%Y-%m-%d").to_string())
+        .ok()
+        .or_else(|| timestamp.get(..10).map(str::to_string))
+}
+
 impl Provider for GeminiProvider {
     fn name(&self) -> &str {
         "Gemini"
@@ -298,36 +402,82 @@ impl Provider for GeminiProvider {
         &self.config_dir
     }
 
-    fn get_usage_stats(&self) -> Result<UsageStats, String> {
+    fn watch_dirs(&self) -> Vec<PathBuf> {
+        // Sessions live under tmp/<hash>/chats/
+        vec![self.effective_dir().join("tmp")]
+    }
+
+    fn probe(&self) -> Result<String, ProviderError> {
+        let dir = self.effective_dir();
+        if !dir.is_dir() {
+            return Err(ProviderError::NotConfigured(format!(
+                "Config directory not found: {}",
+                dir.display()
+            )));
+        }
+        let count = self.find_session_jsonl_files().len() + self.find_legacy_session_files().len();
+        if count == 0 {
+            return Err(ProviderError::NotConfigured(format!(
+                "No session files under {}",
+                dir.join("tmp").display()
+            )));
+        }
+        Ok(format!("Found {} session files", count))
+    }
+
+    fn health_check(&self) -> Result<(), ProviderError> {
+        self.probe()?;
+        let readable = self.find_session_jsonl_files().iter().any(|f| self.parse_jsonl_session(f).is_some())
+            || self.find_legacy_session_files().iter().any(|f| self.parse_legacy_session(f).is_some());
+        if readable {
+            Ok(())
+        } else {
+            Err(ProviderError::Parse(format!(
+                "No readable session file under {}",
+                self.effective_dir().join("tmp").display()
+            )))
+        }
+    }
+
+    fn diagnostics(&self) -> Diagnostics {
+        let dir = self.effective_dir();
+        let count = self.find_session_jsonl_files().len() + self.find_legacy_session_files().len();
+        Diagnostics {
+            config_dir: dir.display().to_string(),
+            config_dir_exists: dir.is_dir(),
+            session_files: Some(count as u32),
+            ..Default::default()
+        }
+    }
+
+    fn get_usage_stats(&self) -> Result<UsageStats, ProviderError> {
         let sessions = self.all_sessions();
 
         let mut total_input: u64 = 0;
         let mut total_output: u64 = 0;
+        let mut total_cached: u64 = 0;
         let mut total_messages: u32 = 0;
-        let mut model_map: HashMap<String, (u64, u64, u32)> = HashMap::new();
-
-        for session in &sessions {
-            // Approximate input/output split: 40/60 of total tokens_used
-            let input_est = session.tokens_used * 40 / 100;
-            let output_est = session.tokens_used - input_est;
+        let mut model_map: HashMap<String, (u64, u64, u64)> = HashMap::new();
 
-            total_input += input_est;
-            total_output += output_est;
-            total_messages += session.message_count;
+        for s in &sessions {
+            total_input += s.input_tokens;
+            total_output += s.output_tokens;
+            total_cached += s.cached_tokens;
+            total_messages += s.session.message_count;
 
             let entry = model_map
-                .entry(session.model.clone())
+                .entry(s.session.model.clone())
                 .or_insert((0, 0, 0));
-            entry.0 += input_est;
-            entry.1 += output_est;
-            entry.2 += session.message_count;
+            entry.0 += s.input_tokens;
+            entry.1 += s.output_tokens;
+            entry.2 += s.cached_tokens;
         }
 
         let mut model_breakdown: HashMap<String, ModelUsage> = HashMap::new();
         let mut total_cost: f64 = 0.0;
 
-        for (model_name, (input, output, _count)) in &model_map {
-            let cost = Self::estimate_cost(model_name, *input, *output);
+        for (model_name, (input, output, cached)) in &model_map {
+            let cost = Self::estimate_cost(model_name, *input, *output, *cached);
             total_cost += cost;
             model_breakdown.insert(
                 model_name.clone(),
@@ -335,65 +485,73 @@ impl Provider for GeminiProvider {
                     model: model_name.clone(),
                     input_tokens: *input,
                     output_tokens: *output,
-                    cache_read_tokens: 0,
+                    cache_read_tokens: *cached,
                     cache_write_tokens: 0,
                     cost_usd: cost,
+                    ..Default::default()
                 },
             );
         }
 
-        Ok(UsageStats {
+        let mut stats = UsageStats {
             provider: "Gemini".to_string(),
             total_input_tokens: total_input,
             total_output_tokens: total_output,
-            total_cache_read_tokens: 0,
+            total_cache_read_tokens: total_cached,
             total_cache_write_tokens: 0,
             total_sessions: sessions.len() as u32,
             total_messages,
             estimated_cost_usd: (total_cost * 100.0).round() / 100.0,
             model_breakdown,
-        })
+            ..Default::default()
+        };
+        stats.compute_cache_hit_ratios();
+        Ok(stats)
     }
 
-    fn get_active_sessions(&self) -> Result<Vec<Session>, String> {
-        let sessions = self.all_sessions();
-        Ok(sessions.into_iter().filter(|s| s.is_active).collect())
+    fn get_active_sessions(&self) -> Result<Vec<Session>, ProviderError> {
+        Ok(self
+            .all_sessions()
+            .into_iter()
+            .map(|s| s.session)
+            .filter(|s| s.is_active)
+            .collect())
     }
 
-    fn get_daily_usage(&self, days: u32) -> Result<Vec<DailyUsage>, String> {
-        let sessions = self.all_sessions();
+    fn active_session_count(&self) -> Result<u32, ProviderError> {
+        let mut files = self.find_session_jsonl_files();
+        files.extend(self.find_legacy_session_files());
+        Ok(count_recently_modified(self.source.as_ref(), &files, self.active_threshold))
+    }
 
-        // Group sessions by date (from last_active timestamp)
-        let mut date_map: HashMap<String, (u64, u64, u32, u32)> = HashMap::new();
+    fn get_daily_usage(&self, days: u32) -> Result<Vec<DailyUsage>, ProviderError> {
+        let mut date_map: HashMap<String, DailyUsage> = HashMap::new();
 
-        for session in &sessions {
-            // Extract date portion from ISO 8601 timestamp
-            let date = if session.last_active.len() >= 10 {
-                session.last_active[..10].to_string()
-            } else {
+        for path in self.find_session_jsonl_files() {
+            self.add_jsonl_daily_usage(&path, &mut date_map);
+        }
+
+        // Legacy sessions have no per-message timestamps; use createdAt
+        for path in self.find_legacy_session_files() {
+            let Some(s) = self.parse_legacy_session(&path) else {
+                continue;
+            };
+            let Some(date) = day_of(&s.session.last_active) else {
                 continue;
             };
 
-            let input_est = session.tokens_used * 40 / 100;
-            let output_est = session.tokens_used - input_est;
-
-            let entry = date_map.entry(date).or_insert((0, 0, 0, 0));
-            entry.0 += input_est;
-            entry.1 += output_est;
-            entry.2 += 1;
-            entry.3 += session.message_count;
+            let entry = date_map.entry(date.clone()).or_insert_with(|| DailyUsage {
+                date,
+                ..Default::default()
+            });
+            entry.input_tokens += s.input_tokens;
+            entry.output_tokens += s.output_tokens;
+            entry.cache_read_tokens += s.cached_tokens;
+            entry.sessions += 1;
+            entry.messages += s.session.message_count;
         }
 
-        let mut daily: Vec<DailyUsage> = date_map
-            .into_iter()
-            .map(|(date, (input, output, sessions, messages))| DailyUsage {
-                date,
-                input_tokens: input,
-                output_tokens: output,
-                sessions,
-                messages,
-            })
-            .collect();
+        let mut daily: Vec<DailyUsage> = date_map.into_values().collect();
 
         daily.sort_by(|a, b| b.date.cmp(&a.date));
         daily.truncate(days as usize);
@@ -401,8 +559,8 @@ impl Provider for GeminiProvider {
         Ok(daily)
     }
 
-    fn get_session_history(&self, limit: u32) -> Result<Vec<Session>, String> {
-        let mut sessions = self.all_sessions();
+    fn get_session_history(&self, limit: u32) -> Result<Vec<Session>, ProviderError> {
+        let mut sessions: Vec<Session> = self.all_sessions().into_iter().map(|s| s.session).collect();
 
         // Sort by last_active descending
         sessions.sort_by(|a, b| b.last_active.cmp(&a.last_active));
@@ -410,4 +568,11 @@ impl Provider for GeminiProvider {
 
         Ok(sessions)
     }
+
+    fn get_known_models(&self) -> Result<Vec<String>, ProviderError> {
+        let mut models: Vec<String> = self.all_sessions().into_iter().map(|s| s.session.model).collect();
+        models.sort();
+        models.dedup();
+        Ok(models)
+    }
 }
@@ -0,0 +1,210 @@
+use super::claude::ClaudeProvider;
+use super::{ProviderError, DailyModelUsage, DailyUsage, ModelUsage, Provider, Session, UsageStats};
+use chrono::{Datelike, Duration, Local, NaiveDate, Utc};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Demo provider that serves synthetic usage so the UI renders populated
+/// on first run and for screenshots. Data is deterministic for a given date.
+pub struct MockProvider {
+    config_dir: PathBuf,
+}
+
+/// (model, share of daily tokens in percent)
+const MODELS: &[(&str, u64)] = &[
+    ("claude-sonnet-4-5", 65),
+    ("claude-opus-4-1", 25),
+    ("claude-haiku-4-5", 10),
+];
+
+const PROJECTS: &[&str] = &["cldbar", "web-dashboard", "api-gateway", "ml-pipeline", "dotfiles"];
+
+/// Number of days summed into the overall usage stats.
+const STATS_DAYS: i64 = 30;
+
+/// SplitMix64: tiny deterministic PRNG so the same seed always yields the same data.
+struct Rng(u64);
+
+impl Rng {
+    fn seeded(date: NaiveDate) -> Self {
+        Self(date.num_days_from_ce() as u64)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `lo..hi`.
+    fn range(&mut self, lo: u64, hi: u64) -> u64 {
+        lo + self.next() % (hi - lo).max(1)
+    }
+}
+
+impl MockProvider {
+    pub fn new(config_dir: PathBuf) -> Self {
+        Self { config_dir }
+    }
+
+    /// Synthetic usage for a single day.
+    fn day(date: NaiveDate) -> DailyUsage {
+        let mut rng = Rng::seeded(date);
+        // Quieter weekends
+        let scale = if date.weekday().number_from_monday() > 5 { 3 } else { 10 };
+        let input = rng.range(20_000, 60_000) * scale;
+        let output = rng.range(8_000, 30_000) * scale;
+        let sessions = rng.range(1, 4) as u32 * scale as u32 / 3 + 1;
+        let messages = sessions * rng.range(8, 40) as u32;
+
+        DailyUsage {
+            date: date.format("%Y-%m-%d").to_string(),
+            input_tokens: input,
+            output_tokens: output,
+            sessions,
+            messages,
+            ..Default::default()
+        }
+    }
+
+    /// Synthetic sessions, newest first, seeded from today's date.
+    fn sessions(count: u32) -> Vec<Session> {
+        let now = Utc::now();
+        let mut rng = Rng::seeded(Local::now().date_naive());
+        let mut minutes_ago: i64 = 0;
+
+        (0..count)
+            .map(|i| {
+                minutes_ago += rng.range(3, 90) as i64;
+                let (model, _) = MODELS[rng.range(0, MODELS.len() as u64) as usize];
+                let tokens_used = rng.range(5_000, 400_000);
+                Session {
+                    id: format!("demo-{:04x}", rng.next() & 0xffff),
+                    provider: "Demo".to_string(),
+                    profile_id: String::new(),
+                    project: PROJECTS[(i as usize) % PROJECTS.len()].to_string(),
+                    project_path: String::new(),
+                    project_dir_encoded: String::new(),
+                    model: model.to_string(),
+                    tokens_used,
+                    last_active: (now - Duration::minutes(minutes_ago)).to_rfc3339(),
+                    is_active: minutes_ago < 30,
+                    message_count: rng.range(4, 120) as u32,
+                    cost_usd: ClaudeProvider::estimate_cost(model, tokens_used * 3 / 4, tokens_used / 4, 0, 0),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Provider for MockProvider {
+    fn name(&self) -> &str {
+        "Demo"
+    }
+
+    fn provider_type(&self) -> &str {
+        "demo"
+    }
+
+    fn config_dir(&self) -> &PathBuf {
+        &self.config_dir
+    }
+
+    fn probe(&self) -> Result<String, ProviderError> {
+        Ok("Demo data is always available".to_string())
+    }
+
+    fn get_usage_stats(&self) -> Result<UsageStats, ProviderError> {
+        let today = Local::now().date_naive();
+        let days: Vec<DailyUsage> = (0..STATS_DAYS)
+            .map(|i| Self::day(today - Duration::days(i)))
+            .collect();
+
+        let input: u64 = days.iter().map(|d| d.input_tokens).sum();
+        let output: u64 = days.iter().map(|d| d.output_tokens).sum();
+        let mut model_breakdown = HashMap::new();
+        let mut total_cost = 0.0;
+
+        for (model, share) in MODELS {
+            let m_input = input * share / 100;
+            let m_output = output * share / 100;
+            // Pretend most input is served from the prompt cache
+            let cache_read = m_input * 4;
+            let cache_write = m_input / 2;
+            let cost = ClaudeProvider::estimate_cost(model, m_input, m_output, cache_read, cache_write);
+            total_cost += cost;
+            model_breakdown.insert(
+                model.to_string(),
+                ModelUsage {
+                    model: model.to_string(),
+                    input_tokens: m_input,
+                    output_tokens: m_output,
+                    cache_read_tokens: cache_read,
+                    cache_write_tokens: cache_write,
+                    cost_usd: cost,
+                    ..Default::default()
+                },
+            );
+        }
+
+        let mut stats = UsageStats {
+            provider: "Demo".to_string(),
+            total_input_tokens: model_breakdown.values().map(|m| m.input_tokens).sum(),
+            total_output_tokens: model_breakdown.values().map(|m| m.output_tokens).sum(),
+            total_cache_read_tokens: model_breakdown.values().map(|m| m.cache_read_tokens).sum(),
+            total_cache_write_tokens: model_breakdown.values().map(|m| m.cache_write_tokens).sum(),
+            total_sessions: days.iter().map(|d| d.sessions).sum(),
+            total_messages: days.iter().map(|d| d.messages).sum(),
+            estimated_cost_usd: (total_cost * 100.0).round() / 100.0,
+            model_breakdown,
+            ..Default::default()
+        };
+        stats.compute_cache_hit_ratios();
+        Ok(stats)
+    }
+
+    fn get_active_sessions(&self) -> Result<Vec<Session>, ProviderError> {
+        Ok(Self::sessions(5).into_iter().filter(|s| s.is_active).collect())
+    }
+
+    fn get_daily_usage(&self, days: u32) -> Result<Vec<DailyUsage>, ProviderError> {
+        let today = Local::now().date_naive();
+        Ok((0..days as i64)
+            .map(|i| Self::day(today - Duration::days(i)))
+            .collect())
+    }
+
+    fn get_daily_model_usage(&self, days: u32) -> Result<Vec<DailyModelUsage>, ProviderError> {
+        let today = Local::now().date_naive();
+        let mut usage = Vec::new();
+        for i in 0..days as i64 {
+            let day = Self::day(today - Duration::days(i));
+            for (model, share) in MODELS {
+                let input = day.input_tokens * share / 100;
+                let output = day.output_tokens * share / 100;
+                usage.push(DailyModelUsage {
+                    date: day.date.clone(),
+                    model: model.to_string(),
+                    input_tokens: input,
+                    output_tokens: output,
+                    cache_read_tokens: 0,
+                    cache_write_tokens: 0,
+                    cost_usd: ClaudeProvider::estimate_cost(model, input, output, 0, 0),
+                });
+            }
+        }
+        Ok(usage)
+    }
+
+    fn get_session_history(&self, limit: u32) -> Result<Vec<Session>, ProviderError> {
+        Ok(Self::sessions(limit))
+    }
+
+    fn get_known_models(&self) -> Result<Vec<String>, ProviderError> {
+        let mut models: Vec<String> = MODELS.iter().map(|(m, _)| m.to_string()).collect();
+        models.sort();
+        Ok(models)
+    }
+}
@@ -1,8 +1,13 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use source::FileSource;
+use std::io::BufRead;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UsageStats {
     pub provider: String,
@@ -14,9 +19,49 @@ pub struct UsageStats {
     pub total_messages: u32,
     pub estimated_cost_usd: f64,
     pub model_breakdown: HashMap<String, ModelUsage>,
+    /// `estimated_cost_usd` converted to `display_currency`; set by the commands layer.
+    #[serde(default)]
+    pub display_cost: f64,
+    #[serde(default)]
+    pub display_currency: String,
+    /// Actual spend from a cost report in a currency other than USD, which
+    /// can't be added to USD costs; `estimated_cost_usd` then holds the USD
+    /// estimate. None when costs are in USD.
+    #[serde(default)]
+    pub reported_cost: Option<f64>,
+    /// ISO 4217 code of `reported_cost`.
+    #[serde(default)]
+    pub reported_currency: String,
+    /// Total tokens per API service tier ("standard", "batch", "priority").
+    /// Only populated by providers that report tiers.
+    #[serde(default)]
+    pub service_tier_tokens: HashMap<String, u64>,
+    #[serde(default)]
+    pub billing_mode: BillingMode,
+    /// What the usage would have cost at API list prices. Set in subscription
+    /// mode, where `estimated_cost_usd` is the actual spend of zero.
+    #[serde(default)]
+    pub equivalent_api_cost_usd: f64,
+    /// Tool calls made by the model. Only reported by providers that log them.
+    #[serde(default)]
+    pub total_tool_calls: u32,
+    /// Share of input served from cache; see [`cache_hit_ratio`].
+    /// Zero for providers that don't report cache reads.
+    #[serde(default)]
+    pub cache_hit_ratio: f64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// How a provider's usage is paid for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BillingMode {
+    #[default]
+    PerToken,
+    /// Flat-rate plan (e.g. Claude Pro/Max): tokens carry no marginal cost.
+    Subscription,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ModelUsage {
     pub model: String,
@@ -24,22 +69,77 @@ pub struct ModelUsage {
     pub output_tokens: u64,
     pub cache_read_tokens: u64,
     pub cache_write_tokens: u64,
+    /// Part of `cache_write_tokens` written with a 1-hour TTL, which is
+    /// billed higher. Only reported by the Claude API.
+    #[serde(default)]
+    pub cache_write_1h_tokens: u64,
     pub cost_usd: f64,
+    /// `cost_usd` converted to the display currency; set by the commands layer.
+    #[serde(default)]
+    pub display_cost: f64,
+    /// Share of input served from cache; see [`cache_hit_ratio`].
+    #[serde(default)]
+    pub cache_hit_ratio: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Session {
     pub id: String,
+    /// Display name of the provider that produced the session.
+    pub provider: String,
+    /// Owning profile id; filled in by the commands layer.
+    #[serde(default)]
+    pub profile_id: String,
     pub project: String,
+    /// Full project path when known; `project` holds a short display label.
+    pub project_path: String,
+    /// The project's directory name as the provider stores it, before
+    /// decoding (Claude's `-Users-me-code-myapp`). Empty for other providers.
+    #[serde(default)]
+    pub project_dir_encoded: String,
     pub model: String,
     pub tokens_used: u64,
     pub last_active: String,
     pub is_active: bool,
     pub message_count: u32,
+    /// Estimated cost in USD at API prices; 0.0 when the provider can't price it.
+    pub cost_usd: f64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Usage summed over all sessions of one project.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectUsage {
+    pub project: String,
+    /// Full project path, to tell apart projects sharing a label; empty
+    /// when the provider doesn't know it.
+    pub project_path: String,
+    pub tokens: u64,
+    pub messages: u32,
+    pub cost_usd: f64,
+    pub sessions: u32,
+}
+
+/// Usage summed over one workspace of an organization, or one API key
+/// within it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceUsage {
+    /// `None` for the organization's default workspace.
+    pub workspace_id: Option<String>,
+    /// The workspace's name, or its id when the name couldn't be looked up.
+    pub workspace_name: String,
+    /// Set when the breakdown is per API key.
+    pub api_key_id: Option<String>,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_write_tokens: u64,
+    pub cost_usd: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DailyUsage {
     pub date: String,
@@ -47,15 +147,104 @@ pub struct DailyUsage {
     pub output_tokens: u64,
     pub sessions: u32,
     pub messages: u32,
+    /// Only reported by providers that track cache usage per day.
+    #[serde(default)]
+    pub cache_read_tokens: u64,
+    #[serde(default)]
+    pub cache_write_tokens: u64,
+    /// Tool calls made by the model that day. Zero for providers that don't log them.
+    #[serde(default)]
+    pub tool_calls: u32,
 }
 
+/// Token usage for one model on one day.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+pub struct DailyModelUsage {
+    pub date: String,
+    pub model: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// Only reported by providers that track cache usage per model and day.
+    #[serde(default)]
+    pub cache_read_tokens: u64,
+    #[serde(default)]
+    pub cache_write_tokens: u64,
+    pub cost_usd: f64,
+}
+
+impl DailyModelUsage {
+    /// Move cache read/write tokens into the input count.
+    pub fn fold_cache_into_input(&mut self) {
+        self.input_tokens += self.cache_read_tokens + self.cache_write_tokens;
+        self.cache_read_tokens = 0;
+        self.cache_write_tokens = 0;
+    }
+}
+
+/// `cache_read / (cache_read + input)`, clamped to 0..1; zero when there
+/// was no input at all.
+pub fn cache_hit_ratio(cache_read_tokens: u64, input_tokens: u64) -> f64 {
+    let total = cache_read_tokens + input_tokens;
+    if total == 0 {
+        return 0.0;
+    }
+    (cache_read_tokens as f64 / total as f64).clamp(0.0, 1.0)
+}
+
+impl UsageStats {
+    /// Set `cache_hit_ratio` overall and per model from the token totals.
+    /// Call before `fold_cache_into_input`, which zeroes the cache reads.
+    pub fn compute_cache_hit_ratios(&mut self) {
+        self.cache_hit_ratio = cache_hit_ratio(self.total_cache_read_tokens, self.total_input_tokens);
+        for model in self.model_breakdown.values_mut() {
+            model.cache_hit_ratio = cache_hit_ratio(model.cache_read_tokens, model.input_tokens);
+        }
+    }
+
+    /// Move cache read/write tokens into the input totals, per model and overall.
+    pub fn fold_cache_into_input(&mut self) {
+        self.total_input_tokens += self.total_cache_read_tokens + self.total_cache_write_tokens;
+        self.total_cache_read_tokens = 0;
+        self.total_cache_write_tokens = 0;
+        for model in self.model_breakdown.values_mut() {
+            model.input_tokens += model.cache_read_tokens + model.cache_write_tokens;
+            model.cache_read_tokens = 0;
+            model.cache_write_tokens = 0;
+            model.cache_write_1h_tokens = 0;
+        }
+    }
+}
+
+impl DailyUsage {
+    /// Move cache read/write tokens into `input_tokens`.
+    pub fn fold_cache_into_input(&mut self) {
+        self.input_tokens += self.cache_read_tokens + self.cache_write_tokens;
+        self.cache_read_tokens = 0;
+        self.cache_write_tokens = 0;
+    }
+
+    /// All tokens for the day, cache included.
+    pub fn total_tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens + self.cache_read_tokens + self.cache_write_tokens
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct RateLimitStatus {
     pub available: bool,
     pub five_hour: Option<RateLimitWindow>,
     pub seven_day: Option<RateLimitWindow>,
     pub seven_day_opus: Option<RateLimitWindow>,
+    /// Start of the current 5-hour window, when local session data is available.
+    #[serde(default)]
+    pub window_started_at: Option<String>,
+    /// Sessions and messages with activity since `window_started_at`.
+    #[serde(default)]
+    pub sessions_in_window: Option<u32>,
+    #[serde(default)]
+    pub messages_in_window: Option<u32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -66,18 +255,512 @@ pub struct RateLimitWindow {
     pub resets_at: Option<String>,
 }
 
+/// Where a provider looks for data and what it found, for working out why
+/// stats are empty.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostics {
+    /// Directory actually read, after env var overrides. Empty for API providers.
+    pub config_dir: String,
+    pub config_dir_exists: bool,
+    /// Session files found; None for providers that don't read session files.
+    pub session_files: Option<u32>,
+    /// Data files the provider reads, e.g. stats-cache.json or sessions.db.
+    pub files: Vec<FileStatus>,
+    /// Outcome of the most recent API request; None until one is made.
+    pub last_fetch: Option<FetchStatus>,
+    /// Malformed lines skipped in the session files parsed so far; None for
+    /// providers that don't count them.
+    pub skipped_lines: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileStatus {
+    pub path: String,
+    pub exists: bool,
+    pub readable: bool,
+}
+
+impl FileStatus {
+    pub fn of(path: &Path) -> Self {
+        Self {
+            path: path.display().to_string(),
+            exists: path.exists(),
+            readable: std::fs::File::open(path).is_ok(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FetchStatus {
+    pub succeeded: bool,
+    /// When the request finished (RFC 3339).
+    pub at: String,
+    pub error: Option<String>,
+}
+
+impl FetchStatus {
+    pub fn of<T>(result: &Result<T, ProviderError>) -> Self {
+        Self {
+            succeeded: result.is_ok(),
+            at: Utc::now().to_rfc3339(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        }
+    }
+}
+
+/// Why a provider call failed. Serialized to the frontend as
+/// `{ "code": "unauthorized", "message": "..." }` so it can react to specific
+/// failures, e.g. prompt for a new API key on `unauthorized`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProviderError {
+    /// The API key was rejected (HTTP 401/403).
+    Unauthorized(String),
+    /// The API is throttling requests (HTTP 429).
+    RateLimited(String),
+    /// The request didn't get a response.
+    Network(String),
+    /// The data was read but couldn't be parsed.
+    Parse(String),
+    /// The profile is missing, disabled or lacks what it needs (key, directory).
+    NotConfigured(String),
+    Other(String),
+}
+
+impl ProviderError {
+    /// Machine-readable error kind sent to the frontend.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ProviderError::Unauthorized(_) => "unauthorized",
+            ProviderError::RateLimited(_) => "rate_limited",
+            ProviderError::Network(_) => "network",
+            ProviderError::Parse(_) => "parse",
+            ProviderError::NotConfigured(_) => "not_configured",
+            ProviderError::Other(_) => "other",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            ProviderError::Unauthorized(m)
+            | ProviderError::RateLimited(m)
+            | ProviderError::Network(m)
+            | ProviderError::Parse(m)
+            | ProviderError::NotConfigured(m)
+            | ProviderError::Other(m) => m,
+        }
+    }
+
+    /// Error for a non-success HTTP response, classified by status.
+    pub fn from_status(status: reqwest::StatusCode, message: String) -> Self {
+        match status.as_u16() {
+            401 | 403 => ProviderError::Unauthorized(message),
+            429 => ProviderError::RateLimited(message),
+            _ => ProviderError::Other(message),
+        }
+    }
+}
+
+impl std::fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for ProviderError {}
+
+impl Serialize for ProviderError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut s = serializer.serialize_struct("ProviderError", 2)?;
+        s.serialize_field("code", self.code())?;
+        s.serialize_field("message", self.message())?;
+        s.end()
+    }
+}
+
+impl From<String> for ProviderError {
+    fn from(message: String) -> Self {
+        ProviderError::Other(message)
+    }
+}
+
+impl From<ProviderError> for String {
+    fn from(e: ProviderError) -> Self {
+        e.to_string()
+    }
+}
+
 pub trait Provider: Send + Sync {
     fn name(&self) -> &str;
     fn provider_type(&self) -> &str;
     fn config_dir(&self) -> &PathBuf;
-    fn get_usage_stats(&self) -> Result<UsageStats, String>;
-    fn get_active_sessions(&self) -> Result<Vec<Session>, String>;
-    fn get_daily_usage(&self, days: u32) -> Result<Vec<DailyUsage>, String>;
-    fn get_session_history(&self, limit: u32) -> Result<Vec<Session>, String>;
+    fn get_usage_stats(&self) -> Result<UsageStats, ProviderError>;
+
+    /// Drop any cached usage so the next call fetches fresh data.
+    /// No-op for providers that don't cache.
+    fn clear_cache(&self) {}
+
+    /// Usage stats for `start..end` (RFC 3339). Defaults to ignoring the
+    /// range, for providers that can't query arbitrary windows.
+    fn get_usage_stats_range(&self, _start: &str, _end: &str) -> Result<UsageStats, ProviderError> {
+        self.get_usage_stats()
+    }
+
+    fn get_active_sessions(&self) -> Result<Vec<Session>, ProviderError>;
+    fn get_daily_usage(&self, days: u32) -> Result<Vec<DailyUsage>, ProviderError>;
+    fn get_session_history(&self, limit: u32) -> Result<Vec<Session>, ProviderError>;
+
+    /// Number of active sessions, for the tray badge. File-based providers
+    /// count recently written session files without parsing them; the
+    /// default counts `get_active_sessions`.
+    fn active_session_count(&self) -> Result<u32, ProviderError> {
+        Ok(self.get_active_sessions()?.len() as u32)
+    }
+
+    /// Whether `get_daily_usage` reports usage per day. Providers that only
+    /// know a current total (e.g. a rolling window) return false, and their
+    /// totals stand in for today in the history. Defaults to true.
+    fn has_daily_breakdown(&self) -> bool {
+        true
+    }
+
+    /// Per-model token usage for the last `days` days, newest first.
+    /// Defaults to empty for providers without a per-day model split.
+    fn get_daily_model_usage(&self, _days: u32) -> Result<Vec<DailyModelUsage>, ProviderError> {
+        Ok(Vec::new())
+    }
+
+    /// Cheap readiness check that avoids loading full stats.
+    /// Returns a short detail message either way. Defaults to checking that
+    /// the config directory exists.
+    fn probe(&self) -> Result<String, ProviderError> {
+        let dir = self.config_dir();
+        if dir.is_dir() {
+            Ok(format!("Found {}", dir.display()))
+        } else {
+            Err(ProviderError::NotConfigured(format!(
+                "Config directory not found: {}",
+                dir.display()
+            )))
+        }
+    }
+
+    /// Whether the provider can read usage right now: file providers need at
+    /// least one data file that parses, API providers an accepted key. Lets
+    /// "no usage yet" be told apart from a misconfiguration. Defaults to
+    /// healthy.
+    fn health_check(&self) -> Result<(), ProviderError> {
+        Ok(())
+    }
+
+    /// Current rate-limit utilization. Defaults to unavailable for providers
+    /// that don't report limits.
+    fn get_rate_limit_status(&self) -> Result<RateLimitStatus, ProviderError> {
+        Ok(RateLimitStatus::default())
+    }
+
+    /// How usage is billed. Defaults to per-token pricing.
+    fn billing_mode(&self) -> BillingMode {
+        BillingMode::PerToken
+    }
+
+    /// The `limit` most expensive sessions active within the last `days` days.
+    /// Defaults to filtering the full session history by `last_active`.
+    fn get_top_sessions(&self, days: u32, limit: u32) -> Result<Vec<Session>, ProviderError> {
+        let sessions = within_days(self.get_session_history(u32::MAX)?, days);
+        Ok(rank_by_cost(sessions, limit))
+    }
+
+    /// Unaggregated source data for the last `days` days, for bug reports.
+    /// API providers return the parsed API response; the default is the
+    /// parsed sessions.
+    fn debug_raw_usage(&self, days: u32) -> Result<serde_json::Value, ProviderError> {
+        let sessions = within_days(self.get_session_history(u32::MAX)?, days);
+        serde_json::to_value(sessions)
+            .map_err(|e| ProviderError::Parse(format!("Failed to serialize sessions: {}", e)))
+    }
+
+    /// Usage per workspace, or per API key within each workspace when
+    /// `by_api_key` is set. Defaults to empty for providers without
+    /// workspaces.
+    fn get_workspace_breakdown(&self, _by_api_key: bool) -> Result<Vec<WorkspaceUsage>, ProviderError> {
+        Ok(Vec::new())
+    }
+
+    /// Distinct model names this provider has seen, sorted.
+    /// Defaults to the keys of the usage stats model breakdown.
+    fn get_known_models(&self) -> Result<Vec<String>, ProviderError> {
+        let mut models: Vec<String> = self.get_usage_stats()?.model_breakdown.into_keys().collect();
+        models.sort();
+        Ok(models)
+    }
+
+    /// Directories whose session files change as usage is recorded, watched
+    /// recursively for live updates. Defaults to none, leaving the provider
+    /// to polling.
+    fn watch_dirs(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    /// Where the provider looks for data and what it found there. Defaults
+    /// to reporting only the config directory.
+    fn diagnostics(&self) -> Diagnostics {
+        let dir = self.config_dir();
+        Diagnostics {
+            config_dir: dir.display().to_string(),
+            config_dir_exists: dir.is_dir(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Parse a session's `last_active`: RFC 3339, or the "YYYY-MM-DD HH:MM:SS"
+/// UTC timestamps z.ai stores.
+pub fn parse_last_active(last_active: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(last_active)
+        .map(|t| t.with_timezone(&Utc))
+        .or_else(|_| {
+            chrono::NaiveDateTime::parse_from_str(last_active, "%Y-%m-%d %H:%M:%S").map(|t| t.and_utc())
+        })
+        .ok()
+}
+
+/// Keep sessions whose `last_active` is within the last `days` days.
+fn within_days(sessions: Vec<Session>, days: u32) -> Vec<Session> {
+    let cutoff = Utc::now() - Duration::days(days as i64);
+    sessions
+        .into_iter()
+        .filter(|s| {
+            DateTime::parse_from_rfc3339(&s.last_active)
+                .map(|t| t >= cutoff)
+                .unwrap_or(false)
+        })
+        .collect()
+}
+
+/// Sort sessions by cost descending, falling back to tokens for providers
+/// without cost data, and keep the first `limit`.
+pub fn rank_by_cost(mut sessions: Vec<Session>, limit: u32) -> Vec<Session> {
+    sessions.sort_by(|a, b| {
+        b.cost_usd
+            .total_cmp(&a.cost_usd)
+            .then(b.tokens_used.cmp(&a.tokens_used))
+    });
+    sessions.truncate(limit as usize);
+    sessions
+}
+
+/// Group sessions by project and keep the `limit` most expensive projects,
+/// falling back to tokens like [`rank_by_cost`]. Projects are keyed on
+/// `project_path` where known, so two repos sharing a folder name stay
+/// apart; `project` is kept as the label. Sessions without a project are
+/// left out.
+pub fn project_breakdown(sessions: Vec<Session>, limit: u32) -> Vec<ProjectUsage> {
+    let mut projects: HashMap<String, ProjectUsage> = HashMap::new();
+    for s in sessions.into_iter().filter(|s| !s.project.is_empty()) {
+        let key = if s.project_path.is_empty() {
+            s.project.clone()
+        } else {
+            s.project_path.clone()
+        };
+        let p = projects.entry(key).or_insert_with(|| ProjectUsage {
+            project: s.project,
+            project_path: s.project_path,
+            ..Default::default()
+        });
+        p.tokens += s.tokens_used;
+        p.messages += s.message_count;
+        p.cost_usd += s.cost_usd;
+        p.sessions += 1;
+    }
+
+    let mut projects: Vec<ProjectUsage> = projects
+        .into_values()
+        .map(|mut p| {
+            p.cost_usd = (p.cost_usd * 100.0).round() / 100.0;
+            p
+        })
+        .collect();
+    projects.sort_by(|a, b| {
+        b.cost_usd
+            .total_cmp(&a.cost_usd)
+            .then(b.tokens.cmp(&a.tokens))
+            .then_with(|| a.project.cmp(&b.project))
+            .then_with(|| a.project_path.cmp(&b.project_path))
+    });
+    projects.truncate(limit as usize);
+    projects
+}
+
+/// How many of `files` were modified within `threshold`.
+pub(crate) fn count_recently_modified(
+    source: &dyn FileSource,
+    files: &[PathBuf],
+    threshold: std::time::Duration,
+) -> u32 {
+    let now = std::time::SystemTime::now();
+    files
+        .iter()
+        .filter(|f| {
+            source
+                .modified(f)
+                .map(|modified| now.duration_since(modified).is_ok_and(|age| age < threshold))
+                .unwrap_or(false)
+        })
+        .count() as u32
+}
+
+/// The lines of a JSONL file, parsed one at a time so memory stays bounded
+/// however large the file is. Blank lines are skipped quietly; malformed
+/// ones are logged, skipped and counted, except a last line without a
+/// trailing newline, which is most likely still being written.
+pub(crate) struct JsonlLines<T> {
+    path: PathBuf,
+    reader: Box<dyn BufRead + Send>,
+    buf: Vec<u8>,
+    skipped: u32,
+    entry: PhantomData<T>,
+}
+
+impl<T> JsonlLines<T> {
+    pub(crate) fn open(source: &dyn FileSource, path: &Path) -> std::io::Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            reader: source.open(path)?,
+            buf: Vec::new(),
+            skipped: 0,
+            entry: PhantomData,
+        })
+    }
+
+    /// Malformed lines skipped so far.
+    pub(crate) fn skipped(&self) -> u32 {
+        self.skipped
+    }
+}
+
+impl<T: DeserializeOwned> Iterator for JsonlLines<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        loop {
+            self.buf.clear();
+            match self.reader.read_until(b'\n', &mut self.buf) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::warn!("Failed to read {}: {}", self.path.display(), e);
+                    return None;
+                }
+            }
+
+            let line = self.buf.trim_ascii();
+            if line.is_empty() {
+                continue;
+            }
+            match serde_json::from_slice(line) {
+                Ok(entry) => return Some(entry),
+                Err(_) if !self.buf.ends_with(b"\n") => {
+                    tracing::debug!("Ignoring partial last line in {}", self.path.display());
+                }
+                Err(e) => {
+                    tracing::debug!("Skipping malformed line in {}: {}", self.path.display(), e);
+                    self.skipped += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Build an HTTP client for API requests. A non-empty `proxy_url` routes all
+/// requests through that proxy, still honoring NO_PROXY; otherwise reqwest
+/// picks up HTTPS_PROXY/HTTP_PROXY from the environment.
+pub(crate) fn http_client(
+    timeout: std::time::Duration,
+    proxy_url: Option<&str>,
+) -> Result<reqwest::blocking::Client, String> {
+    let mut builder = reqwest::blocking::Client::builder().timeout(timeout);
+
+    if let Some(url) = proxy_url.map(str::trim).filter(|u| !u.is_empty()) {
+        let proxy = reqwest::Proxy::all(url)
+            .map_err(|e| format!("Invalid proxy URL {}: {}", url, e))?
+            .no_proxy(reqwest::NoProxy::from_env());
+        builder = builder.proxy(proxy);
+    }
+
+    builder
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+/// Retries after the first attempt for transient API failures.
+const MAX_RETRIES: u32 = 3;
+
+/// Longest `Retry-After` wait honored on a 429.
+const MAX_RETRY_AFTER: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Most time spent waiting between attempts of one request. Provider calls
+/// run with the providers lock held, so a long wait would stall every other
+/// command; past this the last response is returned instead.
+const MAX_RETRY_WAIT: std::time::Duration = std::time::Duration::from_secs(8);
+
+/// Send an API request, retrying timeouts, connection errors, 429s and 5xx
+/// responses with exponential backoff (0.5s, 1s, 2s). A 429 with a
+/// `Retry-After` header waits that many seconds instead, up to
+/// `MAX_RETRY_AFTER`, and no more than `MAX_RETRY_WAIT` is spent waiting in
+/// total. Other responses, including 400/401/403, are returned as-is on the
+/// first attempt.
+pub(crate) fn send_with_retry(
+    request: reqwest::blocking::RequestBuilder,
+) -> reqwest::Result<reqwest::blocking::Response> {
+    let mut attempt: u32 = 0;
+    let mut waited = std::time::Duration::ZERO;
+    loop {
+        // Requests with a streaming body can't be cloned; send those once
+        let result = match request.try_clone() {
+            Some(r) => r.send(),
+            None => return request.send(),
+        };
+        if attempt >= MAX_RETRIES {
+            return result;
+        }
+
+        let backoff = std::time::Duration::from_millis(500 << attempt);
+        let wait = match &result {
+            Ok(resp) if resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS => resp
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .map(|secs| std::time::Duration::from_secs(secs).min(MAX_RETRY_AFTER))
+                .unwrap_or(backoff),
+            Ok(resp) if resp.status().is_server_error() => backoff,
+            Ok(_) => return result,
+            Err(e) if e.is_timeout() || e.is_connect() => backoff,
+            Err(_) => return result,
+        };
+        if waited + wait > MAX_RETRY_WAIT {
+            return result;
+        }
+
+        match &result {
+            Ok(resp) => tracing::info!("API request returned {}, retrying in {:?}", resp.status(), wait),
+            Err(e) => tracing::info!("API request failed ({}), retrying in {:?}", e, wait),
+        }
+        std::thread::sleep(wait);
+        waited += wait;
+        attempt += 1;
+    }
 }
 
 pub mod claude;
 pub mod claude_api;
 pub mod gemini;
+pub mod mock;
+pub mod openai;
+pub mod source;
 pub mod zai;
 pub mod zai_api;
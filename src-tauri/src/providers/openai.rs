@@ -0,0 +1,437 @@
+use super::source::RealFs;
+use super::{
+    count_recently_modified, DailyUsage, Diagnostics, JsonlLines, ModelUsage, Provider, ProviderError, Session, UsageStats,
+};
+use crate::pricing;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// OpenAI Codex CLI provider, reading session logs under ~/.codex/sessions/.
+pub struct OpenAiProvider {
+    config_dir: PathBuf,
+    /// How recently a session must have been written to count as active.
+    active_threshold: Duration,
+}
+
+// --- Deserialization types for Codex session JSONL ---
+
+/// One line of a Codex rollout file. Current CLI versions wrap events in
+/// `{ "type", "payload" }`; older logs carry a chat completion `usage` and
+/// `model` directly on the line.
+#[derive(Debug, Deserialize)]
+struct CodexLine {
+    #[serde(default)]
+    timestamp: Option<String>,
+    #[serde(default)]
+    payload: Option<CodexPayload>,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    usage: Option<ChatUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CodexPayload {
+    #[serde(default, rename = "type")]
+    payload_type: Option<String>,
+    /// Session id, on the `session_meta` line.
+    #[serde(default)]
+    id: Option<String>,
+    /// Working directory, on `session_meta` and `turn_context` lines.
+    #[serde(default)]
+    cwd: Option<String>,
+    /// Model, on `turn_context` lines.
+    #[serde(default)]
+    model: Option<String>,
+    /// Token totals, on `token_count` events.
+    #[serde(default)]
+    info: Option<TokenCountInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenCountInfo {
+    /// Running totals for the whole session.
+    #[serde(default)]
+    total_token_usage: Option<CodexTokenUsage>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+struct CodexTokenUsage {
+    #[serde(default)]
+    input_tokens: u64,
+    /// Part of `input_tokens` served from the prompt cache.
+    #[serde(default)]
+    cached_input_tokens: u64,
+    #[serde(default)]
+    output_tokens: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatUsage {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+    #[serde(default)]
+    prompt_tokens_details: Option<PromptTokensDetails>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PromptTokensDetails {
+    #[serde(default)]
+    cached_tokens: u64,
+}
+
+/// A parsed session together with its token split, which `Session` only
+/// carries as a single total.
+struct OpenAiSession {
+    session: Session,
+    input_tokens: u64,
+    output_tokens: u64,
+    cached_tokens: u64,
+}
+
+impl OpenAiProvider {
+    pub fn new(config_dir: PathBuf, active_threshold: Duration) -> Self {
+        Self {
+            config_dir,
+            active_threshold,
+        }
+    }
+
+    /// Determine the Codex config directory.
+    /// Uses CODEX_HOME env var if set, otherwise the provided config_dir.
+    fn effective_dir(&self) -> PathBuf {
+        std::env::var("CODEX_HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| self.config_dir.clone())
+    }
+
+    /// Find all session JSONL files under sessions/YYYY/MM/DD/
+    fn find_session_files(&self) -> Vec<PathBuf> {
+        let base = self.effective_dir().join("sessions");
+        if !base.exists() {
+            return Vec::new();
+        }
+
+        // Escape the directory so glob metacharacters in it (e.g. `[`) match literally
+        let pattern = PathBuf::from(glob::Pattern::escape(&base.to_string_lossy()))
+            .join("**")
+            .join("*.jsonl")
+            .to_string_lossy()
+            .to_string();
+
+        glob::glob(&pattern)
+            .map(|paths| paths.filter_map(|p| p.ok()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Parse a session JSONL file.
+    fn parse_session_file(&self, path: &Path) -> Option<OpenAiSession> {
+        let entries = JsonlLines::<CodexLine>::open(&RealFs, path)
+            .map_err(|e| tracing::warn!("Failed to read {}: {}", path.display(), e))
+            .ok()?;
+
+        // Running totals from the latest `token_count` event
+        let mut running = CodexTokenUsage::default();
+        // Sums of per-request chat completion usage
+        let mut summed = CodexTokenUsage::default();
+        let mut message_count: u32 = 0;
+        let mut session_id = String::new();
+        let mut cwd = String::new();
+        let mut last_model = String::new();
+        let mut last_timestamp = String::new();
+
+        for entry in entries {
+            if let Some(ts) = entry.timestamp {
+                last_timestamp = ts;
+            }
+            if let Some(model) = entry.model {
+                last_model = model;
+            }
+
+            if let Some(usage) = entry.usage {
+                let cached = usage
+                    .prompt_tokens_details
+                    .map(|d| d.cached_tokens)
+                    .unwrap_or(0);
+                summed.input_tokens += usage.prompt_tokens;
+                summed.cached_input_tokens += cached;
+                summed.output_tokens += usage.completion_tokens;
+                message_count += 1;
+            }
+
+            if let Some(payload) = entry.payload {
+                if payload.payload_type.as_deref() == Some("session_meta") {
+                    if let Some(id) = payload.id {
+                        session_id = id;
+                    }
+                }
+                if let Some(dir) = payload.cwd {
+                    cwd = dir;
+                }
+                if let Some(model) = payload.model {
+                    last_model = model;
+                }
+                if let Some(total) = payload.info.and_then(|i| i.total_token_usage) {
+                    running = total;
+                    message_count += 1;
+                }
+            }
+        }
+
+        if message_count == 0 {
+            return None;
+        }
+
+        let input_total = running.input_tokens + summed.input_tokens;
+        let cached_tokens = running.cached_input_tokens + summed.cached_input_tokens;
+        // Cached tokens are part of the prompt count; report them separately
+        let input_tokens = input_total.saturating_sub(cached_tokens);
+        let output_tokens = running.output_tokens + summed.output_tokens;
+
+        if session_id.is_empty() {
+            session_id = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| "unknown".to_string());
+        }
+
+        let project = cwd
+            .rsplit(['/', '\\'])
+            .find(|s| !s.is_empty())
+            .unwrap_or("unknown")
+            .to_string();
+
+        let is_active = fs::metadata(path)
+            .and_then(|m| m.modified())
+            .map(|modified| {
+                SystemTime::now()
+                    .duration_since(modified)
+                    .unwrap_or(Duration::from_secs(u64::MAX))
+                    < self.active_threshold
+            })
+            .unwrap_or(false);
+
+        let model = if last_model.is_empty() {
+            "openai-unknown".to_string()
+        } else {
+            last_model
+        };
+
+        let session = Session {
+            id: session_id,
+            provider: "OpenAI".to_string(),
+            profile_id: String::new(),
+            project,
+            project_path: cwd,
+            project_dir_encoded: String::new(),
+            cost_usd: Self::estimate_cost(&model, input_tokens, output_tokens, cached_tokens),
+            model,
+            tokens_used: input_tokens + output_tokens + cached_tokens,
+            last_active: last_timestamp,
+            is_active,
+            message_count,
+        };
+
+        Some(OpenAiSession {
+            session,
+            input_tokens,
+            output_tokens,
+            cached_tokens,
+        })
+    }
+
+    fn all_sessions(&self) -> Vec<OpenAiSession> {
+        self.find_session_files()
+            .iter()
+            .filter_map(|path| self.parse_session_file(path))
+            .collect()
+    }
+
+    /// Estimate cost for OpenAI models using the pricing table.
+    pub(crate) fn estimate_cost(model: &str, input_tokens: u64, output_tokens: u64, cached_tokens: u64) -> f64 {
+        let cost = pricing::table()
+            .openai_rates(model)
+            .cost(input_tokens, output_tokens, cached_tokens, 0);
+
+        (cost * 100.0).round() / 100.0
+    }
+}
+
+impl Provider for OpenAiProvider {
+    fn name(&self) -> &str {
+        "OpenAI"
+    }
+
+    fn provider_type(&self) -> &str {
+        "openai"
+    }
+
+    fn config_dir(&self) -> &PathBuf {
+        &self.config_dir
+    }
+
+    fn watch_dirs(&self) -> Vec<PathBuf> {
+        vec![self.effective_dir().join("sessions")]
+    }
+
+    fn probe(&self) -> Result<String, ProviderError> {
+        let dir = self.effective_dir();
+        if !dir.is_dir() {
+            return Err(ProviderError::NotConfigured(format!(
+                "Config directory not found: {}",
+                dir.display()
+            )));
+        }
+        let count = self.find_session_files().len();
+        if count == 0 {
+            return Err(ProviderError::NotConfigured(format!(
+                "No session files under {}",
+                dir.join("sessions").display()
+            )));
+        }
+        Ok(format!("Found {} session files", count))
+    }
+
+    fn health_check(&self) -> Result<(), ProviderError> {
+        self.probe()?;
+        if self.find_session_files().iter().any(|f| self.parse_session_file(f).is_some()) {
+            Ok(())
+        } else {
+            Err(ProviderError::Parse(format!(
+                "No readable session file under {}",
+                self.effective_dir().join("sessions").display()
+            )))
+        }
+    }
+
+    fn diagnostics(&self) -> Diagnostics {
+        let dir = self.effective_dir();
+        Diagnostics {
+            config_dir: dir.display().to_string(),
+            config_dir_exists: dir.is_dir(),
+            session_files: Some(self.find_session_files().len() as u32),
+            ..Default::default()
+        }
+    }
+
+    fn get_usage_stats(&self) -> Result<UsageStats, ProviderError> {
+        let sessions = self.all_sessions();
+
+        let mut total_input: u64 = 0;
+        let mut total_output: u64 = 0;
+        let mut total_cached: u64 = 0;
+        let mut total_messages: u32 = 0;
+        let mut model_map: HashMap<String, (u64, u64, u64)> = HashMap::new();
+
+        for s in &sessions {
+            total_input += s.input_tokens;
+            total_output += s.output_tokens;
+            total_cached += s.cached_tokens;
+            total_messages += s.session.message_count;
+
+            let entry = model_map
+                .entry(s.session.model.clone())
+                .or_insert((0, 0, 0));
+            entry.0 += s.input_tokens;
+            entry.1 += s.output_tokens;
+            entry.2 += s.cached_tokens;
+        }
+
+        let mut model_breakdown: HashMap<String, ModelUsage> = HashMap::new();
+        let mut total_cost: f64 = 0.0;
+
+        for (model_name, (input, output, cached)) in &model_map {
+            let cost = Self::estimate_cost(model_name, *input, *output, *cached);
+            total_cost += cost;
+            model_breakdown.insert(
+                model_name.clone(),
+                ModelUsage {
+                    model: model_name.clone(),
+                    input_tokens: *input,
+                    output_tokens: *output,
+                    cache_read_tokens: *cached,
+                    cost_usd: cost,
+                    ..Default::default()
+                },
+            );
+        }
+
+        let mut stats = UsageStats {
+            provider: "OpenAI".to_string(),
+            total_input_tokens: total_input,
+            total_output_tokens: total_output,
+            total_cache_read_tokens: total_cached,
+            total_sessions: sessions.len() as u32,
+            total_messages,
+            estimated_cost_usd: (total_cost * 100.0).round() / 100.0,
+            model_breakdown,
+            ..Default::default()
+        };
+        stats.compute_cache_hit_ratios();
+        Ok(stats)
+    }
+
+    fn get_active_sessions(&self) -> Result<Vec<Session>, ProviderError> {
+        Ok(self
+            .all_sessions()
+            .into_iter()
+            .map(|s| s.session)
+            .filter(|s| s.is_active)
+            .collect())
+    }
+
+    fn active_session_count(&self) -> Result<u32, ProviderError> {
+        Ok(count_recently_modified(&RealFs, &self.find_session_files(), self.active_threshold))
+    }
+
+    fn get_daily_usage(&self, days: u32) -> Result<Vec<DailyUsage>, ProviderError> {
+        // Group sessions by date (from last_active timestamp)
+        let mut date_map: HashMap<String, DailyUsage> = HashMap::new();
+
+        for s in self.all_sessions() {
+            let date = match s.session.last_active.get(..10) {
+                Some(d) => d.to_string(),
+                None => continue,
+            };
+
+            let entry = date_map.entry(date.clone()).or_insert_with(|| DailyUsage {
+                date,
+                ..Default::default()
+            });
+            entry.input_tokens += s.input_tokens;
+            entry.output_tokens += s.output_tokens;
+            entry.cache_read_tokens += s.cached_tokens;
+            entry.sessions += 1;
+            entry.messages += s.session.message_count;
+        }
+
+        let mut daily: Vec<DailyUsage> = date_map.into_values().collect();
+
+        daily.sort_by(|a, b| b.date.cmp(&a.date));
+        daily.truncate(days as usize);
+
+        Ok(daily)
+    }
+
+    fn get_session_history(&self, limit: u32) -> Result<Vec<Session>, ProviderError> {
+        let mut sessions: Vec<Session> = self.all_sessions().into_iter().map(|s| s.session).collect();
+
+        // Sort by last_active descending
+        sessions.sort_by(|a, b| b.last_active.cmp(&a.last_active));
+        sessions.truncate(limit as usize);
+
+        Ok(sessions)
+    }
+
+    fn get_known_models(&self) -> Result<Vec<String>, ProviderError> {
+        let mut models: Vec<String> = self.all_sessions().into_iter().map(|s| s.session.model).collect();
+        models.sort();
+        models.dedup();
+        Ok(models)
+    }
+}
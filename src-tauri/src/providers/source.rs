@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// Where file-based providers read their session data from. The app uses
+/// [`RealFs`]; [`MemoryFs`] serves in-memory fixtures so the aggregation
+/// logic can be exercised without a real config directory.
+pub trait FileSource: Send + Sync {
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+    /// A buffered reader over the file, for reading it a line at a time.
+    fn open(&self, path: &Path) -> io::Result<Box<dyn BufRead + Send>>;
+    fn modified(&self, path: &Path) -> io::Result<SystemTime>;
+    fn exists(&self, path: &Path) -> bool;
+    /// `path` with symlinks resolved.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+    /// Files matching a glob `pattern`, in path order. An invalid pattern
+    /// matches nothing.
+    fn glob(&self, pattern: &str) -> Vec<PathBuf>;
+}
+
+/// The local filesystem.
+pub struct RealFs;
+
+impl FileSource for RealFs {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn BufRead + Send>> {
+        Ok(Box::new(BufReader::new(File::open(path)?)))
+    }
+
+    fn modified(&self, path: &Path) -> io::Result<SystemTime> {
+        std::fs::metadata(path)?.modified()
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        std::fs::canonicalize(path)
+    }
+
+    fn glob(&self, pattern: &str) -> Vec<PathBuf> {
+        glob::glob(pattern)
+            .map(|paths| paths.filter_map(|p| p.ok()).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Files held in memory, keyed by path. A directory exists if any file is
+/// below it.
+#[allow(dead_code)] // Only constructed by provider tests against fixtures
+#[derive(Default)]
+pub struct MemoryFs {
+    files: Mutex<BTreeMap<PathBuf, (String, SystemTime)>>,
+}
+
+#[allow(dead_code)]
+impl MemoryFs {
+    /// Add or replace a file, modified now.
+    pub fn insert(&self, path: impl Into<PathBuf>, contents: impl Into<String>) {
+        self.insert_modified(path, contents, SystemTime::now());
+    }
+
+    /// Add or replace a file with the given modification time.
+    pub fn insert_modified(&self, path: impl Into<PathBuf>, contents: impl Into<String>, modified: SystemTime) {
+        if let Ok(mut files) = self.files.lock() {
+            files.insert(path.into(), (contents.into(), modified));
+        }
+    }
+
+    fn get(&self, path: &Path) -> io::Result<(String, SystemTime)> {
+        self.files
+            .lock()
+            .ok()
+            .and_then(|files| files.get(path).cloned())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} not found", path.display())))
+    }
+}
+
+impl FileSource for MemoryFs {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        Ok(self.get(path)?.0)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn BufRead + Send>> {
+        Ok(Box::new(Cursor::new(self.get(path)?.0.into_bytes())))
+    }
+
+    fn modified(&self, path: &Path) -> io::Result<SystemTime> {
+        Ok(self.get(path)?.1)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.files
+            .lock()
+            .map(|files| files.keys().any(|p| p.starts_with(path)))
+            .unwrap_or(false)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        Ok(path.to_path_buf())
+    }
+
+    fn glob(&self, pattern: &str) -> Vec<PathBuf> {
+        let Ok(pattern) = glob::Pattern::new(pattern) else {
+            return Vec::new();
+        };
+        self.files
+            .lock()
+            .map(|files| files.keys().filter(|p| pattern.matches_path(p)).cloned().collect())
+            .unwrap_or_default()
+    }
+}
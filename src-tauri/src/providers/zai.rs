@@ -1,14 +1,172 @@
-use super::{DailyUsage, ModelUsage, Provider, Session, UsageStats};
-use std::collections::HashMap;
-use std::path::PathBuf;
+use super::{
+    rank_by_cost, DailyModelUsage, DailyUsage, Diagnostics, FileStatus, ModelUsage, Provider, ProviderError,
+    Session, UsageStats,
+};
+use crate::pricing;
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
+use rusqlite::types::ValueRef;
+use rusqlite::Connection;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// How long a query waits on a lock held by the z.ai CLI before failing.
+const BUSY_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Open connection together with the db file mtime it was opened at.
+struct CachedConnection {
+    conn: Connection,
+    modified: SystemTime,
+    columns: MessageColumns,
+}
+
+/// Column names in the `messages` table, which vary between z.ai CLI versions.
+#[derive(Debug, Clone, Copy)]
+struct MessageColumns {
+    input_tokens: &'static str,
+    output_tokens: &'static str,
+    /// `NULL` when the table has no model column.
+    model: &'static str,
+    session_id: &'static str,
+    /// None when messages carry no timestamp.
+    created_at: Option<&'static str>,
+}
+
+/// Drop a row that failed to decode, logging why.
+fn skip_bad_row<T>(row: rusqlite::Result<T>) -> Option<T> {
+    row.map_err(|e| tracing::debug!("Skipping z.ai row: {}", e)).ok()
+}
+
+impl MessageColumns {
+    /// Read the `messages` schema and pick the first known alias of each column.
+    fn detect(conn: &Connection) -> Result<Self, ProviderError> {
+        let mut stmt = conn
+            .prepare("PRAGMA table_info(messages)")
+            .map_err(|e| ProviderError::Parse(format!("Failed to read z.ai messages schema: {}", e)))?;
+        let names: HashSet<String> = stmt
+            .query_map([], |row| row.get::<_, String>(1))
+            .map_err(|e| ProviderError::Parse(format!("Failed to read z.ai messages schema: {}", e)))?
+            .filter_map(skip_bad_row)
+            .collect();
+        if names.is_empty() {
+            return Err(ProviderError::Parse(
+                "z.ai database has no messages table".to_string(),
+            ));
+        }
+
+        let pick = |aliases: &[&'static str]| aliases.iter().copied().find(|a| names.contains(*a));
+        let require = |aliases: &[&'static str]| {
+            pick(aliases).ok_or_else(|| {
+                ProviderError::Parse(format!(
+                    "z.ai messages table has no {} column",
+                    aliases.join(" or ")
+                ))
+            })
+        };
+
+        Ok(Self {
+            input_tokens: require(&["input_tokens", "prompt_tokens"])?,
+            output_tokens: require(&["output_tokens", "completion_tokens"])?,
+            model: pick(&["model", "model_name"]).unwrap_or("NULL"),
+            session_id: require(&["session_id", "conversation_id"])?,
+            created_at: pick(&["created_at", "timestamp"]),
+        })
+    }
+
+    /// Per-session totals as a subquery with columns session_id, model,
+    /// total_tokens, msg_count, input_tokens and output_tokens.
+    fn session_totals(&self) -> String {
+        format!(
+            "SELECT {sid} as session_id, \
+                    MAX(COALESCE({model}, 'unknown')) as model, \
+                    SUM(COALESCE({input}, 0) + COALESCE({output}, 0)) as total_tokens, \
+                    COUNT(*) as msg_count, \
+                    SUM(COALESCE({input}, 0)) as input_tokens, \
+                    SUM(COALESCE({output}, 0)) as output_tokens \
+             FROM messages GROUP BY {sid}",
+            sid = self.session_id,
+            model = self.model,
+            input = self.input_tokens,
+            output = self.output_tokens,
+        )
+    }
+}
 
 pub struct ZaiProvider {
     config_dir: PathBuf,
+    conn: Mutex<Option<CachedConnection>>,
+    /// How recently a session must have been updated to count as active.
+    active_threshold: Duration,
+}
+
+/// How `messages.created_at` is stored. Varies between z.ai CLI versions.
+#[derive(Debug, Clone, Copy)]
+enum CreatedAtFormat {
+    /// ISO 8601 / SQLite datetime text; UTC unless an offset is included.
+    Iso,
+    EpochMillis,
+    EpochSeconds,
+}
+
+impl CreatedAtFormat {
+    /// Sample one row of `column` to determine the storage format.
+    fn detect(conn: &Connection, column: &str) -> Option<Self> {
+        conn.query_row(
+            &format!("SELECT {c} FROM messages WHERE {c} IS NOT NULL LIMIT 1", c = column),
+            [],
+            |row| {
+                Ok(match row.get_ref(0)? {
+                    ValueRef::Integer(n) if n > 1_000_000_000_000 => Self::EpochMillis,
+                    ValueRef::Integer(_) => Self::EpochSeconds,
+                    ValueRef::Real(n) if n > 1_000_000_000_000.0 => Self::EpochMillis,
+                    ValueRef::Real(_) => Self::EpochSeconds,
+                    _ => Self::Iso,
+                })
+            },
+        )
+        .ok()
+    }
+
+    /// SQL expression normalizing `column` to a UTC `datetime()` value.
+    fn utc_datetime(self, column: &str) -> String {
+        match self {
+            Self::Iso => format!("datetime({})", column),
+            Self::EpochMillis => format!("datetime({} / 1000, 'unixepoch')", column),
+            Self::EpochSeconds => format!("datetime({}, 'unixepoch')", column),
+        }
+    }
+}
+
+/// Parse a `created_at` value in any of the known storage formats.
+fn parse_timestamp(value: ValueRef) -> Option<DateTime<Local>> {
+    let utc = match value {
+        ValueRef::Integer(n) if n > 1_000_000_000_000 => DateTime::from_timestamp_millis(n)?,
+        ValueRef::Integer(n) => DateTime::from_timestamp(n, 0)?,
+        ValueRef::Real(n) if n > 1_000_000_000_000.0 => DateTime::from_timestamp_millis(n as i64)?,
+        ValueRef::Real(n) => DateTime::from_timestamp(n as i64, 0)?,
+        ValueRef::Text(bytes) => {
+            let text = std::str::from_utf8(bytes).ok()?.trim();
+            DateTime::parse_from_rfc3339(text)
+                .map(|t| t.with_timezone(&Utc))
+                .or_else(|_| {
+                    NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f").map(|t| t.and_utc())
+                })
+                .ok()?
+        }
+        _ => return None,
+    };
+    Some(utc.with_timezone(&Local))
 }
 
 impl ZaiProvider {
-    pub fn new(config_dir: PathBuf) -> Self {
-        Self { config_dir }
+    pub fn new(config_dir: PathBuf, active_threshold: Duration) -> Self {
+        Self {
+            config_dir,
+            conn: Mutex::new(None),
+            active_threshold,
+        }
     }
 
     /// Determine the database path.
@@ -20,78 +178,133 @@ impl ZaiProvider {
     }
 
     /// Open a read-only connection to the SQLite database.
-    /// Returns None if the database file does not exist.
-    fn open_db(&self) -> Option<rusqlite::Connection> {
-        let path = self.db_path();
-        if !path.exists() {
-            return None;
-        }
-        rusqlite::Connection::open_with_flags(
-            &path,
+    /// Falls back to an immutable snapshot when the live database can't be
+    /// read, e.g. while the z.ai CLI holds a lock or the WAL index (`-shm`)
+    /// can't be created next to it.
+    fn open_db(path: &PathBuf) -> Option<Connection> {
+        let live = Connection::open_with_flags(
+            path,
             rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX,
         )
-        .ok()
+        .and_then(|conn| {
+            conn.busy_timeout(BUSY_TIMEOUT)?;
+            // Opening is lazy; touch the schema so lock and WAL errors show up here
+            conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(()))?;
+            Ok(conn)
+        });
+
+        match live {
+            Ok(conn) => Some(conn),
+            Err(e) => {
+                tracing::warn!("Failed to open {}: {}; reading it as immutable", path.display(), e);
+                Self::open_immutable(path)
+            }
+        }
     }
 
-    /// Estimate cost for z.ai / GLM models (per million tokens).
-    fn estimate_cost(_model: &str, input_tokens: u64, output_tokens: u64) -> f64 {
-        let input_rate = 1.0;
-        let output_rate = 4.0;
+    /// Open the database with `immutable=1`, which skips locking and the WAL
+    /// entirely. Writes not yet checkpointed into the main file are missed.
+    fn open_immutable(path: &Path) -> Option<Connection> {
+        // URI paths use forward slashes and need `%`, `?` and `#` escaped
+        let mut file = path
+            .to_string_lossy()
+            .replace('%', "%25")
+            .replace('?', "%3f")
+            .replace('#', "%23")
+            .replace('\\', "/");
+        if !file.starts_with('/') {
+            // Windows drive paths become file:/C:/...
+            file.insert(0, '/');
+        }
+        let uri = format!("file:{}?immutable=1", file);
+        Connection::open_with_flags(
+            &uri,
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY
+                | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX
+                | rusqlite::OpenFlags::SQLITE_OPEN_URI,
+        )
+        .map_err(|e| tracing::warn!("Failed to open {} as immutable: {}", path.display(), e))
+        .ok()
+    }
 
-        let cost =
-            (input_tokens as f64 * input_rate + output_tokens as f64 * output_rate) / 1_000_000.0;
+    /// Run `f` against the cached connection, reopening it when the database
+    /// file's mtime changed or the previous query failed. A failed query is
+    /// retried once against an immutable snapshot.
+    /// Returns Ok(None) if the database file does not exist, cannot be opened
+    /// or cannot be queried, so callers degrade to empty results; only a
+    /// `messages` table without the columns we need is an error.
+    fn with_db<T>(
+        &self,
+        f: impl Fn(&Connection, &MessageColumns) -> Result<T, String>,
+    ) -> Result<Option<T>, ProviderError> {
+        let path = self.db_path();
+        let modified = match fs::metadata(&path).and_then(|m| m.modified()) {
+            Ok(m) => m,
+            Err(_) => return Ok(None),
+        };
 
-        (cost * 100.0).round() / 100.0
-    }
-}
+        let mut cached = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let stale = cached.as_ref().map(|c| c.modified != modified).unwrap_or(true);
+        if stale {
+            let conn = match Self::open_db(&path) {
+                Some(c) => c,
+                None => return Ok(None),
+            };
+            let columns = MessageColumns::detect(&conn)?;
+            *cached = Some(CachedConnection {
+                conn,
+                modified,
+                columns,
+            });
+        }
+        let Some(entry) = cached.as_ref() else {
+            return Ok(None);
+        };
 
-impl Provider for ZaiProvider {
-    fn name(&self) -> &str {
-        "z.ai"
+        match f(&entry.conn, &entry.columns) {
+            Ok(result) => Ok(Some(result)),
+            Err(e) => {
+                tracing::warn!("z.ai database query failed: {}", e);
+                *cached = None;
+                let Some(conn) = Self::open_immutable(&path) else {
+                    return Ok(None);
+                };
+                let columns = MessageColumns::detect(&conn)?;
+                Ok(f(&conn, &columns)
+                    .map_err(|e| tracing::warn!("z.ai database query failed on snapshot: {}", e))
+                    .ok())
+            }
+        }
     }
 
-    fn provider_type(&self) -> &str {
-        "zai"
-    }
+    /// Estimate cost for z.ai / GLM models (per million tokens).
+    pub(crate) fn estimate_cost(model: &str, input_tokens: u64, output_tokens: u64) -> f64 {
+        let cost = pricing::table().zai_rates(model).cost(input_tokens, output_tokens, 0, 0);
 
-    fn config_dir(&self) -> &PathBuf {
-        &self.config_dir
+        (cost * 100.0).round() / 100.0
     }
 
-    fn get_usage_stats(&self) -> Result<UsageStats, String> {
-        let conn = match self.open_db() {
-            Some(c) => c,
-            None => {
-                return Ok(UsageStats {
-                    provider: "z.ai".to_string(),
-                    total_input_tokens: 0,
-                    total_output_tokens: 0,
-                    total_cache_read_tokens: 0,
-                    total_cache_write_tokens: 0,
-                    total_sessions: 0,
-                    total_messages: 0,
-                    estimated_cost_usd: 0.0,
-                    model_breakdown: HashMap::new(),
-                });
-            }
-        };
-
+    /// Aggregate token usage per model.
+    fn query_usage_stats(conn: &Connection, cols: &MessageColumns) -> Result<UsageStats, String> {
         // Count total sessions
         let total_sessions: u32 = conn
             .query_row("SELECT COUNT(*) FROM sessions", [], |row| row.get(0))
             .unwrap_or(0);
 
         // Aggregate token usage from messages table
-        // Expected columns: input_tokens, output_tokens, model
+        let sql = format!(
+            "SELECT COALESCE({model}, 'unknown'), \
+             COALESCE(SUM({input}), 0), \
+             COALESCE(SUM({output}), 0), \
+             COUNT(*) \
+             FROM messages \
+             GROUP BY COALESCE({model}, 'unknown')",
+            model = cols.model,
+            input = cols.input_tokens,
+            output = cols.output_tokens,
+        );
         let mut stmt = conn
-            .prepare(
-                "SELECT COALESCE(model, 'unknown'), \
-                 COALESCE(SUM(input_tokens), 0), \
-                 COALESCE(SUM(output_tokens), 0), \
-                 COUNT(*) \
-                 FROM messages \
-                 GROUP BY COALESCE(model, 'unknown')",
-            )
+            .prepare_cached(&sql)
             .map_err(|e| format!("Failed to prepare query: {}", e))?;
 
         let mut total_input: u64 = 0;
@@ -111,27 +324,26 @@ impl Provider for ZaiProvider {
             })
             .map_err(|e| format!("Failed to query messages: {}", e))?;
 
-        for row in rows {
-            if let Ok((model, input, output, count)) = row {
-                let cost = Self::estimate_cost(&model, input, output);
-
-                total_input += input;
-                total_output += output;
-                total_messages += count;
-                total_cost += cost;
-
-                model_breakdown.insert(
-                    model.clone(),
-                    ModelUsage {
-                        model,
-                        input_tokens: input,
-                        output_tokens: output,
-                        cache_read_tokens: 0,
-                        cache_write_tokens: 0,
-                        cost_usd: cost,
-                    },
-                );
-            }
+        for (model, input, output, count) in rows.flatten() {
+            let cost = Self::estimate_cost(&model, input, output);
+
+            total_input += input;
+            total_output += output;
+            total_messages += count;
+            total_cost += cost;
+
+            model_breakdown.insert(
+                model.clone(),
+                ModelUsage {
+                    model,
+                    input_tokens: input,
+                    output_tokens: output,
+                    cache_read_tokens: 0,
+                    cache_write_tokens: 0,
+                    cost_usd: cost,
+                    ..Default::default()
+                },
+            );
         }
 
         Ok(UsageStats {
@@ -144,78 +356,103 @@ impl Provider for ZaiProvider {
             total_messages,
             estimated_cost_usd: (total_cost * 100.0).round() / 100.0,
             model_breakdown,
+            ..Default::default()
         })
     }
 
-    fn get_active_sessions(&self) -> Result<Vec<Session>, String> {
-        let conn = match self.open_db() {
-            Some(c) => c,
-            None => return Ok(Vec::new()),
-        };
-
-        // Active sessions: updated in the last 30 minutes
-        let mut stmt = conn
-            .prepare(
-                "SELECT s.id, s.name, s.working_directory, \
+    /// Sessions updated within `threshold`.
+    fn query_active_sessions(
+        conn: &Connection,
+        cols: &MessageColumns,
+        threshold: Duration,
+    ) -> Result<Vec<Session>, String> {
+        let sql = format!(
+            "SELECT s.id, s.name, s.working_directory, \
                  COALESCE(s.updated_at, s.created_at, '') as last_active, \
                  COALESCE(m.model, 'unknown') as model, \
                  COALESCE(m.total_tokens, 0) as tokens_used, \
-                 COALESCE(m.msg_count, 0) as msg_count \
+                 COALESCE(m.msg_count, 0) as msg_count, \
+                 COALESCE(m.input_tokens, 0) as input_tokens, \
+                 COALESCE(m.output_tokens, 0) as output_tokens \
                  FROM sessions s \
                  LEFT JOIN ( \
-                     SELECT session_id, \
-                            MAX(COALESCE(model, 'unknown')) as model, \
-                            SUM(COALESCE(input_tokens, 0) + COALESCE(output_tokens, 0)) as total_tokens, \
-                            COUNT(*) as msg_count \
-                     FROM messages GROUP BY session_id \
+                     {totals} \
                  ) m ON s.id = m.session_id \
-                 WHERE s.updated_at >= datetime('now', '-30 minutes') \
+                 WHERE s.updated_at >= datetime('now', ?1) \
                  ORDER BY last_active DESC",
-            )
+            totals = cols.session_totals()
+        );
+        let mut stmt = conn
+            .prepare_cached(&sql)
             .map_err(|e| format!("Failed to prepare query: {}", e))?;
 
+        let offset = format!("-{} seconds", threshold.as_secs());
+
         let sessions = stmt
-            .query_map([], |row| {
+            .query_map([&offset], |row| {
+                let model = row.get::<_, String>(4).unwrap_or_else(|_| "unknown".to_string());
+                let cost_usd = Self::estimate_cost(
+                    &model,
+                    row.get::<_, u64>(7).unwrap_or(0),
+                    row.get::<_, u64>(8).unwrap_or(0),
+                );
+
                 Ok(Session {
                     id: row.get::<_, String>(0)?,
+                    provider: "z.ai".to_string(),
+                    profile_id: String::new(),
                     project: row.get::<_, String>(2).unwrap_or_default(),
-                    model: row.get::<_, String>(4).unwrap_or_else(|_| "unknown".to_string()),
+                    project_path: row.get::<_, String>(2).unwrap_or_default(),
+                    project_dir_encoded: String::new(),
+                    model,
                     tokens_used: row.get::<_, u64>(5).unwrap_or(0),
                     last_active: row.get::<_, String>(3).unwrap_or_default(),
                     is_active: true,
                     message_count: row.get::<_, u32>(6).unwrap_or(0),
+                    cost_usd,
                 })
             })
             .map_err(|e| format!("Failed to query sessions: {}", e))?
-            .filter_map(|r| r.ok())
+            .filter_map(skip_bad_row)
             .collect();
 
         Ok(sessions)
     }
 
-    fn get_daily_usage(&self, days: u32) -> Result<Vec<DailyUsage>, String> {
-        let conn = match self.open_db() {
-            Some(c) => c,
+    /// Per-day token totals for the last `days` days.
+    fn query_daily_usage(conn: &Connection, cols: &MessageColumns, days: u32) -> Result<Vec<DailyUsage>, String> {
+        let Some(created_at) = cols.created_at else {
+            return Ok(Vec::new());
+        };
+        let format = match CreatedAtFormat::detect(conn, created_at) {
+            Some(f) => f,
+            // No timestamped messages yet
             None => return Ok(Vec::new()),
         };
-
+        let timestamp = format.utc_datetime(&format!("m.{}", created_at));
+
+        let sql = format!(
+            "SELECT DATE({ts}, 'localtime') as date, \
+             COALESCE(SUM(m.{input}), 0), \
+             COALESCE(SUM(m.{output}), 0), \
+             COUNT(DISTINCT m.{sid}), \
+             COUNT(*) \
+             FROM messages m \
+             WHERE {ts} >= datetime('now', ?1) \
+             GROUP BY date \
+             ORDER BY date DESC",
+            ts = timestamp,
+            input = cols.input_tokens,
+            output = cols.output_tokens,
+            sid = cols.session_id,
+        );
         let mut stmt = conn
-            .prepare(
-                "SELECT DATE(m.created_at) as date, \
-                 COALESCE(SUM(m.input_tokens), 0), \
-                 COALESCE(SUM(m.output_tokens), 0), \
-                 COUNT(DISTINCT m.session_id), \
-                 COUNT(*) \
-                 FROM messages m \
-                 WHERE m.created_at >= datetime('now', ?1) \
-                 GROUP BY DATE(m.created_at) \
-                 ORDER BY date DESC",
-            )
+            .prepare_cached(&sql)
             .map_err(|e| format!("Failed to prepare query: {}", e))?;
 
         let offset = format!("-{} days", days);
 
-        let daily = stmt
+        let daily: Vec<DailyUsage> = stmt
             .query_map([&offset], |row| {
                 Ok(DailyUsage {
                     date: row.get::<_, String>(0)?,
@@ -223,64 +460,519 @@ impl Provider for ZaiProvider {
                     output_tokens: row.get::<_, u64>(2)?,
                     sessions: row.get::<_, u32>(3)?,
                     messages: row.get::<_, u32>(4)?,
+                    ..Default::default()
                 })
             })
             .map_err(|e| format!("Failed to query daily usage: {}", e))?
-            .filter_map(|r| r.ok())
+            .filter_map(skip_bad_row)
+            .collect();
+
+        if daily.is_empty() {
+            // SQLite couldn't interpret the timestamps; bucket in Rust instead
+            return Self::bucket_daily_usage(conn, cols, created_at, days);
+        }
+
+        Ok(daily)
+    }
+
+    /// Fallback for `query_daily_usage`: read every message and bucket by
+    /// local date, parsing timestamps with chrono.
+    fn bucket_daily_usage(
+        conn: &Connection,
+        cols: &MessageColumns,
+        created_at: &str,
+        days: u32,
+    ) -> Result<Vec<DailyUsage>, String> {
+        let sql = format!(
+            "SELECT {created_at}, COALESCE({input}, 0), COALESCE({output}, 0), {sid} \
+             FROM messages",
+            input = cols.input_tokens,
+            output = cols.output_tokens,
+            sid = cols.session_id,
+        );
+        let mut stmt = conn
+            .prepare_cached(&sql)
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let cutoff = chrono::Local::now() - chrono::Duration::days(days as i64);
+        // date -> (input, output, session ids, messages)
+        let mut buckets: HashMap<String, (u64, u64, HashSet<String>, u32)> = HashMap::new();
+
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    parse_timestamp(row.get_ref(0)?),
+                    row.get::<_, u64>(1).unwrap_or(0),
+                    row.get::<_, u64>(2).unwrap_or(0),
+                    row.get::<_, String>(3).unwrap_or_default(),
+                ))
+            })
+            .map_err(|e| format!("Failed to query messages: {}", e))?;
+
+        for (created_at, input, output, session_id) in rows.flatten() {
+            let created_at = match created_at {
+                Some(t) if t >= cutoff => t,
+                _ => continue,
+            };
+            let entry = buckets
+                .entry(created_at.format("%Y-%m-%d").to_string())
+                .or_default();
+            entry.0 += input;
+            entry.1 += output;
+            entry.2.insert(session_id);
+            entry.3 += 1;
+        }
+
+        let mut daily: Vec<DailyUsage> = buckets
+            .into_iter()
+            .map(|(date, (input, output, sessions, messages))| DailyUsage {
+                date,
+                input_tokens: input,
+                output_tokens: output,
+                sessions: sessions.len() as u32,
+                messages,
+                ..Default::default()
+            })
             .collect();
+        daily.sort_by(|a, b| b.date.cmp(&a.date));
 
         Ok(daily)
     }
 
-    fn get_session_history(&self, limit: u32) -> Result<Vec<Session>, String> {
-        let conn = match self.open_db() {
-            Some(c) => c,
+    /// Per-model token totals by local date for the last `days` days, newest first.
+    fn query_daily_model_usage(
+        conn: &Connection,
+        cols: &MessageColumns,
+        days: u32,
+    ) -> Result<Vec<DailyModelUsage>, String> {
+        let Some(created_at) = cols.created_at else {
+            return Ok(Vec::new());
+        };
+        let format = match CreatedAtFormat::detect(conn, created_at) {
+            Some(f) => f,
             None => return Ok(Vec::new()),
         };
-
+        let timestamp = format.utc_datetime(&format!("m.{}", created_at));
+
+        let sql = format!(
+            "SELECT DATE({ts}, 'localtime') as date, \
+             COALESCE({model}, 'unknown') as model_name, \
+             COALESCE(SUM(m.{input}), 0), \
+             COALESCE(SUM(m.{output}), 0) \
+             FROM messages m \
+             WHERE {ts} >= datetime('now', ?1) \
+             GROUP BY date, model_name \
+             ORDER BY date DESC, model_name",
+            ts = timestamp,
+            model = if cols.model == "NULL" { "NULL".to_string() } else { format!("m.{}", cols.model) },
+            input = cols.input_tokens,
+            output = cols.output_tokens,
+        );
         let mut stmt = conn
-            .prepare(
-                "SELECT s.id, s.name, s.working_directory, \
+            .prepare_cached(&sql)
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let offset = format!("-{} days", days);
+
+        let usage = stmt
+            .query_map([&offset], |row| {
+                let model: String = row.get(1)?;
+                let input_tokens: u64 = row.get(2)?;
+                let output_tokens: u64 = row.get(3)?;
+                Ok(DailyModelUsage {
+                    date: row.get::<_, String>(0)?,
+                    cost_usd: Self::estimate_cost(&model, input_tokens, output_tokens),
+                    model,
+                    input_tokens,
+                    output_tokens,
+                    cache_read_tokens: 0,
+                    cache_write_tokens: 0,
+                })
+            })
+            .map_err(|e| format!("Failed to query daily model usage: {}", e))?
+            .filter_map(skip_bad_row)
+            .collect();
+
+        Ok(usage)
+    }
+
+    /// Most recently updated sessions, newest first.
+    fn query_session_history(
+        conn: &Connection,
+        cols: &MessageColumns,
+        limit: u32,
+        threshold: Duration,
+    ) -> Result<Vec<Session>, String> {
+        let sql = format!(
+            "SELECT s.id, s.name, s.working_directory, \
                  COALESCE(s.updated_at, s.created_at, '') as last_active, \
                  COALESCE(m.model, 'unknown') as model, \
                  COALESCE(m.total_tokens, 0) as tokens_used, \
-                 COALESCE(m.msg_count, 0) as msg_count \
+                 COALESCE(m.msg_count, 0) as msg_count, \
+                 COALESCE(m.input_tokens, 0) as input_tokens, \
+                 COALESCE(m.output_tokens, 0) as output_tokens \
                  FROM sessions s \
                  LEFT JOIN ( \
-                     SELECT session_id, \
-                            MAX(COALESCE(model, 'unknown')) as model, \
-                            SUM(COALESCE(input_tokens, 0) + COALESCE(output_tokens, 0)) as total_tokens, \
-                            COUNT(*) as msg_count \
-                     FROM messages GROUP BY session_id \
+                     {totals} \
                  ) m ON s.id = m.session_id \
                  ORDER BY last_active DESC \
                  LIMIT ?1",
-            )
+            totals = cols.session_totals()
+        );
+        let mut stmt = conn
+            .prepare_cached(&sql)
             .map_err(|e| format!("Failed to prepare query: {}", e))?;
 
         let now_str = chrono::Utc::now().to_rfc3339();
-        let thirty_min_ago = (chrono::Utc::now() - chrono::Duration::minutes(30)).to_rfc3339();
+        let active_since = (chrono::Utc::now() - threshold).to_rfc3339();
 
         let sessions = stmt
             .query_map([limit], |row| {
                 let last_active: String = row.get::<_, String>(3).unwrap_or_default();
-                let is_active = last_active.as_str() >= thirty_min_ago.as_str()
+                let is_active = last_active.as_str() >= active_since.as_str()
                     && last_active.as_str() <= now_str.as_str();
 
+                let model = row.get::<_, String>(4).unwrap_or_else(|_| "unknown".to_string());
+                let cost_usd = Self::estimate_cost(
+                    &model,
+                    row.get::<_, u64>(7).unwrap_or(0),
+                    row.get::<_, u64>(8).unwrap_or(0),
+                );
+
                 Ok(Session {
                     id: row.get::<_, String>(0)?,
+                    provider: "z.ai".to_string(),
+                    profile_id: String::new(),
                     project: row.get::<_, String>(2).unwrap_or_default(),
-                    model: row.get::<_, String>(4).unwrap_or_else(|_| "unknown".to_string()),
+                    project_path: row.get::<_, String>(2).unwrap_or_default(),
+                    project_dir_encoded: String::new(),
+                    model,
                     tokens_used: row.get::<_, u64>(5).unwrap_or(0),
                     last_active,
                     is_active,
                     message_count: row.get::<_, u32>(6).unwrap_or(0),
+                    cost_usd,
                 })
             })
             .map_err(|e| format!("Failed to query session history: {}", e))?
-            .filter_map(|r| r.ok())
+            .filter_map(skip_bad_row)
             .collect();
 
         Ok(sessions)
     }
+
+    /// Sessions active within the last `days` days, most expensive first.
+    /// Cost depends on per-model rates, so SQL narrows by date and tokens and
+    /// the final ranking by cost happens here.
+    fn query_top_sessions(
+        conn: &Connection,
+        cols: &MessageColumns,
+        days: u32,
+        limit: u32,
+        threshold: Duration,
+    ) -> Result<Vec<Session>, String> {
+        let sql = format!(
+            "SELECT s.id, s.name, s.working_directory, \
+                 COALESCE(s.updated_at, s.created_at, '') as last_active, \
+                 COALESCE(m.model, 'unknown') as model, \
+                 COALESCE(m.total_tokens, 0) as tokens_used, \
+                 COALESCE(m.msg_count, 0) as msg_count, \
+                 COALESCE(m.input_tokens, 0) as input_tokens, \
+                 COALESCE(m.output_tokens, 0) as output_tokens \
+                 FROM sessions s \
+                 JOIN ( \
+                     {totals} \
+                 ) m ON s.id = m.session_id \
+                 WHERE COALESCE(s.updated_at, s.created_at) >= datetime('now', ?1) \
+                 ORDER BY tokens_used DESC",
+            totals = cols.session_totals()
+        );
+        let mut stmt = conn
+            .prepare_cached(&sql)
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let active_since = (chrono::Utc::now() - threshold).to_rfc3339();
+
+        let sessions: Vec<Session> = stmt
+            .query_map([format!("-{} days", days)], |row| {
+                let last_active: String = row.get::<_, String>(3).unwrap_or_default();
+                let is_active = last_active.as_str() >= active_since.as_str();
+
+                let model = row.get::<_, String>(4).unwrap_or_else(|_| "unknown".to_string());
+                let cost_usd = Self::estimate_cost(
+                    &model,
+                    row.get::<_, u64>(7).unwrap_or(0),
+                    row.get::<_, u64>(8).unwrap_or(0),
+                );
+
+                Ok(Session {
+                    id: row.get::<_, String>(0)?,
+                    provider: "z.ai".to_string(),
+                    profile_id: String::new(),
+                    project: row.get::<_, String>(2).unwrap_or_default(),
+                    project_path: row.get::<_, String>(2).unwrap_or_default(),
+                    project_dir_encoded: String::new(),
+                    model,
+                    tokens_used: row.get::<_, u64>(5).unwrap_or(0),
+                    last_active,
+                    is_active,
+                    message_count: row.get::<_, u32>(6).unwrap_or(0),
+                    cost_usd,
+                })
+            })
+            .map_err(|e| format!("Failed to query top sessions: {}", e))?
+            .filter_map(skip_bad_row)
+            .collect();
+
+        Ok(rank_by_cost(sessions, limit))
+    }
+
+    /// Distinct model names across all messages.
+    fn query_known_models(conn: &Connection, cols: &MessageColumns) -> Result<Vec<String>, String> {
+        let sql = format!(
+            "SELECT DISTINCT COALESCE({}, 'unknown') FROM messages ORDER BY 1",
+            cols.model
+        );
+        let mut stmt = conn
+            .prepare_cached(&sql)
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let models = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(|e| format!("Failed to query models: {}", e))?
+            .filter_map(skip_bad_row)
+            .collect();
+
+        Ok(models)
+    }
+}
+
+impl Provider for ZaiProvider {
+    fn name(&self) -> &str {
+        "z.ai"
+    }
+
+    fn provider_type(&self) -> &str {
+        "zai"
+    }
+
+    fn config_dir(&self) -> &PathBuf {
+        &self.config_dir
+    }
+
+    fn probe(&self) -> Result<String, ProviderError> {
+        let path = self.db_path();
+        if !path.is_file() {
+            return Err(ProviderError::NotConfigured(format!(
+                "Database not found: {}",
+                path.display()
+            )));
+        }
+        match Self::open_db(&path) {
+            Some(conn) => {
+                MessageColumns::detect(&conn)?;
+                Ok(format!("Opened {}", path.display()))
+            }
+            None => Err(format!("Failed to open database: {}", path.display()).into()),
+        }
+    }
+
+    fn health_check(&self) -> Result<(), ProviderError> {
+        // Opening the database and finding its columns is the whole check
+        self.probe().map(|_| ())
+    }
+
+    fn diagnostics(&self) -> Diagnostics {
+        Diagnostics {
+            config_dir: self.config_dir.display().to_string(),
+            config_dir_exists: self.config_dir.is_dir(),
+            files: vec![FileStatus::of(&self.db_path())],
+            ..Default::default()
+        }
+    }
+
+    fn get_usage_stats(&self) -> Result<UsageStats, ProviderError> {
+        Ok(self.with_db(Self::query_usage_stats)?.unwrap_or_else(|| {
+            UsageStats {
+                provider: "z.ai".to_string(),
+                total_input_tokens: 0,
+                total_output_tokens: 0,
+                total_cache_read_tokens: 0,
+                total_cache_write_tokens: 0,
+                total_sessions: 0,
+                total_messages: 0,
+                estimated_cost_usd: 0.0,
+                model_breakdown: HashMap::new(),
+                ..Default::default()
+            }
+        }))
+    }
+
+    fn get_active_sessions(&self) -> Result<Vec<Session>, ProviderError> {
+        Ok(self
+            .with_db(|conn, cols| Self::query_active_sessions(conn, cols, self.active_threshold))?
+            .unwrap_or_default())
+    }
+
+    fn get_daily_usage(&self, days: u32) -> Result<Vec<DailyUsage>, ProviderError> {
+        Ok(self
+            .with_db(|conn, cols| Self::query_daily_usage(conn, cols, days))?
+            .unwrap_or_default())
+    }
+
+    fn get_daily_model_usage(&self, days: u32) -> Result<Vec<DailyModelUsage>, ProviderError> {
+        Ok(self
+            .with_db(|conn, cols| Self::query_daily_model_usage(conn, cols, days))?
+            .unwrap_or_default())
+    }
+
+    fn get_session_history(&self, limit: u32) -> Result<Vec<Session>, ProviderError> {
+        Ok(self
+            .with_db(|conn, cols| Self::query_session_history(conn, cols, limit, self.active_threshold))?
+            .unwrap_or_default())
+    }
+
+    fn get_top_sessions(&self, days: u32, limit: u32) -> Result<Vec<Session>, ProviderError> {
+        Ok(self
+            .with_db(|conn, cols| Self::query_top_sessions(conn, cols, days, limit, self.active_threshold))?
+            .unwrap_or_default())
+    }
+
+    fn get_known_models(&self) -> Result<Vec<String>, ProviderError> {
+        Ok(self
+            .with_db(Self::query_known_models)?
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// An in-memory database with the given `messages` columns and an empty
+    /// `sessions` table.
+    fn db(message_columns: &str) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(&format!(
+            "CREATE TABLE messages ({});
+             CREATE TABLE sessions (id TEXT, name TEXT, working_directory TEXT, created_at TEXT, updated_at TEXT);",
+            message_columns
+        ))
+        .unwrap();
+        conn
+    }
+
+    fn today() -> String {
+        Local::now().format("%Y-%m-%d").to_string()
+    }
+
+    #[test]
+    fn columns_are_detected_by_their_aliases() {
+        let conn = db("conversation_id TEXT, model_name TEXT, prompt_tokens INTEGER, completion_tokens INTEGER");
+
+        let cols = MessageColumns::detect(&conn).unwrap();
+        assert_eq!(cols.session_id, "conversation_id");
+        assert_eq!(cols.input_tokens, "prompt_tokens");
+        assert_eq!(cols.output_tokens, "completion_tokens");
+        assert_eq!(cols.model, "model_name");
+        assert_eq!(cols.created_at, None);
+    }
+
+    #[test]
+    fn messages_without_a_model_column_count_as_unknown() {
+        let conn = db("session_id TEXT, input_tokens INTEGER, output_tokens INTEGER");
+        conn.execute("INSERT INTO messages VALUES ('s1', 100, 10), ('s1', 50, 5)", []).unwrap();
+
+        let cols = MessageColumns::detect(&conn).unwrap();
+        assert_eq!(cols.model, "NULL");
+        let stats = ZaiProvider::query_usage_stats(&conn, &cols).unwrap();
+        assert_eq!(stats.total_input_tokens, 150);
+        assert_eq!(stats.total_messages, 2);
+        assert_eq!(stats.model_breakdown["unknown"].output_tokens, 15);
+    }
+
+    #[test]
+    fn a_missing_required_column_is_an_error() {
+        let conn = db("session_id TEXT, input_tokens INTEGER");
+        assert!(MessageColumns::detect(&conn).is_err());
+    }
+
+    #[test]
+    fn daily_usage_reads_epoch_millis_timestamps() {
+        let conn = db("session_id TEXT, model TEXT, input_tokens INTEGER, output_tokens INTEGER, created_at INTEGER");
+        let now = Utc::now().timestamp_millis();
+        conn.execute(
+            "INSERT INTO messages VALUES ('s1', 'glm-4.6', 100, 10, ?1), ('s2', 'glm-4.6', 200, 20, ?1)",
+            [now],
+        )
+        .unwrap();
+        let cols = MessageColumns::detect(&conn).unwrap();
+
+        for daily in [
+            ZaiProvider::query_daily_usage(&conn, &cols, 7).unwrap(),
+            ZaiProvider::bucket_daily_usage(&conn, &cols, "created_at", 7).unwrap(),
+        ] {
+            assert_eq!(daily.len(), 1);
+            assert_eq!(daily[0].date, today());
+            assert_eq!(daily[0].input_tokens, 300);
+            assert_eq!(daily[0].output_tokens, 30);
+            assert_eq!(daily[0].sessions, 2);
+            assert_eq!(daily[0].messages, 2);
+        }
+    }
+
+    #[test]
+    fn daily_usage_reads_iso_timestamps() {
+        let conn = db("session_id TEXT, model TEXT, input_tokens INTEGER, output_tokens INTEGER, created_at TEXT");
+        let now = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let old = (Utc::now() - chrono::Duration::days(30)).format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "INSERT INTO messages VALUES ('s1', 'glm-4.6', 100, 10, ?1), ('s1', 'glm-4.6', 500, 50, ?2)",
+            [&now, &old],
+        )
+        .unwrap();
+        let cols = MessageColumns::detect(&conn).unwrap();
+
+        for daily in [
+            ZaiProvider::query_daily_usage(&conn, &cols, 7).unwrap(),
+            ZaiProvider::bucket_daily_usage(&conn, &cols, "created_at", 7).unwrap(),
+        ] {
+            assert_eq!(daily.len(), 1);
+            assert_eq!(daily[0].date, today());
+            assert_eq!(daily[0].input_tokens, 100);
+            assert_eq!(daily[0].messages, 1);
+        }
+    }
+
+    #[test]
+    fn a_failed_query_is_retried_on_an_immutable_snapshot() {
+        let dir = std::env::temp_dir().join(format!("cldbar-zai-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        {
+            let conn = Connection::open(dir.join("sessions.db")).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE messages (session_id TEXT, model TEXT, input_tokens INTEGER, output_tokens INTEGER);
+                 INSERT INTO messages VALUES ('s1', 'glm-4.6', 100, 10);",
+            )
+            .unwrap();
+        }
+
+        let provider = ZaiProvider::new(dir.clone(), Duration::from_secs(300));
+        let calls = Cell::new(0);
+        let result = provider.with_db(|conn, cols| {
+            calls.set(calls.get() + 1);
+            if calls.get() == 1 {
+                return Err("database is locked".to_string());
+            }
+            ZaiProvider::query_usage_stats(conn, cols)
+        });
+        let _ = fs::remove_dir_all(&dir);
+
+        let stats = result.unwrap().unwrap();
+        assert_eq!(calls.get(), 2);
+        assert_eq!(stats.total_input_tokens, 100);
+        // The failed connection is dropped so the next call reopens it
+        assert!(provider.conn.lock().unwrap().is_none());
+    }
 }
@@ -1,12 +1,20 @@
-use super::{DailyUsage, ModelUsage, Provider, RateLimitStatus, RateLimitWindow, Session, UsageStats};
-use serde::Deserialize;
+use super::{
+    http_client, send_with_retry, DailyUsage, Diagnostics, FetchStatus, ModelUsage, Provider, ProviderError,
+    RateLimitStatus, RateLimitWindow, Session, UsageStats,
+};
+use super::zai::ZaiProvider;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::{Mutex, PoisonError};
 
 pub struct ZaiApiProvider {
     api_key: String,
     base_url: String,
+    client: reqwest::blocking::Client,
     config_dir: PathBuf,
+    /// Outcome of the last model usage request, for diagnostics.
+    last_fetch: Mutex<Option<FetchStatus>>,
 }
 
 // --- Deserialization types for z.ai monitoring API ---
@@ -35,7 +43,7 @@ struct ModelUsageResponse {
     data: Option<Vec<ModelUsageEntry>>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 struct ModelUsageEntry {
     #[serde(default, rename = "modelName")]
     model_name: String,
@@ -48,54 +56,56 @@ struct ModelUsageEntry {
 }
 
 impl ZaiApiProvider {
-    pub fn new(api_key: String) -> Self {
+    pub fn new(api_key: String, proxy_url: Option<&str>) -> Result<Self, String> {
         // Detect platform from API key or default to global
         let base_url = "https://api.z.ai".to_string();
-        Self {
+        let client = http_client(std::time::Duration::from_secs(15), proxy_url)?;
+
+        Ok(Self {
             api_key,
             base_url,
+            client,
             config_dir: PathBuf::new(),
-        }
-    }
-
-    fn client(&self) -> Result<reqwest::blocking::Client, String> {
-        reqwest::blocking::Client::builder()
-            .timeout(std::time::Duration::from_secs(15))
-            .build()
-            .map_err(|e| format!("Failed to create HTTP client: {}", e))
+            last_fetch: Mutex::new(None),
+        })
     }
 
     /// Fetch quota/rate limit status from z.ai monitoring API.
-    pub fn get_rate_limit_status(&self) -> RateLimitStatus {
+    fn fetch_rate_limit_status(&self) -> RateLimitStatus {
         let unavailable = RateLimitStatus {
             available: false,
             five_hour: None,
             seven_day: None,
             seven_day_opus: None,
+            window_started_at: None,
+            sessions_in_window: None,
+            messages_in_window: None,
         };
 
-        let client = match self.client() {
-            Ok(c) => c,
-            Err(_) => return unavailable,
-        };
-
-        let resp = client
-            .get(format!("{}/api/monitor/usage/quota/limit", self.base_url))
-            .header("Authorization", &self.api_key)
-            .header("Accept-Language", "en-US,en")
-            .header("Content-Type", "application/json")
-            .send();
+        let resp = send_with_retry(
+            self.client
+                .get(format!("{}/api/monitor/usage/quota/limit", self.base_url))
+                .header("Authorization", &self.api_key)
+                .header("Accept-Language", "en-US,en")
+                .header("Content-Type", "application/json"),
+        );
 
         match resp {
             Ok(r) if r.status().is_success() => {
                 let body = match r.text() {
                     Ok(b) => b,
-                    Err(_) => return unavailable,
+                    Err(e) => {
+                        tracing::warn!("Failed to read z.ai quota response: {}", e);
+                        return unavailable;
+                    }
                 };
 
                 let quota: QuotaLimitResponse = match serde_json::from_str(&body) {
                     Ok(q) => q,
-                    Err(_) => return unavailable,
+                    Err(e) => {
+                        tracing::warn!("Failed to parse z.ai quota response: {}", e);
+                        return unavailable;
+                    }
                 };
 
                 if quota.limits.is_empty() {
@@ -137,36 +147,58 @@ impl ZaiApiProvider {
                     five_hour: token_window,
                     seven_day: time_window,
                     seven_day_opus: None,
+                    window_started_at: None,
+                    sessions_in_window: None,
+                    messages_in_window: None,
                 }
             }
-            _ => unavailable,
+            Ok(r) => {
+                tracing::warn!("z.ai quota request returned {}", r.status());
+                unavailable
+            }
+            Err(e) => {
+                tracing::warn!("z.ai quota request failed: {}", e);
+                unavailable
+            }
         }
     }
 
-    /// Fetch model usage from z.ai monitoring API (24h rolling window).
-    fn fetch_model_usage(&self) -> Option<Vec<ModelUsageEntry>> {
-        let client = self.client().ok()?;
+    /// Fetch model usage from z.ai monitoring API (24h rolling window),
+    /// recording the outcome.
+    fn fetch_model_usage(&self) -> Result<Vec<ModelUsageEntry>, ProviderError> {
+        let result = self.request_model_usage();
+        *self.last_fetch.lock().unwrap_or_else(PoisonError::into_inner) = Some(FetchStatus::of(&result));
+        result
+    }
 
+    fn request_model_usage(&self) -> Result<Vec<ModelUsageEntry>, ProviderError> {
         let now = chrono::Utc::now();
         let start = now - chrono::Duration::hours(24);
         let start_time = start.format("%Y-%m-%d %H:00:00").to_string();
         let end_time = now.format("%Y-%m-%d %H:59:59").to_string();
 
-        let resp = client
-            .get(format!("{}/api/monitor/usage/model-usage", self.base_url))
-            .header("Authorization", &self.api_key)
-            .header("Accept-Language", "en-US,en")
-            .header("Content-Type", "application/json")
-            .query(&[("startTime", &start_time), ("endTime", &end_time)])
-            .send()
-            .ok()?;
-
-        if !resp.status().is_success() {
-            return None;
+        let resp = send_with_retry(
+            self.client
+                .get(format!("{}/api/monitor/usage/model-usage", self.base_url))
+                .header("Authorization", &self.api_key)
+                .header("Accept-Language", "en-US,en")
+                .header("Content-Type", "application/json")
+                .query(&[("startTime", &start_time), ("endTime", &end_time)]),
+        )
+        .map_err(|e| ProviderError::Network(format!("z.ai model usage request failed: {}", e)))?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            return Err(ProviderError::from_status(
+                status,
+                format!("z.ai model usage request returned {}", status),
+            ));
         }
 
-        let body: ModelUsageResponse = resp.json().ok()?;
-        body.data
+        let body: ModelUsageResponse = resp
+            .json()
+            .map_err(|e| ProviderError::Parse(format!("Failed to parse z.ai model usage: {}", e)))?;
+        Ok(body.data.unwrap_or_default())
     }
 }
 
@@ -183,8 +215,28 @@ impl Provider for ZaiApiProvider {
         &self.config_dir
     }
 
-    fn get_usage_stats(&self) -> Result<UsageStats, String> {
-        let entries = self.fetch_model_usage().unwrap_or_default();
+    fn diagnostics(&self) -> Diagnostics {
+        Diagnostics {
+            last_fetch: self.last_fetch.lock().unwrap_or_else(PoisonError::into_inner).clone(),
+            ..Default::default()
+        }
+    }
+
+    fn health_check(&self) -> Result<(), ProviderError> {
+        // The quota request hides failures, so check the key with model usage
+        self.fetch_model_usage().map(|_| ())
+    }
+
+    fn get_usage_stats(&self) -> Result<UsageStats, ProviderError> {
+        // A rejected key is worth surfacing; anything else shows as no usage
+        let entries = match self.fetch_model_usage() {
+            Ok(entries) => entries,
+            Err(e @ ProviderError::Unauthorized(_)) => return Err(e),
+            Err(e) => {
+                tracing::warn!("{}", e);
+                Vec::new()
+            }
+        };
 
         let mut total_input: u64 = 0;
         let mut total_output: u64 = 0;
@@ -193,11 +245,8 @@ impl Provider for ZaiApiProvider {
         let mut model_breakdown: HashMap<String, ModelUsage> = HashMap::new();
 
         for entry in &entries {
-            let input_rate = 1.0_f64;
-            let output_rate = 4.0_f64;
-            let cost = (entry.input_tokens as f64 * input_rate
-                + entry.output_tokens as f64 * output_rate)
-                / 1_000_000.0;
+            let cost =
+                ZaiProvider::estimate_cost(&entry.model_name, entry.input_tokens, entry.output_tokens);
 
             total_input += entry.input_tokens;
             total_output += entry.output_tokens;
@@ -212,7 +261,8 @@ impl Provider for ZaiApiProvider {
                     output_tokens: entry.output_tokens,
                     cache_read_tokens: 0,
                     cache_write_tokens: 0,
-                    cost_usd: (cost * 100.0).round() / 100.0,
+                    cost_usd: cost,
+                    ..Default::default()
                 },
             );
         }
@@ -227,20 +277,36 @@ impl Provider for ZaiApiProvider {
             total_messages,
             estimated_cost_usd: (total_cost * 100.0).round() / 100.0,
             model_breakdown,
+            ..Default::default()
         })
     }
 
-    fn get_active_sessions(&self) -> Result<Vec<Session>, String> {
+    fn get_active_sessions(&self) -> Result<Vec<Session>, ProviderError> {
         // z.ai API doesn't provide session tracking
         Ok(Vec::new())
     }
 
-    fn get_daily_usage(&self, _days: u32) -> Result<Vec<DailyUsage>, String> {
+    fn has_daily_breakdown(&self) -> bool {
+        false
+    }
+
+    fn get_daily_usage(&self, _days: u32) -> Result<Vec<DailyUsage>, ProviderError> {
         // z.ai monitoring API only provides 24h rolling window, not daily breakdown
         Ok(Vec::new())
     }
 
-    fn get_session_history(&self, _limit: u32) -> Result<Vec<Session>, String> {
+    fn get_session_history(&self, _limit: u32) -> Result<Vec<Session>, ProviderError> {
         Ok(Vec::new())
     }
+
+    fn get_rate_limit_status(&self) -> Result<RateLimitStatus, ProviderError> {
+        Ok(self.fetch_rate_limit_status())
+    }
+
+    fn debug_raw_usage(&self, _days: u32) -> Result<serde_json::Value, ProviderError> {
+        // The model-usage endpoint only covers a rolling 24h window
+        let entries = self.fetch_model_usage()?;
+        serde_json::to_value(entries)
+            .map_err(|e| ProviderError::Parse(format!("Failed to serialize model usage: {}", e)))
+    }
 }
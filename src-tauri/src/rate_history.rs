@@ -0,0 +1,83 @@
+use crate::profile;
+use crate::providers::RateLimitStatus;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+
+/// Snapshots older than this are dropped.
+const RETENTION_HOURS: i64 = 24;
+
+/// Hard cap per profile, in case snapshots are recorded more often than expected.
+const MAX_ENTRIES: usize = 500;
+
+/// Per-profile ring buffer of timestamped rate-limit snapshots,
+/// persisted to %APPDATA%/cldbar/cache/rate_limits.json
+#[derive(Default)]
+pub struct RateLimitHistory {
+    entries: HashMap<String, VecDeque<(String, RateLimitStatus)>>,
+}
+
+fn history_file_path() -> Result<PathBuf, String> {
+    Ok(profile::cache_dir()?.join("rate_limits.json"))
+}
+
+impl RateLimitHistory {
+    /// Load history from disk, starting empty if the file is missing or unreadable.
+    pub fn load() -> Self {
+        let entries = history_file_path()
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+
+        let mut history = Self { entries };
+        history.prune();
+        history
+    }
+
+    /// Write history to disk.
+    pub fn save(&self) -> Result<(), String> {
+        let path = history_file_path()?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create cache directory: {}", e))?;
+        }
+
+        let data = serde_json::to_string(&self.entries)
+            .map_err(|e| format!("Failed to serialize rate limit history: {}", e))?;
+
+        fs::write(&path, data)
+            .map_err(|e| format!("Failed to write rate limit history: {}", e))
+    }
+
+    /// Append a snapshot taken now and drop expired entries.
+    pub fn record(&mut self, profile_id: &str, status: RateLimitStatus) {
+        let entries = self.entries.entry(profile_id.to_string()).or_default();
+        entries.push_back((chrono::Utc::now().to_rfc3339(), status));
+        while entries.len() > MAX_ENTRIES {
+            entries.pop_front();
+        }
+        self.prune();
+    }
+
+    /// Snapshots for a profile, oldest first.
+    pub fn get(&self, profile_id: &str) -> Vec<(String, RateLimitStatus)> {
+        self.entries
+            .get(profile_id)
+            .map(|e| e.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn prune(&mut self) {
+        let cutoff = chrono::Utc::now() - chrono::Duration::hours(RETENTION_HOURS);
+        for entries in self.entries.values_mut() {
+            entries.retain(|(ts, _)| {
+                chrono::DateTime::parse_from_rfc3339(ts)
+                    .map(|t| t >= cutoff)
+                    .unwrap_or(false)
+            });
+        }
+        self.entries.retain(|_, e| !e.is_empty());
+    }
+}
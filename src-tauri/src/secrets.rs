@@ -0,0 +1,73 @@
+use crate::profile::AppConfig;
+
+/// Service name API keys are stored under in the OS keychain.
+/// Each key's account name is its profile id.
+const SERVICE: &str = "cldbar";
+
+fn entry(profile_id: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(SERVICE, profile_id)
+        .map_err(|e| format!("Failed to open keychain entry: {}", e))
+}
+
+/// Store a profile's API key in the OS keychain.
+pub fn store_api_key(profile_id: &str, api_key: &str) -> Result<(), String> {
+    entry(profile_id)?
+        .set_password(api_key)
+        .map_err(|e| format!("Failed to store API key in keychain: {}", e))
+}
+
+/// Read a profile's API key from the OS keychain. `None` if there is no entry.
+pub fn load_api_key(profile_id: &str) -> Result<Option<String>, String> {
+    match entry(profile_id)?.get_password() {
+        Ok(key) => Ok(Some(key)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("Failed to read API key from keychain: {}", e)),
+    }
+}
+
+/// Remove a profile's API key from the OS keychain, if present.
+pub fn delete_api_key(profile_id: &str) -> Result<(), String> {
+    match entry(profile_id)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(format!("Failed to delete API key from keychain: {}", e)),
+    }
+}
+
+/// Move every plaintext API key in `config` into the keychain, marking the
+/// profile so `save_config` leaves the key out of config.json. Keys stay in
+/// the file, with a warning, when the keychain is unavailable.
+/// Returns whether any profile changed.
+pub fn secure_api_keys(config: &mut AppConfig) -> bool {
+    let mut changed = false;
+    for profile in &mut config.profiles {
+        if profile.api_key_in_keychain {
+            continue;
+        }
+        let key = match profile.api_key {
+            Some(ref k) => k,
+            None => continue,
+        };
+        match store_api_key(&profile.id, key) {
+            Ok(()) => {
+                profile.api_key_in_keychain = true;
+                changed = true;
+            }
+            Err(e) => tracing::warn!("{}; keeping the API key for {} in config.json", e, profile.id),
+        }
+    }
+    changed
+}
+
+/// Fill in `api_key` for profiles whose key lives in the keychain.
+pub fn load_api_keys(config: &mut AppConfig) {
+    for profile in &mut config.profiles {
+        if !profile.api_key_in_keychain {
+            continue;
+        }
+        match load_api_key(&profile.id) {
+            Ok(Some(key)) => profile.api_key = Some(key),
+            Ok(None) => tracing::warn!("No API key in keychain for {}", profile.id),
+            Err(e) => tracing::warn!("{}", e),
+        }
+    }
+}
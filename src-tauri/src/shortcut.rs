@@ -0,0 +1,18 @@
+use tauri::AppHandle;
+use tauri_plugin_global_shortcut::GlobalShortcutExt;
+
+/// Make `shortcut` the only registered global shortcut; `None` or an empty
+/// string clears it. Pressing it is handled by the plugin handler in `run`.
+pub fn apply(app: &AppHandle, shortcut: Option<&str>) -> Result<(), String> {
+    let manager = app.global_shortcut();
+    manager
+        .unregister_all()
+        .map_err(|e| format!("Failed to clear global shortcuts: {}", e))?;
+
+    match shortcut.map(str::trim).filter(|s| !s.is_empty()) {
+        Some(s) => manager
+            .register(s)
+            .map_err(|e| format!("Failed to register shortcut {}: {}", s, e)),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,135 @@
+use crate::commands::AppState;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Event emitted to the frontend when a profile's session files change.
+const USAGE_UPDATED_EVENT: &str = "usage-updated";
+
+/// Quiet period after the last change before the event is emitted, so a
+/// burst of appends to a session file produces one refresh.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Longest a change waits for a quiet period, so a session that is
+/// streaming output still refreshes regularly.
+const MAX_DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// How often the watched directories are re-read from the providers,
+/// picking up added, removed and edited profiles.
+const RESCAN_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageUpdated {
+    profile_id: String,
+}
+
+/// Spawn the session file watcher.
+/// Emits `usage-updated` with the profile id when a provider's session files
+/// change. Directories that can't be watched are logged and left to the
+/// frontend's polling.
+pub fn spawn(app: AppHandle) {
+    thread::spawn(move || {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = match notify::recommended_watcher(tx) {
+            Ok(w) => w,
+            Err(e) => {
+                tracing::warn!("File watcher unavailable, falling back to polling: {}", e);
+                return;
+            }
+        };
+
+        // watched directory -> ids of the profiles reading it
+        let mut watched: HashMap<PathBuf, Vec<String>> = HashMap::new();
+        let mut last_rescan: Option<Instant> = None;
+        let mut pending: HashSet<String> = HashSet::new();
+        let mut pending_since: Option<Instant> = None;
+
+        loop {
+            if last_rescan.is_none_or(|t| t.elapsed() >= RESCAN_INTERVAL) {
+                update_watches(&app, &mut watcher, &mut watched);
+                last_rescan = Some(Instant::now());
+            }
+
+            let timeout = if pending.is_empty() { RESCAN_INTERVAL } else { DEBOUNCE };
+            match rx.recv_timeout(timeout) {
+                Ok(Ok(event)) if is_session_change(&event) => {
+                    for (dir, ids) in &watched {
+                        if event.paths.iter().any(|p| p.starts_with(dir)) {
+                            pending.extend(ids.iter().cloned());
+                        }
+                    }
+                    if !pending.is_empty() && pending_since.is_none() {
+                        pending_since = Some(Instant::now());
+                    }
+                    if pending_since.is_none_or(|t| t.elapsed() < MAX_DEBOUNCE) {
+                        continue;
+                    }
+                }
+                Ok(Ok(_)) => continue,
+                Ok(Err(e)) => {
+                    tracing::warn!("File watcher error: {}", e);
+                    continue;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            for profile_id in pending.drain() {
+                if let Err(e) = app.emit(USAGE_UPDATED_EVENT, UsageUpdated { profile_id }) {
+                    tracing::warn!("Failed to emit {}: {}", USAGE_UPDATED_EVENT, e);
+                }
+            }
+            pending_since = None;
+        }
+    });
+}
+
+/// Watch every provider's `watch_dirs` and drop watches no provider needs.
+fn update_watches(
+    app: &AppHandle,
+    watcher: &mut RecommendedWatcher,
+    watched: &mut HashMap<PathBuf, Vec<String>>,
+) {
+    let state = app.state::<AppState>();
+    let mut targets: HashMap<PathBuf, Vec<String>> = HashMap::new();
+    if let Ok(providers) = state.providers.lock() {
+        for (id, provider) in providers.iter() {
+            for dir in provider.watch_dirs().into_iter().filter(|d| d.is_dir()) {
+                targets.entry(dir).or_default().push(id.clone());
+            }
+        }
+    }
+
+    for dir in watched.keys() {
+        if !targets.contains_key(dir) {
+            let _ = watcher.unwatch(dir);
+        }
+    }
+    watched.retain(|dir, _| targets.contains_key(dir));
+
+    for (dir, ids) in targets {
+        if !watched.contains_key(&dir) {
+            if let Err(e) = watcher.watch(&dir, RecursiveMode::Recursive) {
+                tracing::warn!("Failed to watch {}, falling back to polling: {}", dir.display(), e);
+                continue;
+            }
+            tracing::info!("Watching {} for session changes", dir.display());
+        }
+        watched.insert(dir, ids);
+    }
+}
+
+/// Whether the event writes a session file (`.json` or `.jsonl`).
+fn is_session_change(event: &Event) -> bool {
+    matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+        && event.paths.iter().any(|p| {
+            p.extension()
+                .is_some_and(|ext| ext == "json" || ext == "jsonl")
+        })
+}
@@ -0,0 +1,125 @@
+use crate::commands::AppState;
+use crate::profile;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{AppHandle, Manager, Monitor, PhysicalPosition, PhysicalSize, Rect, WebviewWindow, WindowEvent};
+
+/// Set when the remembered position changed since it was last written to disk.
+static DIRTY: AtomicBool = AtomicBool::new(false);
+
+/// Track moves of the popover and write the last position to the config
+/// when it loses focus (the frontend hides it on blur).
+pub fn track(window: &WebviewWindow) {
+    let app = window.app_handle().clone();
+    window.on_window_event(move |event| match event {
+        WindowEvent::Moved(position) => remember(&app, *position),
+        WindowEvent::Focused(false) => save(&app),
+        _ => {}
+    });
+}
+
+/// Hide the popover if it's showing, otherwise show and focus it at its
+/// remembered position. Used by the tray icon and the global shortcut.
+pub fn toggle(app: &AppHandle) {
+    let Some(window) = app.get_webview_window("main") else {
+        return;
+    };
+    if window.is_visible().unwrap_or(false) {
+        let _ = window.hide();
+    } else {
+        let tray = app.tray_by_id("main").and_then(|t| t.rect().ok().flatten());
+        restore(&window, tray);
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+/// Move the popover to where it was last left, or next to the tray icon
+/// the first time, clamped to the work area of the monitor it lands on.
+fn restore(window: &WebviewWindow, tray: Option<Rect>) {
+    let size = window
+        .outer_size()
+        .unwrap_or(PhysicalSize { width: 380, height: 490 });
+
+    let saved = window
+        .state::<AppState>()
+        .config
+        .lock()
+        .ok()
+        .and_then(|c| Some(PhysicalPosition::new(c.settings.window_x?, c.settings.window_y?)));
+
+    let position = match saved.or_else(|| tray.map(|t| tray_anchored(window, t, size))) {
+        Some(p) => p,
+        None => return,
+    };
+
+    let _ = window.set_position(clamp_to_work_area(window, position, size));
+}
+
+/// Centered on the tray icon with the bottom edge just above it, or the top
+/// edge just below it when the taskbar is at the top of the screen.
+fn tray_anchored(window: &WebviewWindow, tray: Rect, size: PhysicalSize<u32>) -> PhysicalPosition<i32> {
+    let tray_pos: PhysicalPosition<i32> = tray.position.to_physical(1.0);
+    let tray_size: PhysicalSize<u32> = tray.size.to_physical(1.0);
+
+    let x = tray_pos.x + tray_size.width as i32 / 2 - size.width as i32 / 2;
+    let above = tray_pos.y - size.height as i32;
+    let top = monitor_at(window, tray_pos)
+        .map(|m| m.work_area().position.y)
+        .unwrap_or(i32::MIN);
+    let y = if above < top {
+        tray_pos.y + tray_size.height as i32
+    } else {
+        above
+    };
+    PhysicalPosition::new(x, y)
+}
+
+/// Keep the whole window inside the work area of the monitor containing
+/// `position`, so it never opens past a screen edge.
+fn clamp_to_work_area(
+    window: &WebviewWindow,
+    position: PhysicalPosition<i32>,
+    size: PhysicalSize<u32>,
+) -> PhysicalPosition<i32> {
+    let Some(monitor) = monitor_at(window, position) else {
+        return position;
+    };
+
+    let area = monitor.work_area();
+    let max_x = area.position.x + area.size.width as i32 - size.width as i32;
+    let max_y = area.position.y + area.size.height as i32 - size.height as i32;
+    PhysicalPosition::new(
+        position.x.min(max_x).max(area.position.x),
+        position.y.min(max_y).max(area.position.y),
+    )
+}
+
+/// The monitor containing `position`, falling back to the window's current
+/// monitor and then the primary one (e.g. when a monitor was unplugged).
+fn monitor_at(window: &WebviewWindow, position: PhysicalPosition<i32>) -> Option<Monitor> {
+    window
+        .monitor_from_point(position.x as f64, position.y as f64)
+        .ok()
+        .flatten()
+        .or_else(|| window.current_monitor().ok().flatten())
+        .or_else(|| window.primary_monitor().ok().flatten())
+}
+
+fn remember(app: &AppHandle, position: PhysicalPosition<i32>) {
+    if let Ok(mut config) = app.state::<AppState>().config.lock() {
+        config.settings.window_x = Some(position.x);
+        config.settings.window_y = Some(position.y);
+        DIRTY.store(true, Ordering::Relaxed);
+    }
+}
+
+fn save(app: &AppHandle) {
+    if !DIRTY.swap(false, Ordering::Relaxed) {
+        return;
+    }
+    if let Ok(config) = app.state::<AppState>().config.lock() {
+        if let Err(e) = profile::save_config(&config) {
+            tracing::warn!("{}", e);
+        }
+    }
+}
@@ -1,14 +1,15 @@
 import { motion } from "framer-motion";
 import { Circle, MonitorOff } from "lucide-react";
-import { formatTokens, formatTimeAgo } from "../../lib/format";
+import { formatTokens, formatCost, formatTimeAgo } from "../../lib/format";
 import type { Session, SourceType } from "../../lib/types";
 
 interface Props {
   sessions: Session[];
   sourceType?: SourceType;
+  locale?: string;
 }
 
-export function ActiveSessions({ sessions, sourceType }: Props) {
+export function ActiveSessions({ sessions, sourceType, locale }: Props) {
   const isEmpty = sourceType === "api" || sessions.length === 0;
 
   return (
@@ -48,6 +49,7 @@ export function ActiveSessions({ sessions, sourceType }: Props) {
               </div>
               <div className="text-[10px] text-muted">
                 {session.model.split("-").slice(-2).join("-")} · {formatTokens(session.tokensUsed)}
+                {session.costUsd > 0 && <> · {formatCost(session.costUsd, "USD", locale)}</>}
               </div>
             </div>
             <span className="text-[10px] text-muted whitespace-nowrap">
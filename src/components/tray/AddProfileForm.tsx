@@ -4,7 +4,7 @@ import { invoke } from "@tauri-apps/api/core";
 import { open } from "@tauri-apps/plugin-dialog";
 import { providerColors } from "../../lib/colors";
 import { setDialogOpen, startManualDrag } from "../../lib/windowState";
-import { apiSupportedProviders, accountSupportedProviders, type ProviderType, type SourceType } from "../../lib/types";
+import { apiSupportedProviders, accountSupportedProviders, type KeyValidation, type ProviderType, type SourceType } from "../../lib/types";
 
 interface Props {
   onBack: () => void;
@@ -14,6 +14,7 @@ interface Props {
 const providers: { type: ProviderType; label: string }[] = [
   { type: "claude", label: "Claude" },
   { type: "gemini", label: "Gemini" },
+  { type: "openai", label: "OpenAI" },
   // { type: "zai", label: "z.ai" },  // TODO: re-enable when z.ai API is stable
 ];
 
@@ -50,9 +51,10 @@ export function AddProfileForm({ onBack, onAdded }: Props) {
     setValidated(null);
     setError(null);
     try {
-      const valid = await invoke<boolean>("validate_api_key", { apiKey: apiKey.trim(), providerType });
-      setValidated(valid);
-      if (!valid) setError("Invalid API key or insufficient permissions");
+      const result = await invoke<KeyValidation>("validate_api_key", { apiKey: apiKey.trim(), providerType });
+      setValidated(result.status === "valid");
+      if (result.status === "invalid") setError("Invalid API key or insufficient permissions");
+      if (result.status === "unreachable") setError(`Could not reach the API: ${result.reason}`);
     } catch (e) {
       setValidated(false);
       setError(String(e));
@@ -240,6 +242,7 @@ export function AddProfileForm({ onBack, onAdded }: Props) {
                 placeholder={
                   providerType === "claude" ? "C:\\Users\\...\\.claude"
                   : providerType === "gemini" ? "C:\\Users\\...\\.gemini"
+                  : providerType === "openai" ? "C:\\Users\\...\\.codex"
                   : "%APPDATA%\\zai"
                 }
                 className="flex-1 px-3 py-2 rounded-lg border border-border text-xs text-text placeholder:text-muted/50 outline-none focus:border-border-light transition-colors font-mono"
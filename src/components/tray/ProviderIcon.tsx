@@ -47,6 +47,8 @@ export function ProviderIcon({ type, size = 18 }: Props) {
       return <ClaudeIcon size={size} />;
     case "gemini":
       return <GeminiIcon size={size} />;
+    case "openai":
+      return <DefaultIcon size={size} letter="O" color="var(--provider-openai)" bg="var(--provider-openai-bg)" />;
     default:
       return <DefaultIcon size={size} letter="?" color="#8b8b9e" bg="rgba(139,139,158,0.15)" />;
   }
@@ -1,3 +1,4 @@
+import { useState } from "react";
 import { motion } from "framer-motion";
 import { ArrowLeft, Plus, Trash2, Sun, Moon, Monitor } from "lucide-react";
 import { startManualDrag } from "../../lib/windowState";
@@ -22,6 +23,7 @@ const themes = [
 
 export function SettingsPanel({ profiles, onBack, onAddProfile, onRemoveProfile }: Props) {
   const { settings, update } = useSettings();
+  const [shortcutError, setShortcutError] = useState<string | null>(null);
 
   return (
     <div className="h-full flex flex-col">
@@ -72,6 +74,57 @@ export function SettingsPanel({ profiles, onBack, onAddProfile, onRemoveProfile
           </div>
         </div>
 
+        {/* Window section */}
+        <div>
+          <span className="text-xs font-medium text-text-secondary uppercase tracking-wider mb-2 block">
+            Window
+          </span>
+          <label className="flex items-center justify-between px-3 py-2.5 rounded-lg bg-card border border-border cursor-pointer">
+            <span className="text-xs text-text">Hide when clicking elsewhere</span>
+            <input
+              type="checkbox"
+              checked={settings?.hideOnBlur ?? true}
+              onChange={(e) => {
+                if (!settings) return;
+                update({ ...settings, hideOnBlur: e.target.checked });
+              }}
+            />
+          </label>
+          <label className="mt-1.5 flex items-center justify-between gap-2 px-3 py-2.5 rounded-lg bg-card border border-border">
+            <span className="text-xs text-text shrink-0">Toggle shortcut</span>
+            <input
+              key={settings?.toggleShortcut ?? ""}
+              defaultValue={settings?.toggleShortcut ?? ""}
+              placeholder="CmdOrCtrl+Shift+U"
+              className="min-w-0 flex-1 bg-transparent text-right text-xs text-text outline-none"
+              onBlur={async (e) => {
+                if (!settings) return;
+                const value = e.target.value.trim() || null;
+                if (value === (settings.toggleShortcut ?? null)) return;
+                setShortcutError(await update({ ...settings, toggleShortcut: value }));
+              }}
+            />
+          </label>
+          {shortcutError && (
+            <p className="mt-1 text-[10px] text-danger">{shortcutError}</p>
+          )}
+          <label className="mt-1.5 flex items-center justify-between gap-2 px-3 py-2.5 rounded-lg bg-card border border-border">
+            <span className="text-xs text-text shrink-0">Number format</span>
+            <input
+              key={settings?.locale ?? ""}
+              defaultValue={settings?.locale ?? ""}
+              placeholder="System (e.g. de-DE)"
+              className="min-w-0 flex-1 bg-transparent text-right text-xs text-text outline-none"
+              onBlur={(e) => {
+                if (!settings) return;
+                const value = e.target.value.trim();
+                if (value === settings.locale) return;
+                update({ ...settings, locale: value });
+              }}
+            />
+          </label>
+        </div>
+
         {/* Profiles section */}
         <div>
           <div className="flex items-center justify-between mb-2">
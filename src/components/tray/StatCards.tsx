@@ -1,7 +1,7 @@
 import { useState } from "react";
 import { motion, AnimatePresence } from "framer-motion";
 import { ArrowUpRight, ArrowDownLeft, MessageSquare, DollarSign } from "lucide-react";
-import { formatTokens, formatCost } from "../../lib/format";
+import { formatTokens, formatCost, formatNumber } from "../../lib/format";
 import { providerColors } from "../../lib/colors";
 import type { UsageStats, ProviderType, SourceType } from "../../lib/types";
 
@@ -9,6 +9,7 @@ interface Props {
   stats: UsageStats | null;
   providerType: ProviderType;
   sourceType?: SourceType;
+  locale?: string;
 }
 
 function Tooltip({ text }: { text: string }) {
@@ -26,7 +27,7 @@ function Tooltip({ text }: { text: string }) {
   );
 }
 
-export function StatCards({ stats, providerType, sourceType }: Props) {
+export function StatCards({ stats, providerType, sourceType, locale }: Props) {
   const colors = providerColors[providerType];
   const [hoveredCard, setHoveredCard] = useState<string | null>(null);
 
@@ -57,22 +58,22 @@ export function StatCards({ stats, providerType, sourceType }: Props) {
       },
       {
         label: "Cost",
-        value: stats ? formatCost(stats.estimatedCostUsd) : "\u2014",
+        value: stats ? formatCost(stats.displayCost, stats.displayCurrency || "USD", locale) : "\u2014",
         icon: DollarSign,
         color: "#22c55e",
-        tooltip: "Estimated API usage cost (USD)",
+        tooltip: `Estimated API usage cost (${stats?.displayCurrency || "USD"})`,
       },
     ] : [
       {
         label: "Sessions",
-        value: stats ? stats.totalSessions.toString() : "\u2014",
+        value: stats ? formatNumber(stats.totalSessions, locale) : "\u2014",
         icon: MessageSquare,
         color: "#8b8b9e",
         tooltip: "Total coding sessions",
       },
       {
         label: "Messages",
-        value: stats ? stats.totalMessages.toString() : "\u2014",
+        value: stats ? formatNumber(stats.totalMessages, locale) : "\u2014",
         icon: MessageSquare,
         color: "#8b8b9e",
         tooltip: "Total messages exchanged with AI",
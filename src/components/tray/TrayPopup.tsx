@@ -2,6 +2,7 @@ import { useState, useEffect, useCallback } from "react";
 import { motion, AnimatePresence } from "framer-motion";
 import { Settings, RefreshCw } from "lucide-react";
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 import { getCurrentWindow } from "@tauri-apps/api/window";
 import { PhysicalSize } from "@tauri-apps/api/dpi";
 import { ProviderTabs } from "./ProviderTabs";
@@ -12,7 +13,7 @@ import { WeeklySparkline } from "./WeeklySparkline";
 import { SettingsPanel } from "./SettingsPanel";
 import { AddProfileForm } from "./AddProfileForm";
 import { RateLimits } from "./RateLimits";
-import { useProfiles, useUsageStats, useActiveSessions, useDailyUsage, useRateLimitStatus } from "../../hooks/useProviderData";
+import { useProfiles, useUsageStats, useActiveSessions, useDailyUsage, useRateLimitStatus, useSettings } from "../../hooks/useProviderData";
 import { isDialogOpen, isDragging, startManualDrag } from "../../lib/windowState";
 import type { ProviderType, SourceType } from "../../lib/types";
 
@@ -22,6 +23,13 @@ export function TrayPopup() {
   const { profiles, refresh: refreshProfiles } = useProfiles();
   const [activeProfileId, setActiveProfileId] = useState<string | null>(null);
   const [view, setView] = useState<View>("main");
+  const { settings, refresh: refreshSettings } = useSettings();
+  const hideOnBlur = settings?.hideOnBlur ?? true;
+
+  // Pick up changes made in the settings panel
+  useEffect(() => {
+    if (view === "main") refreshSettings();
+  }, [view, refreshSettings]);
 
   // Auto-select first profile
   useEffect(() => {
@@ -38,6 +46,7 @@ export function TrayPopup() {
 
   // Hide window on blur (debounced to allow drag/dialog interactions)
   useEffect(() => {
+    if (!hideOnBlur) return;
     const win = getCurrentWindow();
     let blurTimeout: ReturnType<typeof setTimeout> | null = null;
 
@@ -55,7 +64,7 @@ export function TrayPopup() {
       unlistenFocus.then(fn => fn());
       if (blurTimeout) clearTimeout(blurTimeout);
     };
-  }, []);
+  }, [hideOnBlur]);
 
   // Dynamic window height based on session count (top-left stays fixed, only height changes)
   useEffect(() => {
@@ -76,7 +85,20 @@ export function TrayPopup() {
     })();
   }, [sessions.length, view]);
 
-  // Auto-refresh every 5 seconds (only on main view)
+  // Refresh as soon as the backend sees the active profile's session files change
+  useEffect(() => {
+    if (view !== "main" || !activeProfileId) return;
+    const unlisten = listen<{ profileId: string }>("usage-updated", (event) => {
+      if (event.payload.profileId !== activeProfileId) return;
+      refreshStats();
+      refreshSessions();
+      refreshDaily();
+    });
+    return () => { unlisten.then(fn => fn()); };
+  }, [view, activeProfileId, refreshStats, refreshSessions, refreshDaily]);
+
+  // Auto-refresh every 5 seconds (only on main view); also the fallback
+  // for profiles whose files can't be watched
   useEffect(() => {
     if (view !== "main") return;
     const interval = setInterval(() => {
@@ -179,7 +201,15 @@ export function TrayPopup() {
               <div className="flex items-center gap-1.5">
                 <button
                   className="p-1.5 rounded-md hover:bg-card-hover transition-colors"
-                  onClick={() => {
+                  onClick={async () => {
+                    // Bypass provider caches so the button always shows fresh numbers
+                    if (activeProfileId) {
+                      try {
+                        await invoke("refresh_provider", { profileId: activeProfileId });
+                      } catch (e) {
+                        console.error("Failed to refresh provider:", e);
+                      }
+                    }
                     refreshStats();
                     refreshSessions();
                     refreshDaily();
@@ -249,10 +279,11 @@ export function TrayPopup() {
                       stats={stats}
                       providerType={(activeProfile.providerType as ProviderType) || "claude"}
                       sourceType={sourceType}
+                      locale={settings?.locale}
                     />
 
                     {/* Active sessions */}
-                    <ActiveSessions sessions={sessions} sourceType={sourceType} />
+                    <ActiveSessions sessions={sessions} sourceType={sourceType} locale={settings?.locale} />
 
                     {/* Weekly sparkline */}
                     <WeeklySparkline
@@ -9,14 +9,17 @@ interface Props {
   providerType: ProviderType;
 }
 
+const dayTotal = (d: DailyUsage) =>
+  d.inputTokens + d.outputTokens + (d.cacheReadTokens ?? 0) + (d.cacheWriteTokens ?? 0);
+
 export function WeeklySparkline({ data, providerType }: Props) {
   const colors = providerColors[providerType];
-  const total = data.reduce((sum, d) => sum + d.inputTokens + d.outputTokens, 0);
+  const total = data.reduce((sum, d) => sum + dayTotal(d), 0);
   const avg = data.length > 0 ? total / data.length : 0;
 
   const chartData = data.map(d => ({
     date: d.date,
-    tokens: d.inputTokens + d.outputTokens,
+    tokens: dayTotal(d),
   }));
 
   return (
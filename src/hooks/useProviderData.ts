@@ -1,6 +1,6 @@
 import { useState, useEffect, useCallback } from "react";
 import { invoke } from "@tauri-apps/api/core";
-import type { Profile, UsageStats, Session, DailyUsage, RateLimitStatus, AppSettings } from "../lib/types";
+import type { Profile, UsageStats, Session, DailyUsage, RateLimitStatus, AppSettings, ProviderError } from "../lib/types";
 
 export function useProfiles() {
   const [profiles, setProfiles] = useState<Profile[]>([]);
@@ -24,22 +24,25 @@ export function useProfiles() {
 
 export function useUsageStats(profileId: string | null) {
   const [stats, setStats] = useState<UsageStats | null>(null);
+  const [error, setError] = useState<ProviderError | null>(null);
   const [loading, setLoading] = useState(true);
 
   const refresh = useCallback(async () => {
-    if (!profileId) { setStats(null); setLoading(false); return; }
+    if (!profileId) { setStats(null); setError(null); setLoading(false); return; }
     try {
       const result = await invoke<UsageStats>("get_usage_stats", { profileId });
       setStats(result);
+      setError(null);
     } catch (e) {
       console.error("Failed to get usage stats:", e);
+      setError(e as ProviderError);
     } finally {
       setLoading(false);
     }
   }, [profileId]);
 
-  useEffect(() => { setStats(null); setLoading(true); refresh(); }, [refresh]);
-  return { stats, loading, refresh };
+  useEffect(() => { setStats(null); setError(null); setLoading(true); refresh(); }, [refresh]);
+  return { stats, error, loading, refresh };
 }
 
 export function useActiveSessions(profileId: string | null) {
@@ -125,12 +128,15 @@ export function useSettings() {
     }
   }, []);
 
-  const update = useCallback(async (newSettings: AppSettings) => {
+  // Resolves to the error message when the backend rejects the change
+  const update = useCallback(async (newSettings: AppSettings): Promise<string | null> => {
     try {
       await invoke("update_settings", { settings: newSettings });
       setSettings(newSettings);
+      return null;
     } catch (e) {
       console.error("Failed to update settings:", e);
+      return String(e);
     }
   }, []);
 
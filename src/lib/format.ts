@@ -5,10 +5,27 @@ export function formatTokens(n: number): string {
   return n.toString();
 }
 
-export function formatCost(usd: number): string {
-  if (usd >= 100) return `$${usd.toFixed(0)}`;
-  if (usd >= 1) return `$${usd.toFixed(2)}`;
-  return `$${usd.toFixed(3)}`;
+// An empty locale follows the system; an invalid one falls back to plain formatting
+export function formatNumber(n: number, locale?: string): string {
+  try {
+    return n.toLocaleString(locale || undefined);
+  } catch {
+    return n.toString();
+  }
+}
+
+export function formatCost(amount: number, currency = "USD", locale?: string): string {
+  const digits = amount >= 100 ? 0 : amount >= 1 ? 2 : 3;
+  try {
+    return new Intl.NumberFormat(locale || undefined, {
+      style: "currency",
+      currency,
+      minimumFractionDigits: digits,
+      maximumFractionDigits: digits,
+    }).format(amount);
+  } catch {
+    return `${amount.toFixed(digits)} ${currency}`;
+  }
 }
 
 export function formatTimeAgo(isoDate: string): string {
@@ -1,11 +1,11 @@
-export type ProviderType = "claude" | "gemini" | "zai";
+export type ProviderType = "claude" | "gemini" | "openai" | "zai";
 export type SourceType = "account" | "api";
 
 /** Providers that support API source type */
 export const apiSupportedProviders: ProviderType[] = ["claude"];  // zai temporarily disabled
 
 /** Providers that support account (local folder) source type */
-export const accountSupportedProviders: ProviderType[] = ["claude", "gemini"];
+export const accountSupportedProviders: ProviderType[] = ["claude", "gemini", "openai"];
 
 export interface Profile {
   id: string;
@@ -15,6 +15,46 @@ export interface Profile {
   enabled: boolean;
   sourceType: SourceType;
   hasApiKey: boolean;
+  dailyTokenBudget: number | null;
+  projectsSubdir: string;
+  configDirs: string[];
+}
+
+export interface BudgetStatus {
+  usedToday: number;
+  budget: number | null;
+  remaining: number | null;
+  exceeded: boolean;
+}
+
+export type KeyValidation =
+  | { status: "valid" }
+  | { status: "invalid" }
+  | { status: "unreachable"; reason: string };
+
+export interface ProbeResult {
+  ok: boolean;
+  detail: string;
+}
+
+export interface HealthStatus {
+  healthy: boolean;
+  message: string;
+  checkedAt: string;
+}
+
+/** Error returned by provider-backed commands */
+export type ProviderErrorCode =
+  | "unauthorized"
+  | "rate_limited"
+  | "network"
+  | "parse"
+  | "not_configured"
+  | "other";
+
+export interface ProviderError {
+  code: ProviderErrorCode;
+  message: string;
 }
 
 export interface UsageStats {
@@ -27,25 +67,64 @@ export interface UsageStats {
   totalMessages: number;
   estimatedCostUsd: number;
   modelBreakdown: Record<string, ModelUsage>;
+  displayCost: number;
+  displayCurrency: string;
+  reportedCost: number | null;
+  reportedCurrency: string;
+  serviceTierTokens: Record<string, number>;
+  billingMode: BillingMode;
+  equivalentApiCostUsd: number;
+  totalToolCalls: number;
+  cacheHitRatio: number;
 }
 
+export type BillingMode = "per_token" | "subscription";
+
 export interface ModelUsage {
   model: string;
   inputTokens: number;
   outputTokens: number;
   cacheReadTokens: number;
   cacheWriteTokens: number;
+  cacheWrite1hTokens: number;
   costUsd: number;
+  displayCost: number;
+  cacheHitRatio: number;
 }
 
 export interface Session {
   id: string;
+  provider: string;
+  profileId: string;
   project: string;
+  projectPath: string;
+  projectDirEncoded: string;
   model: string;
   tokensUsed: number;
   lastActive: string;
   isActive: boolean;
   messageCount: number;
+  costUsd: number;
+}
+
+export interface ProjectUsage {
+  project: string;
+  projectPath: string;
+  tokens: number;
+  messages: number;
+  costUsd: number;
+  sessions: number;
+}
+
+export interface WorkspaceUsage {
+  workspaceId: string | null;
+  workspaceName: string;
+  apiKeyId: string | null;
+  inputTokens: number;
+  outputTokens: number;
+  cacheReadTokens: number;
+  cacheWriteTokens: number;
+  costUsd: number;
 }
 
 export interface DailyUsage {
@@ -54,6 +133,44 @@ export interface DailyUsage {
   outputTokens: number;
   sessions: number;
   messages: number;
+  cacheReadTokens: number;
+  cacheWriteTokens: number;
+  toolCalls: number;
+}
+
+export interface DailyModelUsage {
+  date: string;
+  model: string;
+  inputTokens: number;
+  outputTokens: number;
+  cacheReadTokens: number;
+  cacheWriteTokens: number;
+  costUsd: number;
+}
+
+export interface Dashboard {
+  stats: UsageStats;
+  active: Session[];
+  daily: DailyUsage[];
+  history: Session[];
+}
+
+export interface UsageRollup {
+  periodLabel: string;
+  periodStart: string;
+  inputTokens: number;
+  outputTokens: number;
+  cacheReadTokens: number;
+  cacheWriteTokens: number;
+  costUsd: number;
+  sessions: number;
+  messages: number;
+}
+
+export interface CostForecast {
+  monthToDate: number;
+  projectedTotal: number;
+  dailyAverage: number;
 }
 
 export interface RateLimitWindow {
@@ -67,6 +184,9 @@ export interface RateLimitStatus {
   fiveHour: RateLimitWindow | null;
   sevenDay: RateLimitWindow | null;
   sevenDayOpus: RateLimitWindow | null;
+  windowStartedAt: string | null;
+  sessionsInWindow: number | null;
+  messagesInWindow: number | null;
 }
 
 export interface AppSettings {
@@ -75,4 +195,70 @@ export interface AppSettings {
   launchOnStartup: boolean;
   notificationsEnabled: boolean;
   tokenAlertThreshold: number;
+  displayCurrency: string;
+  locale: string;
+  fxRateUsdToDisplay: number;
+  fxRateUrl?: string | null;
+  excludedModels: string[];
+  excludedProjects: string[];
+  cacheCountsAsInput: boolean;
+  debugMode: boolean;
+  activeThresholdSecs: number;
+  proxyUrl?: string | null;
+  apiCacheTtlSecs: number;
+  windowX?: number | null;
+  windowY?: number | null;
+  hideOnBlur: boolean;
+  toggleShortcut?: string | null;
+}
+
+export interface LogEntry {
+  timestamp: string;
+  level: string;
+  target: string;
+  message: string;
+}
+
+export interface FileStatus {
+  path: string;
+  exists: boolean;
+  readable: boolean;
+}
+
+export interface FetchStatus {
+  succeeded: boolean;
+  at: string;
+  error: string | null;
+}
+
+export interface Diagnostics {
+  configDir: string;
+  configDirExists: boolean;
+  sessionFiles: number | null;
+  files: FileStatus[];
+  lastFetch: FetchStatus | null;
+  skippedLines: number | null;
+}
+
+export interface ImportSummary {
+  added: number;
+  skipped: number;
+  skipReasons: string[];
+}
+
+/** USD per million tokens. */
+export interface PricingRule {
+  pattern: string;
+  input: number;
+  output: number;
+  cacheRead: number;
+  cacheWrite: number;
+  cacheWrite1h: number | null;
+}
+
+export interface PricingTable {
+  claude: PricingRule[];
+  gemini: PricingRule[];
+  openai: PricingRule[];
+  zai: PricingRule[];
 }